@@ -4,10 +4,29 @@ pub struct Editor {
     broker: Broker,
     client: ClientHandle,
     selected: Option<petgraph::graph::NodeIndex>,
+    /// The node under the cursor, read back from the renderer's id buffer
+    hovered: Option<petgraph::graph::NodeIndex>,
     console_history: Vec<String>,
     console_command: String,
     toasts: egui_toast::Toasts,
     gizmo_mode: egui_gizmo::GizmoMode,
+    scene_bvh: serenity::bvh::SceneBvh,
+    /// The running physics simulation and the scene to restore when it stops
+    simulation: Option<(serenity::physics::PhysicsWorld, serenity::scene::Scene)>,
+    /// Applied to the renderer every frame
+    post_effects: Vec<serenity::postprocess::PostEffect>,
+    show_post_processing: bool,
+    show_environment: bool,
+    show_grid: bool,
+    /// Shows orthographic views from the top, front and side next to the active camera
+    quad_view: bool,
+    view_mode: serenity::view::ViewMode,
+    outline_thickness: u32,
+    /// The size in pixels of the panel the scene is rendered into
+    viewport_size: nalgebra_glm::Vec2,
+    viewport_texture: Option<egui::TextureId>,
+    /// The cursor position in viewport pixels while it hovers the viewport
+    viewport_cursor: Option<nalgebra_glm::Vec2>,
 }
 
 impl Editor {
@@ -20,12 +39,46 @@ impl Editor {
             broker,
             client,
             selected: None,
+            hovered: None,
             console_history: vec!["Welcome to the Serenity editor!".to_string()],
             console_command: "Type /help for more commands.".to_string(),
             toasts: egui_toast::Toasts::new()
                 .anchor(egui::Align2::RIGHT_BOTTOM, (-10.0, -10.0))
                 .direction(egui::Direction::BottomUp),
             gizmo_mode: egui_gizmo::GizmoMode::Translate,
+            scene_bvh: serenity::bvh::SceneBvh::default(),
+            simulation: None,
+            post_effects: serenity::postprocess::default_effects(),
+            show_post_processing: false,
+            show_environment: false,
+            show_grid: true,
+            quad_view: false,
+            view_mode: serenity::view::ViewMode::default(),
+            outline_thickness: 3,
+            viewport_size: nalgebra_glm::vec2(1.0, 1.0),
+            viewport_texture: None,
+            viewport_cursor: None,
+        }
+    }
+
+    /// Rebuilds the renderer's pipelines when a shader file changes on disk,
+    /// showing compile errors while the last working shader keeps rendering
+    fn reload_shaders(&mut self, renderer: &mut serenity::render::Renderer) {
+        match renderer.view.reload_shaders(&renderer.gpu) {
+            Ok(true) => self.broker.publish(
+                &Topic::Toast.to_string(),
+                Message::Toast(format!("Reloaded {}", renderer.view.shader_file.path)),
+            ),
+            Ok(false) => {}
+            Err(error) => {
+                self.toasts.add(egui_toast::Toast {
+                    text: error.to_string().into(),
+                    kind: egui_toast::ToastKind::Error,
+                    options: egui_toast::ToastOptions::default()
+                        .duration_in_seconds(10.0)
+                        .show_progress(true),
+                });
+            }
         }
     }
 
@@ -41,6 +94,70 @@ impl Editor {
         );
     }
 
+    fn publish_screenshot_command(&mut self, path: &str) {
+        self.broker.publish(
+            &Topic::Command.to_string(),
+            Message::Command(Command::Screenshot(path.to_string())),
+        );
+    }
+
+    fn publish_simulation_command(&mut self, running: bool) {
+        let command = if running {
+            Command::StartSimulation
+        } else {
+            Command::StopSimulation
+        };
+        self.broker
+            .publish(&Topic::Command.to_string(), Message::Command(command));
+    }
+
+    /// Selects the hovered node, which is picked exactly from the id buffer,
+    /// falling back to raycasting the scene when nothing was read back under the cursor
+    fn select_under_mouse(&mut self, context: &serenity::app::Context) {
+        if let Some(hovered) = self.hovered {
+            self.selected = Some(hovered);
+            return;
+        }
+        let Some(cursor) = self.viewport_cursor else {
+            return;
+        };
+        // The topmost view under the cursor is the one clicked in
+        let (width, height) = (self.viewport_size.x as u32, self.viewport_size.y as u32);
+        let Some((camera_view, rect)) = self
+            .camera_views(&context.scene)
+            .into_iter()
+            .map(|camera_view| {
+                let rect = camera_view.viewport.pixel_rect(width, height);
+                (camera_view, rect)
+            })
+            .filter(|(_, rect)| rect.contains(cursor.x as u32, cursor.y as u32))
+            .last()
+        else {
+            return;
+        };
+        let (_camera_position, projection, view) = camera_view.matrices(rect.aspect_ratio());
+        let Some(ray) = serenity::geometry::Ray::from_screen(
+            cursor - nalgebra_glm::vec2(rect.x as f32, rect.y as f32),
+            nalgebra_glm::vec2(rect.width as f32, rect.height as f32),
+            &projection,
+            &view,
+        ) else {
+            return;
+        };
+        if let Some(hit) = self.scene_bvh.raycast(&context.scene, &ray) {
+            self.selected = Some(hit.node_index);
+        }
+    }
+
+    /// The views the renderer draws, which are either the quad view or the scene's cameras
+    fn camera_views(&self, scene: &serenity::scene::Scene) -> Vec<serenity::view::CameraView> {
+        if self.quad_view {
+            quad_views(scene, self.viewport_size.x / self.viewport_size.y.max(1.0))
+        } else {
+            serenity::view::camera_views(scene)
+        }
+    }
+
     fn receive_messages(
         &mut self,
         context: &mut serenity::app::Context,
@@ -55,13 +172,59 @@ impl Editor {
                     Command::ImportGltfFile(path) => {
                         context.scene = serenity::gltf::import_gltf(&path).clone();
                         if !context.scene.has_camera() {
-                            context
-                                .scene
-                                .add_root_node(serenity::scene::create_camera_node(
-                                    renderer.gpu.aspect_ratio(),
-                                ));
+                            let mut camera_node = serenity::scene::create_camera_node(1.0);
+                            // The editor camera follows the aspect ratio of the viewport panel
+                            camera_node.components.iter_mut().for_each(|component| {
+                                if let serenity::scene::NodeComponent::Camera(
+                                    serenity::scene::Camera {
+                                        projection:
+                                            serenity::scene::Projection::Perspective(perspective),
+                                        ..
+                                    },
+                                ) = component
+                                {
+                                    perspective.aspect_ratio = None;
+                                }
+                            });
+                            context.scene.add_root_node(camera_node);
                         }
                         renderer.view.import_scene(&context.scene, &renderer.gpu);
+                        self.scene_bvh =
+                            serenity::bvh::SceneBvh::with_triangle_bvhs(&context.scene);
+                        self.simulation = None;
+                        self.selected = None;
+                        self.hovered = None;
+                    }
+                    Command::Screenshot(path) => {
+                        let Some(camera) = serenity::view::active_camera(&context.scene) else {
+                            self.broker.publish(
+                                &Topic::Toast.to_string(),
+                                Message::Toast("The scene has no camera to capture".to_string()),
+                            );
+                            continue;
+                        };
+                        // Capture what the viewport panel shows, at its size
+                        let (width, height) = renderer.viewport_size();
+                        let image = renderer.render_to_image(&context.scene, camera, width, height);
+                        let message = match image.save(&path) {
+                            Ok(()) => format!("Saved screenshot to {path}"),
+                            Err(error) => format!("Failed to save screenshot: {error}"),
+                        };
+                        self.broker
+                            .publish(&Topic::Toast.to_string(), Message::Toast(message));
+                    }
+                    Command::StartSimulation => {
+                        if self.simulation.is_none() {
+                            self.simulation = Some((
+                                serenity::physics::PhysicsWorld::from_scene(&context.scene),
+                                context.scene.clone(),
+                            ));
+                        }
+                    }
+                    Command::StopSimulation => {
+                        if let Some((_physics, scene)) = self.simulation.take() {
+                            context.scene = scene;
+                        }
                     }
                 },
                 Message::Toast(message) => {
@@ -112,47 +275,45 @@ impl serenity::app::State for Editor {
         renderer: &mut serenity::render::Renderer,
     ) {
         self.receive_messages(context, renderer);
+        self.reload_shaders(renderer);
         camera_system(context);
+        if let Some((physics, _scene)) = self.simulation.as_mut() {
+            physics.update(&mut context.scene, context.delta_time);
+        }
+        self.scene_bvh.refit(&context.scene);
+        renderer.post_process.effects = self.post_effects.clone();
+        renderer.view.debug.show_grid = self.show_grid;
+        renderer.camera_views = self.quad_view.then(|| self.camera_views(&context.scene));
+        renderer.view.view_mode = self.view_mode;
+        renderer.outline.selected = self.selected;
+        renderer.outline.hovered = self.hovered;
+        renderer.outline.thickness = self.outline_thickness;
+        self.viewport_texture = Some(renderer.viewport_texture(
+            self.viewport_size.x.round() as u32,
+            self.viewport_size.y.round() as u32,
+        ));
+        match self.viewport_cursor {
+            Some(cursor) => {
+                if let Some(pick) = renderer.pick(cursor.x as u32, cursor.y as u32) {
+                    // Picks read back after importing a scene may belong to the previous one
+                    self.hovered = pick
+                        .node_index
+                        .filter(|node_index| node_index.index() < context.scene.graph.node_count());
+                }
+            }
+            None => self.hovered = None,
+        }
+        if let Some(bounds) = self
+            .selected
+            .and_then(|selected| context.scene.world_bounds(selected))
+        {
+            context
+                .debug
+                .aabb(&bounds, nalgebra_glm::vec4(1.0, 0.6, 0.1, 1.0), 0.0);
+        }
     }
 
     fn ui(&mut self, context: &mut serenity::app::Context, ui_context: &mut egui::Context) {
-        egui::Area::new("viewport").show(ui_context, |ui| {
-            let window_size = context.window.inner_size();
-            let aspect_ratio = window_size.width as f32 / window_size.height.max(1) as f32;
-            let (_camera_position, projection, view) =
-                serenity::view::create_camera_matrices(&context.scene, aspect_ratio)
-                    .unwrap_or_default();
-            ui.with_layer_id(egui::LayerId::background(), |ui| {
-                if let Some(selected) = self.selected {
-                    let node = &mut context.scene.graph[selected];
-                    let model_matrix = node.transform.matrix();
-                    let gizmo = egui_gizmo::Gizmo::new("My gizmo")
-                        .view_matrix(view)
-                        .projection_matrix(projection)
-                        .model_matrix(model_matrix)
-                        .mode(self.gizmo_mode);
-                    if let Some(response) = gizmo.interact(ui) {
-                        node.transform.translation = nalgebra_glm::Vec3::new(
-                            response.translation.x,
-                            response.translation.y,
-                            response.translation.z,
-                        );
-                        node.transform.rotation = nalgebra_glm::quat(
-                            response.rotation.x,
-                            response.rotation.y,
-                            response.rotation.z,
-                            response.rotation.w,
-                        );
-                        node.transform.scale = nalgebra_glm::Vec3::new(
-                            response.scale.x,
-                            response.scale.y,
-                            response.scale.z,
-                        );
-                    }
-                }
-            });
-        });
-
         egui::TopBottomPanel::top("top_panel")
             .resizable(true)
             .show(ui_context, |ui| {
@@ -168,6 +329,30 @@ impl serenity::app::State for Editor {
                                 ui.close_menu();
                             }
                         }
+                        if ui.button("Save screenshot...").clicked() {
+                            if let Some(path) = rfd::FileDialog::new()
+                                .add_filter("PNG", &["png"])
+                                .set_file_name("screenshot.png")
+                                .save_file()
+                            {
+                                self.publish_screenshot_command(&path.display().to_string());
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    ui.menu_button("View", |ui| {
+                        ui.checkbox(&mut self.show_post_processing, "Post processing");
+                        ui.checkbox(&mut self.show_environment, "Environment");
+                        ui.checkbox(&mut self.show_grid, "Grid");
+                        ui.checkbox(&mut self.quad_view, "Quad view");
+                        ui.add(
+                            egui::Slider::new(
+                                &mut self.outline_thickness,
+                                1..=serenity::outline::MAX_THICKNESS,
+                            )
+                            .text("Outline"),
+                        );
+                        bloom_toggle_ui(ui, &mut self.post_effects);
                     });
                     ui.separator();
                     ui.horizontal(|ui| {
@@ -183,9 +368,43 @@ impl serenity::app::State for Editor {
                             self.gizmo_mode = egui_gizmo::GizmoMode::Scale;
                         }
                     });
+                    ui.separator();
+                    egui::ComboBox::from_id_source("view_mode")
+                        .selected_text(self.view_mode.name())
+                        .show_ui(ui, |ui| {
+                            serenity::view::ViewMode::ALL
+                                .into_iter()
+                                .for_each(|view_mode| {
+                                    ui.selectable_value(
+                                        &mut self.view_mode,
+                                        view_mode,
+                                        view_mode.name(),
+                                    );
+                                });
+                        });
+                    ui.separator();
+                    if self.simulation.is_some() {
+                        if ui.button("Stop").clicked() {
+                            self.publish_simulation_command(false);
+                        }
+                    } else if ui.button("Simulate").clicked() {
+                        self.publish_simulation_command(true);
+                    }
                 });
             });
 
+        egui::Window::new("Post Processing")
+            .open(&mut self.show_post_processing)
+            .show(ui_context, |ui| {
+                post_effects_ui(ui, &mut self.post_effects);
+            });
+
+        egui::Window::new("Environment")
+            .open(&mut self.show_environment)
+            .show(ui_context, |ui| {
+                environment_ui(ui, &mut context.scene.environment);
+            });
+
         egui::SidePanel::left("left_panel")
             .resizable(true)
             .show(ui_context, |ui| {
@@ -213,22 +432,128 @@ impl serenity::app::State for Editor {
                     .id_source(ui.next_auto_id())
                     .show(ui, |ui| {
                         if let Some(selected) = self.selected {
-                            let node = &mut context.scene.graph[selected];
+                            let serenity::scene::Scene { graph, meshes, .. } = &mut context.scene;
+                            let node = &mut graph[selected];
                             egui::ScrollArea::vertical()
                                 .id_source(ui.next_auto_id())
                                 .show(ui, |ui| {
-                                    for component in node.components.iter_mut() {
+                                    let mut removed_component = None;
+                                    for (component_index, component) in
+                                        node.components.iter_mut().enumerate()
+                                    {
                                         ui.group(|ui| match component {
-                                            serenity::scene::NodeComponent::Camera(_) => {
+                                            serenity::scene::NodeComponent::Camera(camera) => {
                                                 ui.heading("Camera");
+                                                camera_post_effects_ui(
+                                                    ui,
+                                                    &mut camera.post_effects,
+                                                    &self.post_effects,
+                                                );
                                             }
                                             serenity::scene::NodeComponent::Mesh(_) => {
                                                 ui.heading("Mesh");
                                             }
-                                            serenity::scene::NodeComponent::Light(_) => {
+                                            serenity::scene::NodeComponent::Light(light) => {
                                                 ui.heading("Light");
+                                                shadow_settings_ui(ui, &mut light.shadow);
+                                            }
+                                            serenity::scene::NodeComponent::Collider(collider) => {
+                                                ui.horizontal(|ui| {
+                                                    ui.heading("Collider");
+                                                    if ui.button("Remove").clicked() {
+                                                        removed_component = Some(component_index);
+                                                    }
+                                                });
+                                                collider_ui(ui, collider);
                                             }
+                                            serenity::scene::NodeComponent::RigidBody(
+                                                rigid_body,
+                                            ) => {
+                                                ui.horizontal(|ui| {
+                                                    ui.heading("Rigid Body");
+                                                    if ui.button("Remove").clicked() {
+                                                        removed_component = Some(component_index);
+                                                    }
+                                                });
+                                                rigid_body_ui(ui, rigid_body);
+                                            }
+                                            serenity::scene::NodeComponent::CharacterController(
+                                                controller,
+                                            ) => {
+                                                ui.horizontal(|ui| {
+                                                    ui.heading("Character Controller");
+                                                    if ui.button("Remove").clicked() {
+                                                        removed_component = Some(component_index);
+                                                    }
+                                                });
+                                                character_controller_ui(ui, controller);
+                                            }
+                                        });
+                                    }
+                                    if let Some(component_index) = removed_component {
+                                        node.components.remove(component_index);
+                                    }
+
+                                    let node_mesh =
+                                        node.components.iter().find_map(
+                                            |component| match component {
+                                                serenity::scene::NodeComponent::Mesh(mesh_id) => {
+                                                    meshes.get(mesh_id)
+                                                }
+                                                _ => None,
+                                            },
+                                        );
+                                    if let Some(shape) = add_collider_ui(ui, node_mesh) {
+                                        match shape {
+                                            Some(shape) => node.components.push(
+                                                serenity::scene::NodeComponent::Collider(
+                                                    serenity::scene::Collider {
+                                                        shape,
+                                                        ..Default::default()
+                                                    },
+                                                ),
+                                            ),
+                                            None => self.broker.publish(
+                                                &Topic::Toast.to_string(),
+                                                Message::Toast(
+                                                    "Failed to compute a convex hull for the mesh"
+                                                        .to_string(),
+                                                ),
+                                            ),
+                                        }
+                                    }
+
+                                    let has_rigid_body = node.components.iter().any(|component| {
+                                        matches!(
+                                            component,
+                                            serenity::scene::NodeComponent::RigidBody(_)
+                                        )
+                                    });
+                                    if !has_rigid_body && ui.button("Add Rigid Body").clicked() {
+                                        node.components.push(
+                                            serenity::scene::NodeComponent::RigidBody(
+                                                serenity::scene::RigidBody::default(),
+                                            ),
+                                        );
+                                    }
+
+                                    let has_character_controller =
+                                        node.components.iter().any(|component| {
+                                            matches!(
+                                                component,
+                                                serenity::scene::NodeComponent::CharacterController(
+                                                    _
+                                                )
+                                            )
                                         });
+                                    if !has_character_controller
+                                        && ui.button("Add Character Controller").clicked()
+                                    {
+                                        node.components.push(
+                                            serenity::scene::NodeComponent::CharacterController(
+                                                serenity::scene::CharacterController::default(),
+                                            ),
+                                        );
                                     }
                                 });
                         }
@@ -269,6 +594,96 @@ impl serenity::app::State for Editor {
                 });
             });
 
+        // The viewport fills the space left over by the panels, so it must be shown last
+        egui::CentralPanel::default()
+            .frame(egui::Frame::none())
+            .show(ui_context, |ui| {
+                let rect = ui.max_rect();
+                let pixels_per_point = ui.ctx().pixels_per_point();
+                self.viewport_size =
+                    nalgebra_glm::vec2(rect.width(), rect.height()) * pixels_per_point;
+                let response = ui.allocate_rect(rect, egui::Sense::click());
+                if let Some(texture_id) = self.viewport_texture {
+                    ui.painter().image(
+                        texture_id,
+                        rect,
+                        egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                        egui::Color32::WHITE,
+                    );
+                }
+                self.viewport_cursor = response.hover_pos().map(|position| {
+                    let position = (position - rect.min) * pixels_per_point;
+                    nalgebra_glm::vec2(position.x, position.y)
+                });
+                viewport_camera_ui(ui, &response, context);
+
+                if self.quad_view {
+                    let stroke = ui.visuals().widgets.noninteractive.bg_stroke;
+                    ui.painter().vline(rect.center().x, rect.y_range(), stroke);
+                    ui.painter().hline(rect.x_range(), rect.center().y, stroke);
+                }
+
+                // Gizmos and collider wireframes are drawn in the first view,
+                // which is the active camera's unless it renders into its own viewport
+                let Some(camera_view) = self.camera_views(&context.scene).into_iter().next() else {
+                    return;
+                };
+                let view_rect = egui::Rect::from_min_size(
+                    rect.min
+                        + egui::vec2(
+                            camera_view.viewport.x * rect.width(),
+                            camera_view.viewport.y * rect.height(),
+                        ),
+                    egui::vec2(
+                        camera_view.viewport.width * rect.width(),
+                        camera_view.viewport.height * rect.height(),
+                    ),
+                );
+                let (_camera_position, projection, view) =
+                    camera_view.matrices(view_rect.width() / view_rect.height().max(1.0));
+                collider_wireframes_ui(
+                    ui,
+                    view_rect,
+                    &context.scene,
+                    &(projection * view),
+                    self.selected,
+                );
+                let mut gizmo_active = false;
+                if let Some(selected) = self.selected {
+                    let node = &mut context.scene.graph[selected];
+                    let model_matrix = node.transform.matrix();
+                    let gizmo = egui_gizmo::Gizmo::new("My gizmo")
+                        .view_matrix(view)
+                        .projection_matrix(projection)
+                        .model_matrix(model_matrix)
+                        .viewport(view_rect)
+                        .mode(self.gizmo_mode);
+                    if let Some(response) = gizmo.interact(ui) {
+                        gizmo_active = true;
+                        node.transform.translation = nalgebra_glm::Vec3::new(
+                            response.translation.x,
+                            response.translation.y,
+                            response.translation.z,
+                        );
+                        node.transform.rotation = nalgebra_glm::quat(
+                            response.rotation.x,
+                            response.rotation.y,
+                            response.rotation.z,
+                            response.rotation.w,
+                        );
+                        node.transform.scale = nalgebra_glm::Vec3::new(
+                            response.scale.x,
+                            response.scale.y,
+                            response.scale.z,
+                        );
+                    }
+                }
+
+                if !gizmo_active && response.clicked() {
+                    self.select_under_mouse(context);
+                }
+            });
+
         self.toasts.show(ui_context);
     }
 }
@@ -317,8 +732,505 @@ fn node_header_ui(
     }
 }
 
+fn collider_ui(ui: &mut egui::Ui, collider: &mut serenity::scene::Collider) {
+    match &mut collider.shape {
+        serenity::scene::ColliderShape::Sphere { radius } => {
+            ui.horizontal(|ui| {
+                ui.label("Sphere radius");
+                ui.add(
+                    egui::DragValue::new(radius)
+                        .speed(0.01)
+                        .clamp_range(0.0..=f32::MAX),
+                );
+            });
+        }
+        serenity::scene::ColliderShape::Box {
+            half_extents,
+            center,
+        } => {
+            ui.horizontal(|ui| {
+                ui.label("Box half extents");
+                for extent in half_extents.iter_mut() {
+                    ui.add(
+                        egui::DragValue::new(extent)
+                            .speed(0.01)
+                            .clamp_range(0.0..=f32::MAX),
+                    );
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Box center");
+                for coordinate in center.iter_mut() {
+                    ui.add(egui::DragValue::new(coordinate).speed(0.01));
+                }
+            });
+        }
+        serenity::scene::ColliderShape::Capsule {
+            half_height,
+            radius,
+        } => {
+            ui.horizontal(|ui| {
+                ui.label("Capsule half height");
+                ui.add(
+                    egui::DragValue::new(half_height)
+                        .speed(0.01)
+                        .clamp_range(0.0..=f32::MAX),
+                );
+                ui.label("radius");
+                ui.add(
+                    egui::DragValue::new(radius)
+                        .speed(0.01)
+                        .clamp_range(0.0..=f32::MAX),
+                );
+            });
+        }
+        serenity::scene::ColliderShape::ConvexHull { points, indices } => {
+            ui.label(format!(
+                "Convex hull with {} points and {} faces",
+                points.len(),
+                indices.len()
+            ));
+        }
+        serenity::scene::ColliderShape::TriangleMesh { vertices, indices } => {
+            ui.label(format!(
+                "Triangle mesh with {} vertices and {} triangles",
+                vertices.len(),
+                indices.len()
+            ));
+        }
+    }
+    ui.checkbox(&mut collider.is_trigger, "Trigger");
+    collision_layers_ui(ui, "Layers", &mut collider.layers);
+    collision_layers_ui(ui, "Mask", &mut collider.mask);
+}
+
+fn rigid_body_ui(ui: &mut egui::Ui, rigid_body: &mut serenity::scene::RigidBody) {
+    ui.horizontal(|ui| {
+        ui.label("Kind");
+        egui::ComboBox::from_id_source(ui.next_auto_id())
+            .selected_text(format!("{:?}", rigid_body.kind))
+            .show_ui(ui, |ui| {
+                [
+                    serenity::scene::RigidBodyKind::Dynamic,
+                    serenity::scene::RigidBodyKind::Kinematic,
+                    serenity::scene::RigidBodyKind::Static,
+                ]
+                .into_iter()
+                .for_each(|kind| {
+                    ui.selectable_value(&mut rigid_body.kind, kind, format!("{kind:?}"));
+                });
+            });
+    });
+    egui::Grid::new(ui.next_auto_id()).show(ui, |ui| {
+        [
+            ("Mass", &mut rigid_body.mass),
+            ("Linear damping", &mut rigid_body.linear_damping),
+            ("Angular damping", &mut rigid_body.angular_damping),
+            ("Restitution", &mut rigid_body.restitution),
+            ("Friction", &mut rigid_body.friction),
+        ]
+        .into_iter()
+        .for_each(|(label, value)| {
+            ui.label(label);
+            ui.add(
+                egui::DragValue::new(value)
+                    .speed(0.01)
+                    .clamp_range(0.0..=f32::MAX),
+            );
+            ui.end_row();
+        });
+    });
+}
+
+fn character_controller_ui(
+    ui: &mut egui::Ui,
+    controller: &mut serenity::scene::CharacterController,
+) {
+    egui::Grid::new(ui.next_auto_id()).show(ui, |ui| {
+        [
+            ("Radius", &mut controller.radius),
+            ("Half height", &mut controller.half_height),
+            ("Move speed", &mut controller.move_speed),
+            ("Jump speed", &mut controller.jump_speed),
+            ("Gravity", &mut controller.gravity),
+            ("Step height", &mut controller.step_height),
+            ("Snap to ground", &mut controller.snap_to_ground),
+        ]
+        .into_iter()
+        .for_each(|(label, value)| {
+            ui.label(label);
+            ui.add(
+                egui::DragValue::new(value)
+                    .speed(0.01)
+                    .clamp_range(0.0..=f32::MAX),
+            );
+            ui.end_row();
+        });
+        [
+            ("Max climb angle", &mut controller.max_slope_climb_angle),
+            ("Min slide angle", &mut controller.min_slope_slide_angle),
+        ]
+        .into_iter()
+        .for_each(|(label, value)| {
+            ui.label(label);
+            ui.drag_angle(value);
+            ui.end_row();
+        });
+    });
+}
+
+fn shadow_settings_ui(ui: &mut egui::Ui, shadow: &mut serenity::scene::ShadowSettings) {
+    ui.checkbox(&mut shadow.enabled, "Cast shadows");
+    ui.add_enabled_ui(shadow.enabled, |ui| {
+        egui::Grid::new(ui.next_auto_id()).show(ui, |ui| {
+            ui.label("Bias");
+            ui.add(
+                egui::DragValue::new(&mut shadow.bias)
+                    .speed(0.0001)
+                    .clamp_range(0.0..=0.1),
+            );
+            ui.end_row();
+            ui.label("Normal bias");
+            ui.add(
+                egui::DragValue::new(&mut shadow.normal_bias)
+                    .speed(0.01)
+                    .clamp_range(0.0..=10.0),
+            );
+            ui.end_row();
+            ui.label("Distance");
+            ui.add(
+                egui::DragValue::new(&mut shadow.distance)
+                    .speed(0.1)
+                    .clamp_range(0.1..=f32::MAX),
+            );
+            ui.end_row();
+            ui.label("Resolution");
+            egui::ComboBox::from_id_source(ui.next_auto_id())
+                .selected_text(shadow.resolution.to_string())
+                .show_ui(ui, |ui| {
+                    [512, 1024, 2048, 4096].into_iter().for_each(|resolution| {
+                        ui.selectable_value(
+                            &mut shadow.resolution,
+                            resolution,
+                            resolution.to_string(),
+                        );
+                    });
+                });
+            ui.end_row();
+        });
+    });
+}
+
+fn environment_ui(ui: &mut egui::Ui, environment: &mut Option<serenity::scene::Environment>) {
+    let mut enabled = environment.is_some();
+    if ui.checkbox(&mut enabled, "Enabled").changed() {
+        *environment = enabled.then(serenity::scene::Environment::default);
+    }
+    if let Some(environment) = environment.as_mut() {
+        egui::Grid::new(ui.next_auto_id()).show(ui, |ui| {
+            ui.label("Path");
+            ui.text_edit_singleline(&mut environment.path);
+            ui.end_row();
+            ui.label("Intensity");
+            ui.add(
+                egui::DragValue::new(&mut environment.intensity)
+                    .speed(0.01)
+                    .clamp_range(0.0..=f32::MAX),
+            );
+            ui.end_row();
+        });
+        ui.checkbox(&mut environment.skybox, "Skybox");
+    }
+}
+
+/// Adds bloom before the first effect expecting tone mapped input, or removes it
+fn bloom_toggle_ui(ui: &mut egui::Ui, effects: &mut Vec<serenity::postprocess::PostEffect>) {
+    let is_bloom = |effect: &serenity::postprocess::PostEffect| {
+        matches!(effect, serenity::postprocess::PostEffect::Bloom { .. })
+    };
+    let mut bloom = effects.iter().any(is_bloom);
+    if ui.checkbox(&mut bloom, "Bloom").changed() {
+        if bloom {
+            let index = effects
+                .iter()
+                .position(|effect| {
+                    matches!(
+                        effect,
+                        serenity::postprocess::PostEffect::ToneMapping { .. }
+                    )
+                })
+                .unwrap_or(0);
+            effects.insert(index, serenity::postprocess::PostEffect::ALL[0]);
+        } else {
+            effects.retain(|effect| !is_bloom(effect));
+        }
+    }
+}
+
+fn camera_post_effects_ui(
+    ui: &mut egui::Ui,
+    post_effects: &mut Option<Vec<serenity::postprocess::PostEffect>>,
+    default_effects: &[serenity::postprocess::PostEffect],
+) {
+    let mut overridden = post_effects.is_some();
+    if ui
+        .checkbox(&mut overridden, "Override post effects")
+        .changed()
+    {
+        *post_effects = overridden.then(|| default_effects.to_vec());
+    }
+    if let Some(effects) = post_effects.as_mut() {
+        bloom_toggle_ui(ui, effects);
+        ui.collapsing("Post Effects", |ui| post_effects_ui(ui, effects));
+    }
+}
+
+fn post_effects_ui(ui: &mut egui::Ui, effects: &mut Vec<serenity::postprocess::PostEffect>) {
+    let mut removed_effect = None;
+    let mut moved_effect = None;
+    let number_of_effects = effects.len();
+    effects.iter_mut().enumerate().for_each(|(index, effect)| {
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.strong(effect.name());
+                if ui.add_enabled(index > 0, egui::Button::new("Up")).clicked() {
+                    moved_effect = Some((index, index - 1));
+                }
+                if ui
+                    .add_enabled(index + 1 < number_of_effects, egui::Button::new("Down"))
+                    .clicked()
+                {
+                    moved_effect = Some((index, index + 1));
+                }
+                if ui.button("Remove").clicked() {
+                    removed_effect = Some(index);
+                }
+            });
+            post_effect_ui(ui, effect);
+        });
+    });
+    if let Some((from, to)) = moved_effect {
+        effects.swap(from, to);
+    }
+    if let Some(index) = removed_effect {
+        effects.remove(index);
+    }
+
+    ui.menu_button("Add Effect", |ui| {
+        serenity::postprocess::PostEffect::ALL
+            .iter()
+            .for_each(|effect| {
+                if ui.button(effect.name()).clicked() {
+                    effects.push(*effect);
+                    ui.close_menu();
+                }
+            });
+    });
+}
+
+fn post_effect_ui(ui: &mut egui::Ui, effect: &mut serenity::postprocess::PostEffect) {
+    egui::Grid::new(ui.next_auto_id()).show(ui, |ui| match effect {
+        serenity::postprocess::PostEffect::Bloom {
+            threshold,
+            knee,
+            intensity,
+        } => {
+            ui.label("Threshold");
+            ui.add(
+                egui::DragValue::new(threshold)
+                    .speed(0.01)
+                    .clamp_range(0.0..=100.0),
+            );
+            ui.end_row();
+            ui.label("Knee");
+            ui.add(egui::Slider::new(knee, 0.0..=1.0));
+            ui.end_row();
+            ui.label("Intensity");
+            ui.add(egui::Slider::new(intensity, 0.0..=1.0));
+            ui.end_row();
+        }
+        serenity::postprocess::PostEffect::ToneMapping {
+            operator,
+            exposure,
+            gamma,
+        } => {
+            ui.label("Operator");
+            egui::ComboBox::from_id_source(ui.next_auto_id())
+                .selected_text(format!("{operator:?}"))
+                .show_ui(ui, |ui| {
+                    [
+                        serenity::postprocess::ToneMapping::None,
+                        serenity::postprocess::ToneMapping::Reinhard,
+                        serenity::postprocess::ToneMapping::Aces,
+                    ]
+                    .into_iter()
+                    .for_each(|value| {
+                        ui.selectable_value(operator, value, format!("{value:?}"));
+                    });
+                });
+            ui.end_row();
+            ui.label("Exposure");
+            ui.add(
+                egui::DragValue::new(exposure)
+                    .speed(0.01)
+                    .clamp_range(0.0..=100.0),
+            );
+            ui.end_row();
+            ui.label("Gamma");
+            ui.add(
+                egui::DragValue::new(gamma)
+                    .speed(0.01)
+                    .clamp_range(0.1..=5.0),
+            );
+            ui.end_row();
+        }
+        serenity::postprocess::PostEffect::ColorGrading { intensity } => {
+            ui.label("Intensity");
+            ui.add(egui::Slider::new(intensity, 0.0..=1.0));
+            ui.end_row();
+        }
+        serenity::postprocess::PostEffect::Vignette {
+            intensity,
+            radius,
+            smoothness,
+        } => {
+            [
+                ("Intensity", intensity),
+                ("Radius", radius),
+                ("Smoothness", smoothness),
+            ]
+            .into_iter()
+            .for_each(|(label, value)| {
+                ui.label(label);
+                ui.add(egui::Slider::new(value, 0.0..=1.0));
+                ui.end_row();
+            });
+        }
+        serenity::postprocess::PostEffect::Fxaa => {}
+    });
+}
+
+fn collision_layers_ui(ui: &mut egui::Ui, label: &str, bits: &mut u32) {
+    ui.collapsing(format!("{label} ({bits:#010x})"), |ui| {
+        egui::Grid::new(ui.next_auto_id()).show(ui, |ui| {
+            for layer in 0..32 {
+                let mut enabled = (*bits & (1 << layer)) != 0;
+                if ui.checkbox(&mut enabled, layer.to_string()).changed() {
+                    *bits ^= 1 << layer;
+                }
+                if layer % 8 == 7 {
+                    ui.end_row();
+                }
+            }
+        });
+    });
+}
+
+/// Returns the shape chosen by the user, or `Some(None)`
+/// if the chosen shape could not be generated from the mesh
+fn add_collider_ui(
+    ui: &mut egui::Ui,
+    mesh: Option<&serenity::scene::Mesh>,
+) -> Option<Option<serenity::scene::ColliderShape>> {
+    let mut shape = None;
+    ui.menu_button("Add Collider", |ui| {
+        if ui.button("Sphere").clicked() {
+            shape = Some(Some(serenity::scene::ColliderShape::Sphere { radius: 0.5 }));
+        }
+        if ui.button("Box").clicked() {
+            shape = Some(Some(serenity::scene::ColliderShape::Box {
+                half_extents: nalgebra_glm::vec3(0.5, 0.5, 0.5),
+                center: nalgebra_glm::Vec3::zeros(),
+            }));
+        }
+        if ui.button("Capsule").clicked() {
+            shape = Some(Some(serenity::scene::ColliderShape::Capsule {
+                half_height: 0.5,
+                radius: 0.5,
+            }));
+        }
+        if let Some(mesh) = mesh {
+            ui.separator();
+            if ui.button("Box (fit to mesh)").clicked() {
+                shape = Some(Some(serenity::scene::ColliderShape::box_from_mesh(mesh)));
+            }
+            if ui.button("Convex Hull (from mesh)").clicked() {
+                shape = Some(serenity::scene::ColliderShape::convex_hull_from_mesh(mesh));
+            }
+            if ui.button("Triangle Mesh (from mesh)").clicked() {
+                shape = Some(Some(
+                    serenity::scene::ColliderShape::triangle_mesh_from_mesh(mesh),
+                ));
+            }
+        }
+        if shape.is_some() {
+            ui.close_menu();
+        }
+    });
+    shape
+}
+
+fn collider_wireframes_ui(
+    ui: &mut egui::Ui,
+    viewport_rect: egui::Rect,
+    scene: &serenity::scene::Scene,
+    view_projection: &nalgebra_glm::Mat4,
+    selected: Option<petgraph::graph::NodeIndex>,
+) {
+    let project = |point: &nalgebra_glm::Vec3| {
+        let clip = view_projection * nalgebra_glm::vec4(point.x, point.y, point.z, 1.0);
+        if clip.w <= f32::EPSILON {
+            return None;
+        }
+        let ndc = nalgebra_glm::vec4_to_vec3(&clip) / clip.w;
+        Some(egui::pos2(
+            viewport_rect.left() + (ndc.x * 0.5 + 0.5) * viewport_rect.width(),
+            viewport_rect.top() + (0.5 - ndc.y * 0.5) * viewport_rect.height(),
+        ))
+    };
+    let painter = ui.painter();
+    scene.walk_dfs(|node, node_index| {
+        let colliders = node
+            .components
+            .iter()
+            .filter_map(|component| match component {
+                serenity::scene::NodeComponent::Collider(collider) => Some(collider),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if colliders.is_empty() {
+            return;
+        }
+        let global_transform = scene.graph.global_transform(node_index);
+        let is_selected = selected == Some(node_index);
+        colliders.iter().for_each(|collider| {
+            let color = match (collider.is_trigger, is_selected) {
+                (false, false) => egui::Color32::from_rgb(40, 160, 40),
+                (false, true) => egui::Color32::from_rgb(100, 255, 100),
+                (true, false) => egui::Color32::from_rgb(160, 140, 40),
+                (true, true) => egui::Color32::from_rgb(255, 230, 80),
+            };
+            collider.shape.wireframe().iter().for_each(|line| {
+                let [Some(start), Some(end)] = line.map(|point| {
+                    project(&nalgebra_glm::vec4_to_vec3(
+                        &(global_transform * nalgebra_glm::vec4(point.x, point.y, point.z, 1.0)),
+                    ))
+                }) else {
+                    return;
+                };
+                painter.line_segment([start, end], egui::Stroke::new(1.0, color));
+            });
+        });
+    });
+}
+
 fn camera_system(context: &mut serenity::app::Context) {
-    context.scene.walk_dfs_mut(|node, _| {
+    // Cameras rendering into viewports and textures stay where they were placed
+    let active_camera = serenity::view::active_camera(&context.scene);
+    context.scene.walk_dfs_mut(|node, node_index| {
+        if Some(node_index) != active_camera {
+            return;
+        }
         node.components.iter_mut().for_each(|component| {
             if let serenity::scene::NodeComponent::Camera(camera) = component {
                 let speed = 10.0 * context.delta_time as f32;
@@ -346,16 +1258,6 @@ fn camera_system(context: &mut serenity::app::Context) {
                 {
                     camera.orientation.offset -= camera.orientation.up() * speed;
                 }
-
-                camera
-                    .orientation
-                    .zoom(6.0 * context.io.mouse.wheel_delta.y * (context.delta_time as f32));
-
-                if context.io.mouse.is_middle_clicked {
-                    camera
-                        .orientation
-                        .pan(&(context.io.mouse.position_delta * context.delta_time as f32));
-                }
                 node.transform.translation = camera.orientation.position();
 
                 if context.io.is_key_pressed(winit::event::VirtualKeyCode::H) {
@@ -363,19 +1265,139 @@ fn camera_system(context: &mut serenity::app::Context) {
                     camera.orientation.offset = nalgebra_glm::Vec3::new(0.0, 0.0, 0.0);
                 }
 
-                if context.io.mouse.is_right_clicked {
-                    let mut delta = context.io.mouse.position_delta * context.delta_time as f32;
-                    delta.x *= -1.0;
-                    delta.y *= -1.0;
-                    camera.orientation.rotate(&delta);
-                }
+                node.transform.rotation = camera.orientation.look_at_offset();
+            }
+        });
+    });
+}
 
+/// Mouse input over the viewport panel is consumed by the gui before it reaches
+/// the app's io, so the camera orbits, pans and zooms from the panel's input instead
+fn viewport_camera_ui(
+    ui: &egui::Ui,
+    response: &egui::Response,
+    context: &mut serenity::app::Context,
+) {
+    if !response.hovered() {
+        return;
+    }
+    let (pointer_delta, wheel_lines, is_orbiting, is_panning) = ui.input(|input| {
+        (
+            input.pointer.delta() * input.pixels_per_point(),
+            input.scroll_delta.y / POINTS_PER_SCROLL_LINE,
+            input.pointer.secondary_down(),
+            input.pointer.middle_down(),
+        )
+    });
+    let delta = nalgebra_glm::vec2(pointer_delta.x, pointer_delta.y) * context.delta_time as f32;
+    let zoom = 6.0 * wheel_lines * context.delta_time as f32;
+    let active_camera = serenity::view::active_camera(&context.scene);
+    context.scene.walk_dfs_mut(|node, node_index| {
+        if Some(node_index) != active_camera {
+            return;
+        }
+        node.components.iter_mut().for_each(|component| {
+            if let serenity::scene::NodeComponent::Camera(camera) = component {
+                camera.orientation.zoom(zoom);
+                if is_panning {
+                    camera.orientation.pan(&delta);
+                }
+                if is_orbiting {
+                    camera.orientation.rotate(&-delta);
+                }
+                node.transform.translation = camera.orientation.position();
                 node.transform.rotation = camera.orientation.look_at_offset();
             }
         });
     });
 }
 
+/// The editor's quad view, which shows the active camera in the top left
+/// and orthographic views from the top, front and side of what the camera orbits in the other corners
+fn quad_views(
+    scene: &serenity::scene::Scene,
+    aspect_ratio: f32,
+) -> Vec<serenity::view::CameraView> {
+    let Some(camera_node) = serenity::view::active_camera(scene) else {
+        return Vec::new();
+    };
+    let node = &scene.graph[camera_node];
+    let Some(camera) = node
+        .components
+        .iter()
+        .find_map(|component| match component {
+            serenity::scene::NodeComponent::Camera(camera) => Some(camera),
+            _ => None,
+        })
+    else {
+        return Vec::new();
+    };
+    let quadrant = |x: f32, y: f32| serenity::scene::CameraViewport {
+        x,
+        y,
+        width: 0.5,
+        height: 0.5,
+        order: 0,
+    };
+    let target = camera.orientation.offset;
+    let half_height = camera.orientation.radius;
+    let orthographic = |direction: nalgebra_glm::Vec3, up: nalgebra_glm::Vec3, viewport| {
+        serenity::view::CameraView {
+            camera: serenity::scene::Camera {
+                projection: serenity::scene::Projection::Orthographic(
+                    serenity::scene::OrthographicCamera {
+                        x_mag: half_height * aspect_ratio,
+                        y_mag: half_height,
+                        z_near: 0.0,
+                        z_far: 2.0 * ORTHOGRAPHIC_VIEW_DISTANCE,
+                    },
+                ),
+                orientation: camera.orientation.clone(),
+                post_effects: camera.post_effects.clone(),
+                viewport: None,
+                render_target: None,
+            },
+            transform: serenity::scene::Transform {
+                translation: target - direction * ORTHOGRAPHIC_VIEW_DISTANCE,
+                rotation: nalgebra_glm::quat_conjugate(&nalgebra_glm::quat_look_at(
+                    &direction, &up,
+                )),
+                ..Default::default()
+            },
+            viewport,
+        }
+    };
+    vec![
+        serenity::view::CameraView {
+            camera: camera.clone(),
+            transform: node.transform,
+            viewport: quadrant(0.0, 0.0),
+        },
+        orthographic(
+            -nalgebra_glm::Vec3::y(),
+            -nalgebra_glm::Vec3::z(),
+            quadrant(0.5, 0.0),
+        ),
+        orthographic(
+            -nalgebra_glm::Vec3::z(),
+            nalgebra_glm::Vec3::y(),
+            quadrant(0.0, 0.5),
+        ),
+        orthographic(
+            -nalgebra_glm::Vec3::x(),
+            nalgebra_glm::Vec3::y(),
+            quadrant(0.5, 0.5),
+        ),
+    ]
+}
+
+/// How far the orthographic views of the quad view are from what they look at,
+/// which is half of the depth they see
+const ORTHOGRAPHIC_VIEW_DISTANCE: f32 = 500.0;
+
+/// How far egui scrolls for each line of a mouse wheel
+const POINTS_PER_SCROLL_LINE: f32 = 50.0;
+
 #[derive(Default)]
 pub struct Broker {
     pub subscribers:
@@ -456,6 +1478,9 @@ impl std::fmt::Display for Topic {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum Command {
     ImportGltfFile(String),
+    Screenshot(String),
+    StartSimulation,
+    StopSimulation,
     Exit,
 }
 
@@ -1,5 +1,5 @@
 mod editor;
 
 fn main() {
-    serenity::app::App::new("Serenity", 1920, 1080).run(crate::editor::Editor::new());
+    serenity::app::App::new("Serenity", 1920, 1080, 4).run(crate::editor::Editor::new());
 }
@@ -1,12 +1,14 @@
 use serenity::{nalgebra_glm, petgraph, uuid, winit};
 
 fn main() {
-    serenity::app::App::new("Serenity", 1920, 1080).run(Game::default());
+    serenity::app::App::new("Serenity", 1920, 1080, 4).run(Game::default());
 }
 
 #[derive(Default)]
 struct Game {
     player_node_index: petgraph::graph::NodeIndex<u32>,
+    camera_node_index: petgraph::graph::NodeIndex<u32>,
+    physics: serenity::physics::PhysicsWorld,
 }
 
 impl serenity::app::State for Game {
@@ -15,7 +17,7 @@ impl serenity::app::State for Game {
         context: &mut serenity::app::Context,
         renderer: &mut serenity::render::Renderer,
     ) {
-        context.scene = serenity::gltf::import_gltf("resources/models/DamagedHelmet.glb").clone();
+        context.scene = serenity::gltf::import_gltf("resources/models/blocklevel.glb").clone();
 
         let aspect_ratio = {
             let serenity::winit::dpi::PhysicalSize { width, height } = context.window.inner_size();
@@ -23,21 +25,42 @@ impl serenity::app::State for Game {
         };
         renderer.view.import_scene(&context.scene, &renderer.gpu);
 
-        context
+        self.camera_node_index = context
             .scene
             .add_root_node(serenity::scene::create_camera_node(aspect_ratio));
 
+        // Spawn the player on whatever level geometry is below the center of the level
+        let spawn_position = context
+            .scene
+            .bounds()
+            .and_then(|bounds| {
+                let ray = serenity::geometry::Ray::new(
+                    nalgebra_glm::vec3(bounds.center().x, bounds.max.y + 1.0, bounds.center().z),
+                    -nalgebra_glm::Vec3::y(),
+                );
+                serenity::picking::raycast(&context.scene, &ray).map(|hit| hit.position)
+            })
+            .unwrap_or_else(nalgebra_glm::Vec3::zeros);
+        let controller = serenity::scene::CharacterController::default();
+
         self.player_node_index = context.scene.add_root_node({
             serenity::scene::Node {
                 id: uuid::Uuid::new_v4().to_string(),
                 label: "Player".to_string(),
                 transform: serenity::scene::Transform {
-                    translation: nalgebra_glm::vec3(0.0, 0.0, 0.0),
+                    translation: spawn_position
+                        + nalgebra_glm::Vec3::y()
+                            * (controller.half_height + controller.radius + 0.1),
                     ..Default::default()
                 },
-                components: vec![serenity::scene::NodeComponent::Mesh("player".to_string())],
+                components: vec![serenity::scene::NodeComponent::CharacterController(
+                    controller,
+                )],
             }
         });
+
+        self.physics = serenity::physics::PhysicsWorld::from_scene(&context.scene);
+        self.physics.add_static_mesh_colliders(&context.scene);
     }
 
     fn receive_event(
@@ -72,11 +95,40 @@ impl serenity::app::State for Game {
         context: &mut serenity::app::Context,
         _renderer: &mut serenity::render::Renderer,
     ) {
-        if context.io.is_key_pressed(winit::event::VirtualKeyCode::W) {
-            context.scene.graph[self.player_node_index]
-                .transform
-                .translation
-                .x += 100.0;
+        let Some(serenity::scene::NodeComponent::Camera(camera)) = context.scene.graph
+            [self.camera_node_index]
+            .components
+            .first()
+        else {
+            return;
+        };
+        let input = serenity::character::CharacterInput::from_keyboard(
+            &context.io,
+            &-camera.orientation.direction(),
+        );
+        serenity::character::move_character(
+            &self.physics,
+            &mut context.scene,
+            self.player_node_index,
+            &input,
+            context.delta_time as f32,
+        );
+        self.physics.update(&mut context.scene, context.delta_time);
+
+        // The camera orbits the player
+        let player_position = context
+            .scene
+            .graph
+            .global_transform(self.player_node_index)
+            .column(3)
+            .xyz();
+        let camera_node = &mut context.scene.graph[self.camera_node_index];
+        if let Some(serenity::scene::NodeComponent::Camera(camera)) =
+            camera_node.components.first_mut()
+        {
+            camera.orientation.offset = player_position;
+            camera_node.transform.translation = camera.orientation.position();
+            camera_node.transform.rotation = camera.orientation.look_at_offset();
         }
     }
 }
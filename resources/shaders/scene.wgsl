@@ -0,0 +1,112 @@
+// Variants are selected with the ALPHA_MASK, ALPHA_BLEND and DOUBLE_SIDED defines
+#include "view.wgsl"
+#include "material.wgsl"
+#include "lighting.wgsl"
+#include "environment.wgsl"
+
+const WIREFRAME_COLOR: vec4<f32> = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+const OVERDRAW_COLOR: vec4<f32> = vec4<f32>(0.1, 0.04, 0.01, 1.0);
+const UV_CHECKER_SCALE: f32 = 8.0;
+// The view depth at which the depth view mode has faded to a third of its brightness
+const DEPTH_VIEW_DISTANCE: f32 = 10.0;
+
+// A checker pattern tinted by the texture coordinates
+fn uv_checker(uv: vec2<f32>) -> vec3<f32> {
+    let cell = floor(uv * UV_CHECKER_SCALE);
+    let checker = abs(cell.x + cell.y) % 2.0;
+    return vec3(fract(uv), 0.0) * (0.5 + 0.5 * checker);
+}
+
+@fragment
+fn fragment_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    let material = materials[mesh_ubo.material_index];
+    let base_color = material.base_color_factor * sample_base_color(material, in.uv_0);
+    var object_color = base_color * vec4(in.color, 1.0);
+#ifdef ALPHA_MASK
+    if object_color.a < material.alpha_cutoff {
+        discard;
+    }
+#endif
+#ifndef ALPHA_BLEND
+    object_color.a = 1.0;
+#endif
+    let metallic_roughness = sample_metallic_roughness(material, in.uv_0);
+    let metallic = material.metallic_factor * metallic_roughness.x;
+    let roughness = clamp(material.roughness_factor * metallic_roughness.y, 0.0, 1.0);
+    let emissive = material.emissive_factor.rgb * sample_emissive(material, in.uv_0);
+
+    // Primitives without normals are lit as if facing up
+    var normal = vec3(0.0, 1.0, 0.0);
+    if length(in.world_normal) > 0.0001 {
+        normal = normalize(in.world_normal);
+    }
+#ifdef DOUBLE_SIDED
+    // The back faces of double sided materials are lit from behind
+    if !front_facing {
+        normal = -normal;
+    }
+#endif
+
+    // The cases match the order of `ViewMode`
+    switch ubo.view_mode {
+        // ViewMode::Unlit
+        case 1u: {
+            return vec4(object_color.rgb + emissive, object_color.a);
+        }
+        // ViewMode::Normals
+        case 3u: {
+            return vec4(normal * 0.5 + 0.5, 1.0);
+        }
+        // ViewMode::Uv0
+        case 4u: {
+            return vec4(uv_checker(in.uv_0), 1.0);
+        }
+        // ViewMode::Uv1
+        case 5u: {
+            return vec4(uv_checker(in.uv_1), 1.0);
+        }
+        // ViewMode::VertexColors
+        case 6u: {
+            return vec4(in.color, 1.0);
+        }
+        // ViewMode::BaseColor
+        case 7u: {
+            return base_color;
+        }
+        // ViewMode::MetallicRoughness
+        case 8u: {
+            return vec4(0.0, roughness, metallic, 1.0);
+        }
+        // ViewMode::Depth
+        case 9u: {
+            return vec4(vec3(exp(-in.view_depth / DEPTH_VIEW_DISTANCE)), 1.0);
+        }
+        // ViewMode::Overdraw
+        case 10u: {
+            return OVERDRAW_COLOR;
+        }
+        default: {}
+    }
+
+    var lighting = vec3(0.0);
+    for (var light_index = 0u; light_index < ubo.light_count; light_index += 1u) {
+        lighting += light_contribution(lights[light_index], in.world_position, normal, in.view_depth);
+    }
+
+    // Without an environment, surfaces receive a constant ambient light
+    var ambient = vec3(0.1) * object_color.rgb;
+    if ubo.has_environment != 0u {
+        let view_direction = normalize(ubo.camera_position.xyz - in.world_position);
+        ambient = ubo.environment_intensity
+            * environment_lighting(normal, view_direction, object_color.rgb, metallic, roughness);
+    }
+
+    let result = lighting * object_color.rgb + ambient + emissive;
+
+    return vec4<f32>(result, object_color.a);
+}
+
+@fragment
+fn wireframe_fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return WIREFRAME_COLOR;
+}
@@ -0,0 +1,60 @@
+// The view's camera and per draw bindings, and the vertex stage every scene pipeline shares
+struct Uniform {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    camera_position: vec4<f32>,
+    light_count: u32,
+    has_environment: u32,
+    environment_intensity: f32,
+    view_mode: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> ubo: Uniform;
+
+struct DynamicUniform {
+    model: mat4x4<f32>,
+    material_index: u32,
+    node_id: u32,
+    normal_matrix: mat4x4<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> mesh_ubo: DynamicUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv_0: vec2<f32>,
+    @location(3) uv_1: vec2<f32>,
+    @location(4) joint_0: vec4<f32>,
+    @location(5) weight_0: vec4<f32>,
+    @location(6) color_0: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) uv_0: vec2<f32>,
+    @location(3) world_position: vec3<f32>,
+    @location(4) world_normal: vec3<f32>,
+    @location(5) view_depth: f32,
+    @location(6) uv_1: vec2<f32>,
+};
+
+@vertex
+fn vertex_main(vert: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let mvp = ubo.projection * ubo.view * mesh_ubo.model;
+    out.color = vert.color_0;
+    out.uv_0 = vert.uv_0;
+    out.uv_1 = vert.uv_1;
+    out.position = mvp * vec4(vert.position, 1.0);
+    out.normal = vec4((mvp * vec4(vert.normal, 0.0)).xyz, 1.0).xyz;
+    let world_position = mesh_ubo.model * vec4(vert.position, 1.0);
+    out.world_position = world_position.xyz;
+    out.world_normal = (mesh_ubo.normal_matrix * vec4(vert.normal, 0.0)).xyz;
+    out.view_depth = -(ubo.view * world_position).z;
+    return out;
+}
@@ -4,6 +4,8 @@ pub struct Context {
     pub delta_time: f64,
     pub last_frame: std::time::Instant,
     pub scene: crate::scene::Scene,
+    /// Debug shapes drawn over the scene by the renderer
+    pub debug: crate::debug::DebugDraw,
     pub should_exit: bool,
 }
 
@@ -29,7 +31,8 @@ pub struct App {
 }
 
 impl App {
-    pub fn new(title: &str, width: u32, height: u32) -> Self {
+    /// Opens a window whose renderer multisamples with up to `sample_count` samples
+    pub fn new(title: &str, width: u32, height: u32, sample_count: u32) -> Self {
         let event_loop = winit::event_loop::EventLoop::new();
         let window = winit::window::WindowBuilder::new()
             .with_title(title)
@@ -37,13 +40,20 @@ impl App {
             .with_transparent(true)
             .build(&event_loop)
             .expect("Failed to create winit window!");
-        let renderer = crate::render::Renderer::new(&window, width, height, window.scale_factor());
+        let renderer = crate::render::Renderer::new(
+            &window,
+            width,
+            height,
+            window.scale_factor(),
+            sample_count,
+        );
         let context = Context {
             window,
             io: crate::io::Io::default(),
             delta_time: 0.01,
             last_frame: std::time::Instant::now(),
             scene: crate::scene::Scene::default(),
+            debug: crate::debug::DebugDraw::default(),
             should_exit: false,
         };
         Self {
@@ -125,6 +135,7 @@ impl App {
                 renderer.render_frame(&mut context, |context, ui| {
                     state.ui(context, ui);
                 });
+                context.debug.update(context.delta_time as f32);
             }
         });
     }
@@ -0,0 +1,773 @@
+/// A bounding volume hierarchy over a set of items identified by their index
+/// into the slice of bounds the tree was built from
+#[derive(Default, Debug, Clone)]
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    pub items: Vec<usize>,
+    pub item_bounds: Vec<crate::geometry::Aabb>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum BvhNode {
+    Leaf {
+        bounds: crate::geometry::Aabb,
+        first_item: usize,
+        number_of_items: usize,
+    },
+    Interior {
+        bounds: crate::geometry::Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    pub fn bounds(&self) -> &crate::geometry::Aabb {
+        match self {
+            Self::Leaf { bounds, .. } | Self::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+impl Bvh {
+    pub const MAX_ITEMS_PER_LEAF: usize = 4;
+    const NUMBER_OF_BINS: usize = 12;
+
+    pub fn new(item_bounds: &[crate::geometry::Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            items: (0..item_bounds.len()).collect(),
+            item_bounds: item_bounds.to_vec(),
+        };
+        if !item_bounds.is_empty() {
+            let centroids = item_bounds
+                .iter()
+                .map(crate::geometry::Aabb::center)
+                .collect::<Vec<_>>();
+            bvh.build_recursive(&centroids, 0, item_bounds.len());
+        }
+        bvh
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn bounds(&self) -> crate::geometry::Aabb {
+        self.nodes
+            .first()
+            .map(|node| *node.bounds())
+            .unwrap_or_default()
+    }
+
+    /// Updates the bounds of every item and node without changing the tree's topology.
+    /// The number of items must match the number the tree was built with.
+    pub fn refit(&mut self, item_bounds: &[crate::geometry::Aabb]) {
+        assert_eq!(
+            item_bounds.len(),
+            self.item_bounds.len(),
+            "A bvh can only be refit with the same number of items it was built with"
+        );
+        self.item_bounds.copy_from_slice(item_bounds);
+
+        // Children are always created after their parents,
+        // so walking backwards visits every child before its parent
+        for node_index in (0..self.nodes.len()).rev() {
+            let refit_bounds = match self.nodes[node_index] {
+                BvhNode::Leaf {
+                    first_item,
+                    number_of_items,
+                    ..
+                } => self.leaf_bounds(first_item, number_of_items),
+                BvhNode::Interior { left, right, .. } => {
+                    self.nodes[left].bounds().merge(self.nodes[right].bounds())
+                }
+            };
+            match &mut self.nodes[node_index] {
+                BvhNode::Leaf { bounds, .. } | BvhNode::Interior { bounds, .. } => {
+                    *bounds = refit_bounds
+                }
+            }
+        }
+    }
+
+    /// Finds the nearest item hit by the ray, where `intersect_item` performs the narrowphase
+    /// test for an item and returns the distance along the ray to the hit
+    pub fn raycast(
+        &self,
+        ray: &crate::geometry::Ray,
+        max_distance: f32,
+        mut intersect_item: impl FnMut(usize, f32) -> Option<f32>,
+    ) -> Option<(usize, f32)> {
+        let mut nearest: Option<(usize, f32)> = None;
+        let mut nearest_distance = max_distance;
+        let mut stack = Vec::new();
+        if let Some(root) = self.nodes.first() {
+            if root.bounds().intersect_ray(ray).is_some() {
+                stack.push(0);
+            }
+        }
+        while let Some(node_index) = stack.pop() {
+            match self.nodes[node_index] {
+                BvhNode::Leaf {
+                    bounds,
+                    first_item,
+                    number_of_items,
+                } => {
+                    if !matches!(bounds.intersect_ray(ray), Some(distance) if distance <= nearest_distance)
+                    {
+                        continue;
+                    }
+                    for item in &self.items[first_item..first_item + number_of_items] {
+                        if let Some(distance) = intersect_item(*item, nearest_distance) {
+                            if distance <= nearest_distance {
+                                nearest_distance = distance;
+                                nearest = Some((*item, distance));
+                            }
+                        }
+                    }
+                }
+                BvhNode::Interior { left, right, .. } => {
+                    let left_distance = self.nodes[left].bounds().intersect_ray(ray);
+                    let right_distance = self.nodes[right].bounds().intersect_ray(ray);
+                    let mut children = [(left, left_distance), (right, right_distance)];
+                    // Push the farther child first so the nearer one is visited first
+                    if left_distance.unwrap_or(f32::MAX) < right_distance.unwrap_or(f32::MAX) {
+                        children.swap(0, 1);
+                    }
+                    children.iter().for_each(|(child, distance)| {
+                        if matches!(distance, Some(distance) if *distance <= nearest_distance) {
+                            stack.push(*child);
+                        }
+                    });
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Returns every item whose bounds overlap the given bounds
+    pub fn query_aabb(&self, aabb: &crate::geometry::Aabb) -> Vec<usize> {
+        self.query(|bounds| bounds.intersects(aabb))
+    }
+
+    /// Returns every item whose bounds overlap the given sphere
+    pub fn query_sphere(&self, center: &nalgebra_glm::Vec3, radius: f32) -> Vec<usize> {
+        self.query(|bounds| bounds.intersects_sphere(center, radius))
+    }
+
+    /// Returns every item whose bounds pass the given overlap test
+    pub fn query(&self, overlaps: impl Fn(&crate::geometry::Aabb) -> bool) -> Vec<usize> {
+        let mut results = Vec::new();
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !overlaps(node.bounds()) {
+                continue;
+            }
+            match *node {
+                BvhNode::Leaf {
+                    first_item,
+                    number_of_items,
+                    ..
+                } => {
+                    results.extend(
+                        self.items[first_item..first_item + number_of_items]
+                            .iter()
+                            .filter(|item| overlaps(&self.item_bounds[**item])),
+                    );
+                }
+                BvhNode::Interior { left, right, .. } => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+        results
+    }
+
+    /// Returns up to `count` items nearest to the point, measured to their bounds,
+    /// sorted from nearest to farthest
+    pub fn nearest(&self, point: &nalgebra_glm::Vec3, count: usize) -> Vec<(usize, f32)> {
+        let mut found: std::collections::BinaryHeap<Candidate> =
+            std::collections::BinaryHeap::new();
+        let mut pending = std::collections::BinaryHeap::new();
+        if count == 0 || self.nodes.is_empty() {
+            return Vec::new();
+        }
+        pending.push(std::cmp::Reverse(Candidate {
+            distance_squared: self.nodes[0].bounds().distance_squared_to_point(point),
+            index: 0,
+        }));
+        while let Some(std::cmp::Reverse(candidate)) = pending.pop() {
+            if found.len() == count
+                && found
+                    .peek()
+                    .is_some_and(|farthest| candidate.distance_squared > farthest.distance_squared)
+            {
+                break;
+            }
+            match self.nodes[candidate.index] {
+                BvhNode::Leaf {
+                    first_item,
+                    number_of_items,
+                    ..
+                } => {
+                    self.items[first_item..first_item + number_of_items]
+                        .iter()
+                        .for_each(|item| {
+                            found.push(Candidate {
+                                distance_squared: self.item_bounds[*item]
+                                    .distance_squared_to_point(point),
+                                index: *item,
+                            });
+                            if found.len() > count {
+                                found.pop();
+                            }
+                        });
+                }
+                BvhNode::Interior { left, right, .. } => {
+                    [left, right].iter().for_each(|child| {
+                        pending.push(std::cmp::Reverse(Candidate {
+                            distance_squared: self.nodes[*child]
+                                .bounds()
+                                .distance_squared_to_point(point),
+                            index: *child,
+                        }));
+                    });
+                }
+            }
+        }
+        found
+            .into_sorted_vec()
+            .into_iter()
+            .map(|candidate| (candidate.index, candidate.distance_squared.sqrt()))
+            .collect()
+    }
+
+    fn leaf_bounds(&self, first_item: usize, number_of_items: usize) -> crate::geometry::Aabb {
+        self.items[first_item..first_item + number_of_items]
+            .iter()
+            .fold(crate::geometry::Aabb::empty(), |bounds, item| {
+                bounds.merge(&self.item_bounds[*item])
+            })
+    }
+
+    fn build_recursive(
+        &mut self,
+        centroids: &[nalgebra_glm::Vec3],
+        first_item: usize,
+        number_of_items: usize,
+    ) -> usize {
+        let node_index = self.nodes.len();
+        let bounds = self.leaf_bounds(first_item, number_of_items);
+        let leaf = BvhNode::Leaf {
+            bounds,
+            first_item,
+            number_of_items,
+        };
+        self.nodes.push(leaf);
+
+        if number_of_items <= Self::MAX_ITEMS_PER_LEAF {
+            return node_index;
+        }
+
+        let Some(split) = self.find_split(centroids, first_item, number_of_items) else {
+            return node_index;
+        };
+
+        let left = self.build_recursive(centroids, first_item, split - first_item);
+        let right = self.build_recursive(centroids, split, first_item + number_of_items - split);
+        self.nodes[node_index] = BvhNode::Interior {
+            bounds,
+            left,
+            right,
+        };
+        node_index
+    }
+
+    /// Partitions the items using a binned surface area heuristic,
+    /// falling back to a median split, and returns the index of the first right item
+    fn find_split(
+        &mut self,
+        centroids: &[nalgebra_glm::Vec3],
+        first_item: usize,
+        number_of_items: usize,
+    ) -> Option<usize> {
+        let items = &mut self.items[first_item..first_item + number_of_items];
+        let centroid_bounds =
+            crate::geometry::Aabb::from_points(items.iter().map(|item| &centroids[*item]));
+        let extents = centroid_bounds.extents();
+        let axis = if extents.x >= extents.y && extents.x >= extents.z {
+            0
+        } else if extents.y >= extents.z {
+            1
+        } else {
+            2
+        };
+        if extents[axis] <= f32::EPSILON {
+            return None;
+        }
+
+        let bin_of = |item: usize| {
+            let offset = (centroids[item][axis] - centroid_bounds.min[axis]) / extents[axis];
+            ((offset * Self::NUMBER_OF_BINS as f32) as usize).min(Self::NUMBER_OF_BINS - 1)
+        };
+
+        let mut bins = [(crate::geometry::Aabb::empty(), 0_usize); Self::NUMBER_OF_BINS];
+        items.iter().for_each(|item| {
+            let bin = &mut bins[bin_of(*item)];
+            bin.0 = bin.0.merge(&self.item_bounds[*item]);
+            bin.1 += 1;
+        });
+
+        let mut best_cost = f32::MAX;
+        let mut best_bin = None;
+        for split_bin in 1..Self::NUMBER_OF_BINS {
+            let (left_bounds, left_count) = bins[..split_bin].iter().fold(
+                (crate::geometry::Aabb::empty(), 0),
+                |(bounds, count), bin| (bounds.merge(&bin.0), count + bin.1),
+            );
+            let (right_bounds, right_count) = bins[split_bin..].iter().fold(
+                (crate::geometry::Aabb::empty(), 0),
+                |(bounds, count), bin| (bounds.merge(&bin.0), count + bin.1),
+            );
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost = left_bounds.surface_area() * left_count as f32
+                + right_bounds.surface_area() * right_count as f32;
+            if cost < best_cost {
+                best_cost = cost;
+                best_bin = Some(split_bin);
+            }
+        }
+
+        let split = match best_bin {
+            Some(split_bin) => {
+                let mut left_count = 0;
+                for index in 0..items.len() {
+                    if bin_of(items[index]) < split_bin {
+                        items.swap(index, left_count);
+                        left_count += 1;
+                    }
+                }
+                left_count
+            }
+            None => {
+                items.sort_by(|a, b| centroids[*a][axis].total_cmp(&centroids[*b][axis]));
+                items.len() / 2
+            }
+        };
+        Some(first_item + split)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Candidate {
+    distance_squared: f32,
+    index: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.distance_squared
+            .total_cmp(&other.distance_squared)
+            .then(self.index.cmp(&other.index))
+    }
+}
+
+/// A bvh over the triangles of a single primitive in the primitive's local space
+#[derive(Default, Debug, Clone)]
+pub struct TriangleBvh {
+    pub bvh: Bvh,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl TriangleBvh {
+    pub fn new(primitive: &crate::scene::Primitive) -> Self {
+        let triangles = primitive.triangles();
+        let bounds = triangles
+            .iter()
+            .map(|triangle| {
+                crate::geometry::Aabb::from_points(
+                    triangle
+                        .iter()
+                        .filter_map(|index| primitive.vertices.get(*index as usize))
+                        .map(|vertex| &vertex.position),
+                )
+            })
+            .collect::<Vec<_>>();
+        Self {
+            bvh: Bvh::new(&bounds),
+            triangles,
+        }
+    }
+
+    /// Casts a ray given in the primitive's local space,
+    /// returning the index of the nearest triangle hit
+    pub fn raycast(
+        &self,
+        primitive: &crate::scene::Primitive,
+        ray: &crate::geometry::Ray,
+    ) -> Option<(usize, crate::geometry::TriangleHit)> {
+        let mut nearest_hit = None;
+        self.bvh.raycast(ray, f32::MAX, |triangle_index, _| {
+            let [Some(a), Some(b), Some(c)] =
+                self.triangles[triangle_index].map(|index| primitive.vertices.get(index as usize))
+            else {
+                return None;
+            };
+            let hit = crate::geometry::intersect_ray_triangle(
+                ray,
+                &a.position,
+                &b.position,
+                &c.position,
+            )?;
+            if nearest_hit.as_ref().map_or(
+                true,
+                |(_, nearest): &(usize, crate::geometry::TriangleHit)| {
+                    hit.distance < nearest.distance
+                },
+            ) {
+                nearest_hit = Some((triangle_index, hit));
+            }
+            Some(hit.distance)
+        });
+        nearest_hit
+    }
+}
+
+/// A bvh over the world space bounds of every node in the scene that has a mesh
+#[derive(Default, Debug, Clone)]
+pub struct SceneBvh {
+    pub bvh: Bvh,
+    pub nodes: Vec<petgraph::graph::NodeIndex>,
+    /// Triangle bvhs for each primitive, keyed by mesh id
+    pub mesh_bvhs: std::collections::HashMap<String, Vec<TriangleBvh>>,
+    /// The local bounds of each mesh, keyed by mesh id, so refitting
+    /// only transforms their corners instead of scanning every vertex
+    pub mesh_bounds: std::collections::HashMap<String, crate::geometry::Aabb>,
+}
+
+impl SceneBvh {
+    /// Builds the bvh and caches the bounds of every mesh,
+    /// so it must be built again when mesh vertices change
+    pub fn new(scene: &crate::scene::Scene) -> Self {
+        let mesh_bounds = scene
+            .meshes
+            .iter()
+            .map(|(mesh_id, mesh)| (mesh_id.to_string(), mesh.bounding_box()))
+            .collect();
+        let mut scene_bvh = Self {
+            mesh_bounds,
+            ..Default::default()
+        };
+        let (nodes, bounds) = scene_bvh.scene_bounds(scene);
+        scene_bvh.bvh = Bvh::new(&bounds);
+        scene_bvh.nodes = nodes;
+        scene_bvh
+    }
+
+    /// Also builds a triangle bvh for every primitive, which speeds up
+    /// ray casts against large meshes at the cost of memory
+    pub fn with_triangle_bvhs(scene: &crate::scene::Scene) -> Self {
+        let mut scene_bvh = Self::new(scene);
+        scene_bvh.mesh_bvhs = scene
+            .meshes
+            .iter()
+            .map(|(mesh_id, mesh)| {
+                (
+                    mesh_id.to_string(),
+                    mesh.primitives.iter().map(TriangleBvh::new).collect(),
+                )
+            })
+            .collect();
+        scene_bvh
+    }
+
+    /// Updates node bounds after transforms have changed,
+    /// rebuilding the tree if mesh nodes were added or removed
+    pub fn refit(&mut self, scene: &crate::scene::Scene) {
+        let (nodes, bounds) = self.scene_bounds(scene);
+        if nodes == self.nodes {
+            self.bvh.refit(&bounds);
+        } else {
+            self.bvh = Bvh::new(&bounds);
+            self.nodes = nodes;
+        }
+    }
+
+    pub fn raycast(
+        &self,
+        scene: &crate::scene::Scene,
+        ray: &crate::geometry::Ray,
+    ) -> Option<crate::picking::RaycastHit> {
+        let mut nearest_hit: Option<crate::picking::RaycastHit> = None;
+        self.bvh.raycast(ray, f32::MAX, |item, max_distance| {
+            let node_index = self.nodes[item];
+            let hit = self.raycast_node(scene, node_index, ray, max_distance)?;
+            nearest_hit = Some(hit);
+            Some(hit.distance)
+        });
+        nearest_hit
+    }
+
+    pub fn query_aabb(&self, aabb: &crate::geometry::Aabb) -> Vec<petgraph::graph::NodeIndex> {
+        self.map_items(self.bvh.query_aabb(aabb))
+    }
+
+    pub fn query_sphere(
+        &self,
+        center: &nalgebra_glm::Vec3,
+        radius: f32,
+    ) -> Vec<petgraph::graph::NodeIndex> {
+        self.map_items(self.bvh.query_sphere(center, radius))
+    }
+
+    /// Returns up to `count` nodes nearest to the point with their distances,
+    /// sorted from nearest to farthest
+    pub fn nearest(
+        &self,
+        point: &nalgebra_glm::Vec3,
+        count: usize,
+    ) -> Vec<(petgraph::graph::NodeIndex, f32)> {
+        self.bvh
+            .nearest(point, count)
+            .into_iter()
+            .map(|(item, distance)| (self.nodes[item], distance))
+            .collect()
+    }
+
+    /// The world space bounds of every node with a mesh, from the cached mesh bounds
+    fn scene_bounds(
+        &self,
+        scene: &crate::scene::Scene,
+    ) -> (Vec<petgraph::graph::NodeIndex>, Vec<crate::geometry::Aabb>) {
+        let mut nodes = Vec::new();
+        let mut bounds = Vec::new();
+        scene.walk_dfs(|_, node_index| {
+            let local_bounds = scene.graph[node_index]
+                .components
+                .iter()
+                .filter_map(|component| match component {
+                    crate::scene::NodeComponent::Mesh(mesh_id) => self.mesh_bounds.get(mesh_id),
+                    _ => None,
+                })
+                .fold(crate::geometry::Aabb::empty(), |bounds, mesh_bounds| {
+                    bounds.merge(mesh_bounds)
+                });
+            if !local_bounds.is_empty() {
+                nodes.push(node_index);
+                bounds.push(local_bounds.transform(&scene.graph.global_transform(node_index)));
+            }
+        });
+        (nodes, bounds)
+    }
+
+    fn map_items(&self, items: Vec<usize>) -> Vec<petgraph::graph::NodeIndex> {
+        items.into_iter().map(|item| self.nodes[item]).collect()
+    }
+
+    fn raycast_node(
+        &self,
+        scene: &crate::scene::Scene,
+        node_index: petgraph::graph::NodeIndex,
+        ray: &crate::geometry::Ray,
+        max_distance: f32,
+    ) -> Option<crate::picking::RaycastHit> {
+        let global_transform = scene.graph.global_transform(node_index);
+        let local_ray = ray.transform(&nalgebra_glm::inverse(&global_transform));
+        let mut nearest_hit: Option<crate::picking::RaycastHit> = None;
+        scene.graph[node_index]
+            .components
+            .iter()
+            .for_each(|component| {
+                let crate::scene::NodeComponent::Mesh(mesh_id) = component else {
+                    return;
+                };
+                let Some(mesh) = scene.meshes.get(mesh_id) else {
+                    return;
+                };
+                mesh.primitives
+                    .iter()
+                    .enumerate()
+                    .for_each(|(primitive_index, primitive)| {
+                        let nearest_distance = nearest_hit.map_or(max_distance, |hit| hit.distance);
+                        let hit = match self
+                            .mesh_bvhs
+                            .get(mesh_id)
+                            .and_then(|bvhs| bvhs.get(primitive_index))
+                        {
+                            Some(triangle_bvh) => {
+                                // Distances along the local ray are scaled by the transform,
+                                // so the hit is measured again in world space
+                                triangle_bvh.raycast(primitive, &local_ray).map(
+                                    |(triangle_index, hit)| {
+                                        let local_position = local_ray.at(hit.distance);
+                                        let position = nalgebra_glm::vec4_to_vec3(
+                                            &(global_transform
+                                                * nalgebra_glm::vec4(
+                                                    local_position.x,
+                                                    local_position.y,
+                                                    local_position.z,
+                                                    1.0,
+                                                )),
+                                        );
+                                        let distance =
+                                            nalgebra_glm::distance(&ray.origin, &position);
+                                        (
+                                            triangle_index,
+                                            crate::geometry::TriangleHit { distance, ..hit },
+                                        )
+                                    },
+                                )
+                            }
+                            None => crate::picking::raycast_primitive(
+                                primitive,
+                                &global_transform,
+                                ray,
+                                nearest_distance,
+                            ),
+                        };
+                        if let Some((triangle_index, hit)) = hit {
+                            if hit.distance < nearest_distance {
+                                nearest_hit = Some(crate::picking::RaycastHit {
+                                    node_index,
+                                    primitive_index,
+                                    triangle_index,
+                                    distance: hit.distance,
+                                    position: ray.at(hit.distance),
+                                    barycentrics: hit.barycentrics,
+                                });
+                            }
+                        }
+                    });
+            });
+        nearest_hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn unit_boxes_along_x(count: usize) -> Vec<crate::geometry::Aabb> {
+        (0..count)
+            .map(|index| {
+                let min = nalgebra_glm::vec3(index as f32 * 2.0, 0.0, 0.0);
+                crate::geometry::Aabb::new(min, min + nalgebra_glm::vec3(1.0, 1.0, 1.0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let bounds = unit_boxes_along_x(100);
+        let bvh = crate::bvh::Bvh::new(&bounds);
+
+        let query = crate::geometry::Aabb::new(
+            nalgebra_glm::vec3(9.5, 0.5, 0.5),
+            nalgebra_glm::vec3(14.5, 0.6, 0.6),
+        );
+        let mut overlapping = bvh.query_aabb(&query);
+        overlapping.sort();
+        assert_eq!(overlapping, vec![5, 6, 7]);
+
+        let nearest = bvh.nearest(&nalgebra_glm::vec3(41.5, 0.5, 0.5), 3);
+        let nearest_items = nearest.iter().map(|(item, _)| *item).collect::<Vec<_>>();
+        assert_eq!(nearest_items[0], 20);
+        assert!(nearest_items[1..].contains(&21));
+
+        let ray = crate::geometry::Ray::new(
+            nalgebra_glm::vec3(-5.0, 0.5, 0.5),
+            nalgebra_glm::vec3(1.0, 0.0, 0.0),
+        );
+        let (item, distance) = bvh
+            .raycast(&ray, f32::MAX, |item, _| bounds[item].intersect_ray(&ray))
+            .unwrap();
+        assert_eq!(item, 0);
+        assert!((distance - 5.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn refit_moves_items() {
+        let mut bounds = unit_boxes_along_x(20);
+        let mut bvh = crate::bvh::Bvh::new(&bounds);
+        bounds[3] = crate::geometry::Aabb::new(
+            nalgebra_glm::vec3(0.0, 50.0, 0.0),
+            nalgebra_glm::vec3(1.0, 51.0, 1.0),
+        );
+        bvh.refit(&bounds);
+        assert_eq!(
+            bvh.query_sphere(&nalgebra_glm::vec3(0.5, 50.5, 0.5), 1.0),
+            vec![3]
+        );
+        assert!(bvh.bounds().max.y >= 51.0);
+    }
+
+    #[test]
+    fn scene_refit_transforms_cached_mesh_bounds() {
+        let mut scene = crate::scene::Scene::default();
+        scene.graph.add_node(crate::scene::Node {
+            label: "Root".to_string(),
+            ..Default::default()
+        });
+        let vertex = |x: f32, y: f32, z: f32| crate::scene::Vertex {
+            position: nalgebra_glm::vec3(x, y, z),
+            ..Default::default()
+        };
+        scene.meshes.insert(
+            "triangle".to_string(),
+            crate::scene::Mesh {
+                label: "Triangle".to_string(),
+                primitives: vec![crate::scene::Primitive {
+                    vertices: vec![
+                        vertex(0.0, 0.0, 0.0),
+                        vertex(1.0, 0.0, 0.0),
+                        vertex(0.0, 1.0, 0.0),
+                    ],
+                    ..Default::default()
+                }],
+            },
+        );
+        let node = scene.add_root_node(crate::scene::Node {
+            label: "Triangle".to_string(),
+            components: vec![crate::scene::NodeComponent::Mesh("triangle".to_string())],
+            ..Default::default()
+        });
+        let mut scene_bvh = crate::bvh::SceneBvh::new(&scene);
+        assert_eq!(
+            scene_bvh.mesh_bounds["triangle"],
+            crate::geometry::Aabb::new(
+                nalgebra_glm::vec3(0.0, 0.0, 0.0),
+                nalgebra_glm::vec3(1.0, 1.0, 0.0)
+            )
+        );
+
+        scene.graph[node].transform.translation = nalgebra_glm::vec3(10.0, 0.0, 0.0);
+        scene_bvh.refit(&scene);
+        assert_eq!(
+            scene_bvh.query_sphere(&nalgebra_glm::vec3(10.5, 0.5, 0.0), 0.1),
+            vec![node]
+        );
+        assert!(scene_bvh
+            .query_sphere(&nalgebra_glm::vec3(0.5, 0.5, 0.0), 0.1)
+            .is_empty());
+    }
+}
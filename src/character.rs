@@ -0,0 +1,204 @@
+#[derive(Default, Debug, Copy, Clone)]
+pub struct CharacterInput {
+    /// Desired horizontal movement in world space, with a length of at most one
+    pub direction: nalgebra_glm::Vec3,
+    pub jump: bool,
+}
+
+impl CharacterInput {
+    /// Maps WASD to movement relative to the given forward direction and space to jumping
+    pub fn from_keyboard(io: &crate::io::Io, forward: &nalgebra_glm::Vec3) -> Self {
+        let forward = nalgebra_glm::vec3(forward.x, 0.0, forward.z)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(|| -nalgebra_glm::Vec3::z());
+        let right = forward.cross(&nalgebra_glm::Vec3::y());
+
+        let mut direction = nalgebra_glm::Vec3::zeros();
+        if io.is_key_pressed(winit::event::VirtualKeyCode::W) {
+            direction += forward;
+        }
+        if io.is_key_pressed(winit::event::VirtualKeyCode::S) {
+            direction -= forward;
+        }
+        if io.is_key_pressed(winit::event::VirtualKeyCode::D) {
+            direction += right;
+        }
+        if io.is_key_pressed(winit::event::VirtualKeyCode::A) {
+            direction -= right;
+        }
+
+        Self {
+            direction: direction
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(nalgebra_glm::Vec3::zeros),
+            jump: io.is_key_pressed(winit::event::VirtualKeyCode::Space),
+        }
+    }
+}
+
+/// Moves a node with a character controller component through the physics world,
+/// applying gravity and jumping, climbing steps and gentle slopes,
+/// and sliding along walls and steep slopes
+pub fn move_character(
+    physics: &crate::physics::PhysicsWorld,
+    scene: &mut crate::scene::Scene,
+    node_index: petgraph::graph::NodeIndex,
+    input: &CharacterInput,
+    delta_time: f32,
+) {
+    let Some(mut controller) = character_controller(scene, node_index) else {
+        return;
+    };
+
+    if controller.is_grounded {
+        controller.vertical_velocity = if input.jump {
+            controller.jump_speed
+        } else {
+            0.0
+        };
+    }
+    controller.vertical_velocity -= controller.gravity * delta_time;
+
+    let mut horizontal_direction = nalgebra_glm::vec3(input.direction.x, 0.0, input.direction.z);
+    if horizontal_direction.norm() > 1.0 {
+        horizontal_direction.normalize_mut();
+    }
+    let desired_translation = horizontal_direction * controller.move_speed * delta_time
+        + nalgebra_glm::Vec3::y() * controller.vertical_velocity * delta_time;
+
+    let kinematic_controller = rapier3d::control::KinematicCharacterController {
+        up: nalgebra::Vector3::y_axis(),
+        offset: rapier3d::control::CharacterLength::Absolute(0.01),
+        slide: true,
+        autostep: Some(rapier3d::control::CharacterAutostep {
+            max_height: rapier3d::control::CharacterLength::Absolute(controller.step_height),
+            min_width: rapier3d::control::CharacterLength::Absolute(controller.radius),
+            include_dynamic_bodies: false,
+        }),
+        max_slope_climb_angle: controller.max_slope_climb_angle,
+        min_slope_slide_angle: controller.min_slope_slide_angle,
+        // Snapping while moving upwards would cancel out jumps
+        snap_to_ground: (controller.vertical_velocity <= 0.0).then_some(
+            rapier3d::control::CharacterLength::Absolute(controller.snap_to_ground),
+        ),
+    };
+    let shape = rapier3d::geometry::Capsule::new_y(controller.half_height, controller.radius);
+
+    // The capsule always stays upright, regardless of the node's rotation
+    let (world_pose, _scale) = crate::physics::decompose(&scene.graph.global_transform(node_index));
+    let position = nalgebra::Isometry3::from(world_pose.translation);
+
+    // Colliders attached to the character's own node are ignored
+    let owner = node_index.index() as u128;
+    let is_not_owned =
+        |_handle, collider: &rapier3d::geometry::Collider| collider.user_data != owner;
+    let filter = rapier3d::pipeline::QueryFilter::default()
+        .exclude_sensors()
+        .predicate(&is_not_owned);
+
+    let movement = kinematic_controller.move_shape(
+        delta_time,
+        &physics.bodies,
+        &physics.colliders,
+        &physics.query_pipeline,
+        &shape,
+        &position,
+        desired_translation,
+        filter,
+        |_| {},
+    );
+
+    controller.is_grounded = movement.grounded;
+    let hit_ceiling =
+        desired_translation.y > 0.0 && movement.translation.y < desired_translation.y * 0.5;
+    if hit_ceiling {
+        controller.vertical_velocity = 0.0;
+    }
+
+    let world_pose = nalgebra::Isometry3::from_parts(
+        (world_pose.translation.vector + movement.translation).into(),
+        world_pose.rotation,
+    );
+    let (translation, _rotation) = crate::physics::world_to_local(scene, node_index, &world_pose);
+
+    let node = &mut scene.graph[node_index];
+    node.transform.translation = translation;
+    node.components.iter_mut().for_each(|component| {
+        if let crate::scene::NodeComponent::CharacterController(existing) = component {
+            *existing = controller;
+        }
+    });
+}
+
+fn character_controller(
+    scene: &crate::scene::Scene,
+    node_index: petgraph::graph::NodeIndex,
+) -> Option<crate::scene::CharacterController> {
+    scene.graph[node_index]
+        .components
+        .iter()
+        .find_map(|component| match component {
+            crate::scene::NodeComponent::CharacterController(controller) => Some(*controller),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn character_falls_and_walks_on_ground() {
+        let mut scene = crate::scene::Scene::default();
+        scene.graph.add_node(crate::scene::Node {
+            label: "Root".to_string(),
+            ..Default::default()
+        });
+        scene.add_root_node(crate::scene::Node {
+            label: "Ground".to_string(),
+            components: vec![crate::scene::NodeComponent::Collider(
+                crate::scene::Collider {
+                    shape: crate::scene::ColliderShape::Box {
+                        half_extents: nalgebra_glm::vec3(10.0, 0.5, 10.0),
+                        center: nalgebra_glm::Vec3::zeros(),
+                    },
+                    ..Default::default()
+                },
+            )],
+            ..Default::default()
+        });
+        let player = scene.add_root_node(crate::scene::Node {
+            label: "Player".to_string(),
+            transform: crate::scene::Transform {
+                translation: nalgebra_glm::vec3(0.0, 3.0, 0.0),
+                ..Default::default()
+            },
+            components: vec![crate::scene::NodeComponent::CharacterController(
+                crate::scene::CharacterController::default(),
+            )],
+            ..Default::default()
+        });
+
+        let physics = crate::physics::PhysicsWorld::from_scene(&scene);
+        let input = super::CharacterInput {
+            direction: nalgebra_glm::vec3(1.0, 0.0, 0.0),
+            jump: false,
+        };
+        for _ in 0..120 {
+            super::move_character(&physics, &mut scene, player, &input, 1.0 / 60.0);
+        }
+
+        let controller = super::character_controller(&scene, player).unwrap();
+        let translation = scene.graph[player].transform.translation;
+        let resting_height = 0.5 + controller.half_height + controller.radius;
+        assert!(controller.is_grounded);
+        assert!(
+            (translation.y - resting_height).abs() < 0.05,
+            "Character came to rest at {}",
+            translation.y
+        );
+        assert!(
+            translation.x > 5.0,
+            "Character only walked to {}",
+            translation.x
+        );
+    }
+}
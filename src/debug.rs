@@ -0,0 +1,533 @@
+/// Immediate mode debug shapes, drawn as lines depth tested against the scene.
+/// Shapes drawn with a duration of zero are only drawn for the next frame.
+#[derive(Default)]
+pub struct DebugDraw {
+    lines: Vec<DebugLine>,
+}
+
+struct DebugLine {
+    start: nalgebra_glm::Vec3,
+    end: nalgebra_glm::Vec3,
+    color: nalgebra_glm::Vec4,
+    /// The seconds left before the line expires
+    remaining: f32,
+}
+
+impl DebugDraw {
+    /// The number of segments in each circle of a sphere
+    pub const CIRCLE_SEGMENTS: usize = 32;
+
+    pub fn line(
+        &mut self,
+        start: nalgebra_glm::Vec3,
+        end: nalgebra_glm::Vec3,
+        color: nalgebra_glm::Vec4,
+        duration: f32,
+    ) {
+        self.lines.push(DebugLine {
+            start,
+            end,
+            color,
+            remaining: duration,
+        });
+    }
+
+    pub fn lines(
+        &mut self,
+        lines: &[[nalgebra_glm::Vec3; 2]],
+        color: nalgebra_glm::Vec4,
+        duration: f32,
+    ) {
+        lines
+            .iter()
+            .for_each(|[start, end]| self.line(*start, *end, color, duration));
+    }
+
+    pub fn aabb(&mut self, aabb: &crate::geometry::Aabb, color: nalgebra_glm::Vec4, duration: f32) {
+        if aabb.is_empty() {
+            return;
+        }
+        self.box_edges(&aabb.corners(), color, duration);
+    }
+
+    /// Draws three circles around the center, one in each axis aligned plane
+    pub fn sphere(
+        &mut self,
+        center: nalgebra_glm::Vec3,
+        radius: f32,
+        color: nalgebra_glm::Vec4,
+        duration: f32,
+    ) {
+        let (x, y, z) = (
+            nalgebra_glm::Vec3::x(),
+            nalgebra_glm::Vec3::y(),
+            nalgebra_glm::Vec3::z(),
+        );
+        [(x, y), (y, z), (z, x)]
+            .into_iter()
+            .for_each(|(axis_a, axis_b)| {
+                let point = |index: usize| {
+                    let angle = std::f32::consts::TAU * index as f32 / Self::CIRCLE_SEGMENTS as f32;
+                    center + (axis_a * angle.cos() + axis_b * angle.sin()) * radius
+                };
+                (0..Self::CIRCLE_SEGMENTS)
+                    .for_each(|index| self.line(point(index), point(index + 1), color, duration));
+            });
+    }
+
+    /// Draws the frustum of a view projection matrix.
+    /// Infinite perspective projections have their far plane at infinity,
+    /// so their frusta need to be drawn with a finite projection instead.
+    pub fn frustum(
+        &mut self,
+        view_projection: &nalgebra_glm::Mat4,
+        color: nalgebra_glm::Vec4,
+        duration: f32,
+    ) {
+        let inverse_view_projection = nalgebra_glm::inverse(view_projection);
+        let corners = crate::geometry::Aabb::new(
+            nalgebra_glm::vec3(-1.0, -1.0, 0.0),
+            nalgebra_glm::vec3(1.0, 1.0, 1.0),
+        )
+        .corners()
+        .map(|corner| {
+            let point =
+                inverse_view_projection * nalgebra_glm::vec4(corner.x, corner.y, corner.z, 1.0);
+            nalgebra_glm::vec4_to_vec3(&point) / point.w
+        });
+        if corners
+            .iter()
+            .any(|corner| !corner.iter().all(|component| component.is_finite()))
+        {
+            return;
+        }
+        self.box_edges(&corners, color, duration);
+    }
+
+    /// Draws the x, y and z axes of a transform in red, green and blue
+    pub fn axes(&mut self, transform: &nalgebra_glm::Mat4, size: f32, duration: f32) {
+        let origin =
+            nalgebra_glm::vec4_to_vec3(&(transform * nalgebra_glm::vec4(0.0, 0.0, 0.0, 1.0)));
+        [
+            nalgebra_glm::Vec3::x(),
+            nalgebra_glm::Vec3::y(),
+            nalgebra_glm::Vec3::z(),
+        ]
+        .into_iter()
+        .for_each(|axis| {
+            let end = nalgebra_glm::vec4_to_vec3(
+                &(transform * nalgebra_glm::vec4(axis.x * size, axis.y * size, axis.z * size, 1.0)),
+            );
+            self.line(
+                origin,
+                end,
+                nalgebra_glm::vec3_to_vec4(&axis) + nalgebra_glm::Vec4::w(),
+                duration,
+            );
+        });
+    }
+
+    /// Draws a line with an arrow head at its end
+    pub fn arrow(
+        &mut self,
+        start: nalgebra_glm::Vec3,
+        end: nalgebra_glm::Vec3,
+        color: nalgebra_glm::Vec4,
+        duration: f32,
+    ) {
+        self.line(start, end, color, duration);
+        let length = nalgebra_glm::distance(&start, &end);
+        if length <= f32::EPSILON {
+            return;
+        }
+        let direction = (end - start) / length;
+
+        // Any axis that isn't parallel to the arrow gives the directions the head spreads in
+        let reference = if direction.y.abs() < 0.99 {
+            nalgebra_glm::Vec3::y()
+        } else {
+            nalgebra_glm::Vec3::x()
+        };
+        let side = direction.cross(&reference).normalize();
+        let up = side.cross(&direction);
+        let head_length = length * 0.2;
+        [side, -side, up, -up].into_iter().for_each(|spread| {
+            let head = end - (direction - spread * 0.5) * head_length;
+            self.line(end, head, color, duration);
+        });
+    }
+
+    /// Ages the shapes by the frame's delta time, removing the ones that expired
+    pub fn update(&mut self, delta_time: f32) {
+        self.lines.retain_mut(|line| {
+            line.remaining -= delta_time;
+            line.remaining > 0.0
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn vertices(&self) -> Vec<DebugVertex> {
+        self.lines
+            .iter()
+            .flat_map(|line| {
+                [line.start, line.end].map(|position| DebugVertex {
+                    position,
+                    color: line.color,
+                })
+            })
+            .collect()
+    }
+
+    /// Draws the twelve edges between box corners ordered as in `Aabb::corners`
+    fn box_edges(
+        &mut self,
+        corners: &[nalgebra_glm::Vec3; 8],
+        color: nalgebra_glm::Vec4,
+        duration: f32,
+    ) {
+        [
+            (0, 1),
+            (2, 3),
+            (4, 5),
+            (6, 7),
+            (0, 2),
+            (1, 3),
+            (4, 6),
+            (5, 7),
+            (0, 4),
+            (1, 5),
+            (2, 6),
+            (3, 7),
+        ]
+        .into_iter()
+        .for_each(|(start, end)| self.line(corners[start], corners[end], color, duration));
+    }
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugVertex {
+    pub position: nalgebra_glm::Vec3,
+    pub color: nalgebra_glm::Vec4,
+}
+
+impl DebugVertex {
+    pub fn attributes() -> [wgpu::VertexAttribute; 2] {
+        wgpu::vertex_attr_array![
+            0 => Float32x3, // position
+            1 => Float32x4, // color
+        ]
+    }
+}
+
+/// Draws the debug lines and the ground grid into the scene render pass
+pub struct DebugRender {
+    /// Draws an infinite ground grid that fades out with distance
+    pub show_grid: bool,
+    vertex_buffer: wgpu::Buffer,
+    /// The number of vertices the vertex buffer can hold
+    vertex_capacity: usize,
+    vertex_count: u32,
+    line_pipeline: wgpu::RenderPipeline,
+    grid_pipeline: wgpu::RenderPipeline,
+}
+
+impl DebugRender {
+    const INITIAL_VERTEX_CAPACITY: usize = 1024;
+
+    /// Creates the pipelines, which read the camera from the view's uniform bind group
+    pub fn new(gpu: &crate::gpu::Gpu, uniform_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let shader_module = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Debug Shader"),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(SHADER_SOURCE)),
+            });
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Debug Pipeline Layout"),
+                bind_group_layouts: &[uniform_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let attributes = DebugVertex::attributes();
+        let line_pipeline = create_pipeline(
+            gpu,
+            &pipeline_layout,
+            &shader_module,
+            "line",
+            &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<DebugVertex>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &attributes,
+            }],
+            wgpu::PrimitiveTopology::LineList,
+        );
+        let grid_pipeline = create_pipeline(
+            gpu,
+            &pipeline_layout,
+            &shader_module,
+            "grid",
+            &[],
+            wgpu::PrimitiveTopology::TriangleList,
+        );
+        Self {
+            show_grid: false,
+            vertex_buffer: create_vertex_buffer(gpu, Self::INITIAL_VERTEX_CAPACITY),
+            vertex_capacity: Self::INITIAL_VERTEX_CAPACITY,
+            vertex_count: 0,
+            line_pipeline,
+            grid_pipeline,
+        }
+    }
+
+    /// Uploads the lines to draw this frame, growing the vertex buffer if needed
+    pub fn prepare(&mut self, gpu: &crate::gpu::Gpu, debug_draw: &DebugDraw) {
+        let vertices = debug_draw.vertices();
+        if vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = vertices.len().next_power_of_two();
+            self.vertex_buffer = create_vertex_buffer(gpu, self.vertex_capacity);
+        }
+        gpu.queue
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    /// Draws after the scene so the grid blends over it,
+    /// and the lines are drawn over the grid
+    pub fn render<'rp>(
+        &'rp self,
+        render_pass: &mut wgpu::RenderPass<'rp>,
+        uniform_bind_group: &'rp wgpu::BindGroup,
+    ) {
+        if self.show_grid {
+            render_pass.set_pipeline(&self.grid_pipeline);
+            render_pass.set_bind_group(0, uniform_bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+        if self.vertex_count > 0 {
+            render_pass.set_pipeline(&self.line_pipeline);
+            render_pass.set_bind_group(0, uniform_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.draw(0..self.vertex_count, 0..1);
+        }
+    }
+}
+
+fn create_vertex_buffer(gpu: &crate::gpu::Gpu, capacity: usize) -> wgpu::Buffer {
+    gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Debug Vertex Buffer"),
+        size: (capacity * std::mem::size_of::<DebugVertex>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Creates a pipeline from the `{name}_vertex_main` and `{name}_fragment_main` entry points
+/// that blends into the hdr target, testing against but not writing to the scene depth
+fn create_pipeline(
+    gpu: &crate::gpu::Gpu,
+    pipeline_layout: &wgpu::PipelineLayout,
+    shader_module: &wgpu::ShaderModule,
+    name: &str,
+    buffers: &[wgpu::VertexBufferLayout],
+    topology: wgpu::PrimitiveTopology,
+) -> wgpu::RenderPipeline {
+    gpu.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&format!("Debug {name} Pipeline")),
+            layout: Some(pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader_module,
+                entry_point: &format!("{name}_vertex_main"),
+                buffers,
+            },
+            primitive: wgpu::PrimitiveState {
+                topology,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: crate::gpu::Gpu::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: gpu.multisample_state(),
+            fragment: Some(wgpu::FragmentState {
+                module: shader_module,
+                entry_point: &format!("{name}_fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: crate::postprocess::HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+}
+
+const SHADER_SOURCE: &str = "
+// The leading fields of the view's uniform
+struct Uniform {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    camera_position: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> ubo: Uniform;
+
+struct LineVertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct LineVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn line_vertex_main(vert: LineVertexInput) -> LineVertexOutput {
+    var out: LineVertexOutput;
+    out.position = ubo.projection * ubo.view * vec4(vert.position, 1.0);
+    out.color = vert.color;
+    return out;
+}
+
+@fragment
+fn line_fragment_main(in: LineVertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+
+// The grid is a plane at y = 0 that follows the camera,
+// large enough that it has faded out before its edges
+const GRID_EXTENT: f32 = 1000.0;
+const GRID_FADE_DISTANCE: f32 = 150.0;
+const GRID_COLOR: vec3<f32> = vec3<f32>(0.5, 0.5, 0.5);
+const GRID_X_AXIS_COLOR: vec3<f32> = vec3<f32>(0.9, 0.2, 0.2);
+const GRID_Z_AXIS_COLOR: vec3<f32> = vec3<f32>(0.2, 0.3, 0.9);
+
+struct GridVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+};
+
+@vertex
+fn grid_vertex_main(@builtin(vertex_index) vertex_index: u32) -> GridVertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2(-1.0, -1.0),
+        vec2(1.0, -1.0),
+        vec2(1.0, 1.0),
+        vec2(-1.0, -1.0),
+        vec2(1.0, 1.0),
+        vec2(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index] * GRID_EXTENT + ubo.camera_position.xz;
+    var out: GridVertexOutput;
+    out.world_position = vec3(corner.x, 0.0, corner.y);
+    out.position = ubo.projection * ubo.view * vec4(out.world_position, 1.0);
+    return out;
+}
+
+// The coverage of lines one pixel wide spaced apart at the given interval.
+// The derivative is passed in because the gl backend emits helpers into every stage,
+// and derivatives are only available in fragment shaders.
+fn grid_coverage(coordinate: vec2<f32>, derivative: vec2<f32>, spacing: f32) -> f32 {
+    let lines = abs(fract(coordinate / spacing - 0.5) - 0.5) / (derivative / spacing);
+    return 1.0 - min(min(lines.x, lines.y), 1.0);
+}
+
+@fragment
+fn grid_fragment_main(in: GridVertexOutput) -> @location(0) vec4<f32> {
+    let coordinate = in.world_position.xz;
+    let derivative = fwidth(coordinate);
+    var color = GRID_COLOR;
+    var alpha = max(
+        grid_coverage(coordinate, derivative, 1.0) * 0.4,
+        grid_coverage(coordinate, derivative, 10.0) * 0.8,
+    );
+    if abs(coordinate.y) < derivative.y {
+        color = GRID_X_AXIS_COLOR;
+        alpha = 1.0;
+    } else if abs(coordinate.x) < derivative.x {
+        color = GRID_Z_AXIS_COLOR;
+        alpha = 1.0;
+    }
+    let distance = length(in.world_position - ubo.camera_position.xyz);
+    alpha *= 1.0 - smoothstep(GRID_FADE_DISTANCE * 0.25, GRID_FADE_DISTANCE, distance);
+    return vec4(color, alpha);
+}
+";
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn debug_lines_expire() {
+        let mut debug_draw = super::DebugDraw::default();
+        let color = nalgebra_glm::vec4(1.0, 0.0, 0.0, 1.0);
+        debug_draw.aabb(
+            &crate::geometry::Aabb::new(
+                nalgebra_glm::vec3(-1.0, -1.0, -1.0),
+                nalgebra_glm::vec3(1.0, 1.0, 1.0),
+            ),
+            color,
+            0.0,
+        );
+        debug_draw.arrow(
+            nalgebra_glm::Vec3::zeros(),
+            nalgebra_glm::Vec3::y(),
+            color,
+            1.0,
+        );
+        assert_eq!(debug_draw.vertices().len(), (12 + 5) * 2);
+
+        // Single frame lines expire after the first frame, the others after their duration
+        debug_draw.update(0.5);
+        assert_eq!(debug_draw.vertices().len(), 5 * 2);
+        debug_draw.update(0.5);
+        assert!(debug_draw.is_empty());
+    }
+
+    #[test]
+    fn grid_and_lines_are_drawn() {
+        let Some(mut renderer) = crate::render::test_renderer(64, 64, 1) else {
+            return;
+        };
+        let mut scene = crate::scene::Scene::default();
+        let mut camera_node = crate::scene::create_camera_node(1.0);
+        camera_node.transform.translation = nalgebra_glm::vec3(0.0, 2.0, 4.0);
+        let camera = scene.graph.add_node(camera_node);
+        let empty = renderer.render_to_image(&scene, camera, 64, 64);
+
+        // The grid covers the bottom half of the image, below the horizon
+        renderer.view.debug.show_grid = true;
+        let grid = renderer.render_to_image(&scene, camera, 64, 64);
+        assert_eq!(grid.get_pixel(32, 0), empty.get_pixel(32, 0));
+        assert!((32..64).any(|y| grid.get_pixel(32, y) != empty.get_pixel(32, y)));
+        renderer.view.debug.show_grid = false;
+
+        let mut debug_draw = super::DebugDraw::default();
+        debug_draw.line(
+            nalgebra_glm::vec3(-10.0, 2.0, 0.0),
+            nalgebra_glm::vec3(10.0, 2.0, 0.0),
+            nalgebra_glm::vec4(0.0, 1.0, 0.0, 1.0),
+            0.0,
+        );
+        renderer.view.debug.prepare(&renderer.gpu, &debug_draw);
+        let line = renderer.render_to_image(&scene, camera, 64, 64);
+        assert!(line
+            .pixels()
+            .zip(empty.pixels())
+            .any(|(line_pixel, empty_pixel)| line_pixel[1] > empty_pixel[1]));
+    }
+}
@@ -0,0 +1,1086 @@
+/// The resolution of each face of the cubemap the skybox samples
+const ENVIRONMENT_SIZE: u32 = 512;
+
+/// The resolution of each face of the diffuse irradiance cubemap
+const IRRADIANCE_SIZE: u32 = 32;
+
+/// The resolution of the first mip of the prefiltered specular cubemap
+const PREFILTERED_SIZE: u32 = 128;
+
+/// Each mip of the prefiltered cubemap is convolved for a roughness from zero up to one
+pub const PREFILTERED_MIP_LEVELS: u32 = 5;
+
+const BRDF_LUT_SIZE: u32 = 128;
+const BRDF_LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+/// Enough for converting, downsampling and convolving every face of every mip
+const MAX_FILTER_PASSES: usize = 128;
+
+#[repr(C, align(256))]
+#[derive(Default, Copy, Clone, Debug, bytemuck::Zeroable)]
+struct FilterUniform {
+    face: u32,
+    roughness: f32,
+    /// The resolution of the environment cubemap's first mip
+    source_size: f32,
+    /// The lowest environment mip sampled, to avoid aliasing when filtering into smaller targets
+    source_lod: f32,
+}
+
+#[repr(C)]
+#[derive(Default, Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyboxUniform {
+    /// Maps clip space to world space directions, ignoring the camera's translation
+    inverse_view_projection: nalgebra_glm::Mat4,
+    intensity: f32,
+    _padding: [u32; 3],
+}
+
+/// The cubemaps and lookup table used for image based lighting, and the skybox.
+///
+/// Loading an equirectangular hdr image converts it to a cubemap with a full mip chain,
+/// then convolves that into a diffuse irradiance cubemap and a specular cubemap
+/// prefiltered for increasing roughness along its mips.
+/// The brdf lookup table doesn't depend on the image and is computed once.
+pub struct Environment {
+    /// The settings the environment was last prepared with
+    pub settings: Option<crate::scene::Environment>,
+    /// Whether the image in the settings was loaded successfully
+    loaded: bool,
+    pub environment_view: wgpu::TextureView,
+    pub irradiance_view: wgpu::TextureView,
+    pub prefiltered_view: wgpu::TextureView,
+    pub brdf_lut_view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    filter_uniform_buffer: wgpu::Buffer,
+    equirectangular_bind_group_layout: wgpu::BindGroupLayout,
+    cube_bind_group_layout: wgpu::BindGroupLayout,
+    filter_pipelines: std::collections::HashMap<&'static str, wgpu::RenderPipeline>,
+    skybox_uniform_buffer: wgpu::Buffer,
+    skybox_bind_group_layout: wgpu::BindGroupLayout,
+    skybox_bind_group: wgpu::BindGroup,
+    skybox_pipeline: wgpu::RenderPipeline,
+}
+
+impl Environment {
+    pub fn new(gpu: &crate::gpu::Gpu) -> Self {
+        let sampler = gpu.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Environment Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let filter_uniform_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Environment Filter Uniform Buffer"),
+            size: MAX_FILTER_PASSES as wgpu::BufferAddress * gpu.alignment(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let filter_uniform_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<FilterUniform>() as _),
+            },
+            count: None,
+        };
+        let equirectangular_bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Equirectangular Bind Group Layout"),
+                    entries: &[
+                        filter_uniform_entry,
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+        let cube_bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Environment Cube Bind Group Layout"),
+                    entries: &[
+                        filter_uniform_entry,
+                        cube_texture_entry(2, wgpu::ShaderStages::FRAGMENT),
+                        sampler_entry(3, wgpu::ShaderStages::FRAGMENT),
+                    ],
+                });
+
+        let shader_module = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Environment Filter Shader"),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(FILTER_SHADER_SOURCE)),
+            });
+        let filter_pipelines = [
+            (
+                "equirectangular_to_cube_main",
+                Some(&equirectangular_bind_group_layout),
+                crate::postprocess::HDR_FORMAT,
+            ),
+            (
+                "downsample_main",
+                Some(&cube_bind_group_layout),
+                crate::postprocess::HDR_FORMAT,
+            ),
+            (
+                "irradiance_main",
+                Some(&cube_bind_group_layout),
+                crate::postprocess::HDR_FORMAT,
+            ),
+            (
+                "prefilter_main",
+                Some(&cube_bind_group_layout),
+                crate::postprocess::HDR_FORMAT,
+            ),
+            ("brdf_main", None, BRDF_LUT_FORMAT),
+        ]
+        .into_iter()
+        .map(|(entry_point, bind_group_layout, format)| {
+            let pipeline =
+                create_filter_pipeline(gpu, &shader_module, entry_point, bind_group_layout, format);
+            (entry_point, pipeline)
+        })
+        .collect::<std::collections::HashMap<_, _>>();
+
+        // Without an image, every cubemap is a single black texel
+        let environment_view = create_cube_texture(gpu, 1, 1, "Environment Cubemap")
+            .create_view(&cube_view_descriptor(0, None));
+        let irradiance_view = create_cube_texture(gpu, 1, 1, "Irradiance Cubemap")
+            .create_view(&cube_view_descriptor(0, None));
+        let prefiltered_view = create_cube_texture(gpu, 1, 1, "Prefiltered Cubemap")
+            .create_view(&cube_view_descriptor(0, None));
+        let brdf_lut_view = create_brdf_lut(gpu, &filter_pipelines["brdf_main"]);
+
+        let skybox_uniform_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Skybox Uniform Buffer"),
+            size: std::mem::size_of::<SkyboxUniform>() as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let skybox_bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Skybox Bind Group Layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        cube_texture_entry(1, wgpu::ShaderStages::FRAGMENT),
+                        sampler_entry(2, wgpu::ShaderStages::FRAGMENT),
+                    ],
+                });
+        let skybox_bind_group = create_skybox_bind_group(
+            gpu,
+            &skybox_bind_group_layout,
+            &skybox_uniform_buffer,
+            &environment_view,
+            &sampler,
+        );
+        let skybox_pipeline = create_skybox_pipeline(gpu, &skybox_bind_group_layout);
+
+        Self {
+            settings: None,
+            loaded: false,
+            environment_view,
+            irradiance_view,
+            prefiltered_view,
+            brdf_lut_view,
+            sampler,
+            filter_uniform_buffer,
+            equirectangular_bind_group_layout,
+            cube_bind_group_layout,
+            filter_pipelines,
+            skybox_uniform_buffer,
+            skybox_bind_group_layout,
+            skybox_bind_group,
+            skybox_pipeline,
+        }
+    }
+
+    /// Whether an image is loaded and lights the scene
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    /// The scale of the environment's light, or zero if no image is loaded
+    pub fn intensity(&self) -> f32 {
+        match self.settings.as_ref() {
+            Some(settings) if self.loaded => settings.intensity,
+            _ => 0.0,
+        }
+    }
+
+    /// Loads the scene's environment image if it changed and updates the skybox for the camera.
+    /// Returns true if the environment's texture views were replaced.
+    pub fn prepare(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        settings: Option<&crate::scene::Environment>,
+        view: &nalgebra_glm::Mat4,
+        projection: &nalgebra_glm::Mat4,
+    ) -> bool {
+        let loaded_path = self
+            .settings
+            .as_ref()
+            .map(|settings| settings.path.as_str());
+        let requested_path = settings.map(|settings| settings.path.as_str());
+        let reloaded = loaded_path != requested_path;
+        if reloaded {
+            // Failed loads are remembered in the settings so they aren't retried every frame
+            self.loaded = match settings {
+                Some(settings) => match self.load(gpu, &settings.path) {
+                    Ok(()) => true,
+                    Err(error) => {
+                        log::warn!("Failed to load environment '{}': {error}", settings.path);
+                        false
+                    }
+                },
+                None => false,
+            };
+        }
+        self.settings = settings.cloned();
+
+        let mut rotation = *view;
+        rotation.set_column(3, &nalgebra_glm::vec4(0.0, 0.0, 0.0, 1.0));
+        gpu.queue.write_buffer(
+            &self.skybox_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[SkyboxUniform {
+                inverse_view_projection: nalgebra_glm::inverse(&(projection * rotation)),
+                intensity: self.intensity(),
+                ..Default::default()
+            }]),
+        );
+        reloaded
+    }
+
+    /// Draws the environment behind everything already in the render pass,
+    /// if the loaded environment is shown as a skybox
+    pub fn render_skybox<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>) {
+        if !self.loaded
+            || !self
+                .settings
+                .as_ref()
+                .is_some_and(|settings| settings.skybox)
+        {
+            return;
+        }
+        render_pass.set_pipeline(&self.skybox_pipeline);
+        render_pass.set_bind_group(0, &self.skybox_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Reads an equirectangular hdr image and filters it into the environment cubemaps
+    fn load(&mut self, gpu: &crate::gpu::Gpu, path: &str) -> image::ImageResult<()> {
+        let image = image::open(path)?.into_rgba32f();
+        let (width, height) = image.dimensions();
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let equirectangular_texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Equirectangular Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        gpu.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &equirectangular_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(image.as_raw()),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 16),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+        let equirectangular_view =
+            equirectangular_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let environment_mips = crate::texture::mip_level_count(ENVIRONMENT_SIZE, ENVIRONMENT_SIZE);
+        let environment_texture = create_cube_texture(
+            gpu,
+            ENVIRONMENT_SIZE,
+            environment_mips,
+            "Environment Cubemap",
+        );
+        let irradiance_texture = create_cube_texture(gpu, IRRADIANCE_SIZE, 1, "Irradiance Cubemap");
+        let prefiltered_texture = create_cube_texture(
+            gpu,
+            PREFILTERED_SIZE,
+            PREFILTERED_MIP_LEVELS,
+            "Prefiltered Cubemap",
+        );
+        let environment_view = environment_texture.create_view(&cube_view_descriptor(0, None));
+
+        // Every pass is recorded first, so all of their uniforms are written at once
+        let mut uniforms = Vec::new();
+        let mut passes = Vec::new();
+        let uniform = |face, roughness, target_size: u32| FilterUniform {
+            face,
+            roughness,
+            source_size: ENVIRONMENT_SIZE as f32,
+            source_lod: (ENVIRONMENT_SIZE as f32 / target_size as f32)
+                .log2()
+                .max(0.0),
+        };
+        let equirectangular_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Equirectangular Bind Group"),
+            layout: &self.equirectangular_bind_group_layout,
+            entries: &[
+                self.filter_uniform_entry(),
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&equirectangular_view),
+                },
+            ],
+        });
+        (0..6).for_each(|face| {
+            uniforms.push(uniform(face, 0.0, ENVIRONMENT_SIZE));
+            passes.push((
+                "equirectangular_to_cube_main",
+                &equirectangular_bind_group,
+                face_view(&environment_texture, face, 0),
+            ));
+        });
+
+        // Each mip is downsampled from the one above it, which is bound on its own
+        let mip_bind_groups = (0..environment_mips)
+            .map(|mip| {
+                let mip_view = environment_texture.create_view(&cube_view_descriptor(mip, Some(1)));
+                self.create_cube_bind_group(gpu, &mip_view)
+            })
+            .collect::<Vec<_>>();
+        let environment_bind_group = self.create_cube_bind_group(gpu, &environment_view);
+        (1..environment_mips).for_each(|mip| {
+            (0..6).for_each(|face| {
+                uniforms.push(uniform(face, 0.0, ENVIRONMENT_SIZE >> mip));
+                passes.push((
+                    "downsample_main",
+                    &mip_bind_groups[mip as usize - 1],
+                    face_view(&environment_texture, face, mip),
+                ));
+            });
+        });
+        (0..6).for_each(|face| {
+            uniforms.push(uniform(face, 0.0, IRRADIANCE_SIZE * 2));
+            passes.push((
+                "irradiance_main",
+                &environment_bind_group,
+                face_view(&irradiance_texture, face, 0),
+            ));
+        });
+        (0..PREFILTERED_MIP_LEVELS).for_each(|mip| {
+            let roughness = mip as f32 / (PREFILTERED_MIP_LEVELS - 1) as f32;
+            (0..6).for_each(|face| {
+                uniforms.push(uniform(face, roughness, PREFILTERED_SIZE >> mip));
+                passes.push((
+                    "prefilter_main",
+                    &environment_bind_group,
+                    face_view(&prefiltered_texture, face, mip),
+                ));
+            });
+        });
+
+        let mut padded_uniforms = vec![FilterUniform::default(); MAX_FILTER_PASSES];
+        padded_uniforms[..uniforms.len()].copy_from_slice(&uniforms);
+        gpu.queue
+            .write_buffer(&self.filter_uniform_buffer, 0, unsafe {
+                std::slice::from_raw_parts(
+                    padded_uniforms.as_ptr() as *const u8,
+                    padded_uniforms.len() * gpu.alignment() as usize,
+                )
+            });
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Environment Filter Encoder"),
+            });
+        passes
+            .iter()
+            .enumerate()
+            .for_each(|(index, (entry_point, bind_group, target_view))| {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Environment Filter Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: target_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: true,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                });
+                render_pass.set_pipeline(&self.filter_pipelines[entry_point]);
+                render_pass.set_bind_group(
+                    0,
+                    bind_group,
+                    &[(index as u64 * gpu.alignment()) as wgpu::DynamicOffset],
+                );
+                render_pass.draw(0..3, 0..1);
+            });
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+
+        self.irradiance_view = irradiance_texture.create_view(&cube_view_descriptor(0, None));
+        self.prefiltered_view = prefiltered_texture.create_view(&cube_view_descriptor(0, None));
+        self.skybox_bind_group = create_skybox_bind_group(
+            gpu,
+            &self.skybox_bind_group_layout,
+            &self.skybox_uniform_buffer,
+            &environment_view,
+            &self.sampler,
+        );
+        self.environment_view = environment_view;
+        Ok(())
+    }
+
+    fn filter_uniform_entry(&self) -> wgpu::BindGroupEntry {
+        wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &self.filter_uniform_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(std::mem::size_of::<FilterUniform>() as _),
+            }),
+        }
+    }
+
+    fn create_cube_bind_group(
+        &self,
+        gpu: &crate::gpu::Gpu,
+        cube_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Environment Cube Bind Group"),
+            layout: &self.cube_bind_group_layout,
+            entries: &[
+                self.filter_uniform_entry(),
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(cube_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+}
+
+/// The layout entry of a filterable cubemap
+pub fn cube_texture_entry(
+    binding: u32,
+    visibility: wgpu::ShaderStages,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::Cube,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+pub fn sampler_entry(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    }
+}
+
+fn create_cube_texture(
+    gpu: &crate::gpu::Gpu,
+    size: u32,
+    mip_level_count: u32,
+    label: &str,
+) -> wgpu::Texture {
+    gpu.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: crate::postprocess::HDR_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+fn cube_view_descriptor(
+    base_mip_level: u32,
+    mip_level_count: Option<u32>,
+) -> wgpu::TextureViewDescriptor<'static> {
+    wgpu::TextureViewDescriptor {
+        label: Some("Cubemap"),
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        base_mip_level,
+        mip_level_count,
+        ..Default::default()
+    }
+}
+
+/// A view of a single face of a single cubemap mip, to render into
+fn face_view(texture: &wgpu::Texture, face: u32, mip: u32) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Cubemap Face"),
+        dimension: Some(wgpu::TextureViewDimension::D2),
+        base_mip_level: mip,
+        mip_level_count: Some(1),
+        base_array_layer: face,
+        array_layer_count: Some(1),
+        ..Default::default()
+    })
+}
+
+/// Integrates the specular brdf for each combination of view angle and roughness
+fn create_brdf_lut(gpu: &crate::gpu::Gpu, pipeline: &wgpu::RenderPipeline) -> wgpu::TextureView {
+    let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("BRDF Lut"),
+        size: wgpu::Extent3d {
+            width: BRDF_LUT_SIZE,
+            height: BRDF_LUT_SIZE,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: BRDF_LUT_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let mut encoder = gpu
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("BRDF Lut Encoder"),
+        });
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("BRDF Lut Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.draw(0..3, 0..1);
+    }
+    gpu.queue.submit(std::iter::once(encoder.finish()));
+    view
+}
+
+fn create_skybox_bind_group(
+    gpu: &crate::gpu::Gpu,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    environment_view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Skybox Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(environment_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
+
+fn create_filter_pipeline(
+    gpu: &crate::gpu::Gpu,
+    shader_module: &wgpu::ShaderModule,
+    entry_point: &str,
+    bind_group_layout: Option<&wgpu::BindGroupLayout>,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let pipeline_layout = gpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Environment Filter Pipeline Layout"),
+            bind_group_layouts: bind_group_layout.as_slice(),
+            push_constant_ranges: &[],
+        });
+    gpu.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Environment Filter Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader_module,
+                entry_point: "fullscreen_vertex_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: shader_module,
+                entry_point,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+}
+
+fn create_skybox_pipeline(
+    gpu: &crate::gpu::Gpu,
+    bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let shader_module = gpu
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Skybox Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(SKYBOX_SHADER_SOURCE)),
+        });
+    let pipeline_layout = gpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            bind_group_layouts: &[bind_group_layout],
+            push_constant_ranges: &[],
+        });
+    gpu.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Skybox Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vertex_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            // The skybox is drawn on the far plane, wherever nothing else was drawn
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: crate::gpu::Gpu::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: gpu.multisample_state(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fragment_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: crate::postprocess::HDR_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+}
+
+/// Declares the environment bindings of the lighting bind group
+/// and an `environment_lighting` function returning the light a surface receives from the environment
+pub const SHADER_SOURCE: &str = "
+@group(3) @binding(4)
+var irradiance_map: texture_cube<f32>;
+
+@group(3) @binding(5)
+var prefiltered_map: texture_cube<f32>;
+
+@group(3) @binding(6)
+var brdf_lut: texture_2d<f32>;
+
+@group(3) @binding(7)
+var environment_sampler: sampler;
+
+const PREFILTERED_MAX_LOD: f32 = 4.0;
+
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Split sum image based lighting, with diffuse light from the irradiance map
+// and specular light from the prefiltered map scaled by the brdf lut
+fn environment_lighting(
+    normal: vec3<f32>,
+    view_direction: vec3<f32>,
+    albedo: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+) -> vec3<f32> {
+    let n_dot_v = max(dot(normal, view_direction), 0.0);
+    let f0 = mix(vec3(0.04), albedo, metallic);
+    let fresnel = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+    let diffuse_weight = (1.0 - fresnel) * (1.0 - metallic);
+
+    let irradiance = textureSample(irradiance_map, environment_sampler, normal).rgb;
+    let diffuse = diffuse_weight * irradiance * albedo;
+
+    let reflection = reflect(-view_direction, normal);
+    let prefiltered = textureSampleLevel(
+        prefiltered_map,
+        environment_sampler,
+        reflection,
+        roughness * PREFILTERED_MAX_LOD
+    ).rgb;
+    let brdf = textureSample(brdf_lut, environment_sampler, vec2(n_dot_v, roughness)).rg;
+    let specular = prefiltered * (fresnel * brdf.x + brdf.y);
+
+    return diffuse + specular;
+}
+";
+
+const FILTER_SHADER_SOURCE: &str = "
+struct FilterUniform {
+    face: u32,
+    roughness: f32,
+    source_size: f32,
+    source_lod: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: FilterUniform;
+
+@group(0) @binding(1)
+var equirectangular_texture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var environment_texture: texture_cube<f32>;
+
+@group(0) @binding(3)
+var environment_sampler: sampler;
+
+const PI: f32 = 3.14159265359;
+const TAU: f32 = 6.28318530718;
+const MAX_RADIANCE: f32 = 65504.0;
+const IRRADIANCE_SAMPLE_DELTA: f32 = 0.05;
+const PREFILTER_SAMPLE_COUNT: u32 = 128u;
+const BRDF_SAMPLE_COUNT: u32 = 256u;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn fullscreen_vertex_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let position = vec2(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u)) * 2.0 - 1.0;
+    var out: VertexOutput;
+    out.position = vec4(position, 0.0, 1.0);
+    out.uv = vec2(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+    return out;
+}
+
+// The direction through a texel of a cubemap face, where uv increases right and down
+fn cube_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let st = uv * 2.0 - 1.0;
+    var direction: vec3<f32>;
+    switch face {
+        case 0u: { direction = vec3(1.0, -st.y, -st.x); }
+        case 1u: { direction = vec3(-1.0, -st.y, st.x); }
+        case 2u: { direction = vec3(st.x, 1.0, st.y); }
+        case 3u: { direction = vec3(st.x, -1.0, -st.y); }
+        case 4u: { direction = vec3(st.x, -st.y, 1.0); }
+        default: { direction = vec3(-st.x, -st.y, -1.0); }
+    }
+    return normalize(direction);
+}
+
+fn load_equirectangular(texel: vec2<i32>) -> vec3<f32> {
+    return min(textureLoad(equirectangular_texture, texel, 0).rgb, vec3(MAX_RADIANCE));
+}
+
+// Float textures aren't filterable everywhere, so texels are blended manually,
+// wrapping around horizontally
+fn sample_equirectangular(direction: vec3<f32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(equirectangular_texture));
+    let uv = vec2(
+        atan2(direction.z, direction.x) / TAU + 0.5,
+        acos(clamp(direction.y, -1.0, 1.0)) / PI
+    );
+    let texel = uv * vec2<f32>(size) - 0.5;
+    let base = floor(texel);
+    let blend = texel - base;
+    let x0 = ((i32(base.x) % size.x) + size.x) % size.x;
+    let x1 = (x0 + 1) % size.x;
+    let y0 = clamp(i32(base.y), 0, size.y - 1);
+    let y1 = clamp(i32(base.y) + 1, 0, size.y - 1);
+    let top = mix(load_equirectangular(vec2(x0, y0)), load_equirectangular(vec2(x1, y0)), blend.x);
+    let bottom = mix(load_equirectangular(vec2(x0, y1)), load_equirectangular(vec2(x1, y1)), blend.x);
+    return mix(top, bottom, blend.y);
+}
+
+fn radical_inverse(index: u32) -> f32 {
+    var bits = (index << 16u) | (index >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return f32(bits) * 2.3283064365386963e-10;
+}
+
+fn hammersley(index: u32, count: u32) -> vec2<f32> {
+    return vec2(f32(index) / f32(count), radical_inverse(index));
+}
+
+fn importance_sample_ggx(xi: vec2<f32>, normal: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = TAU * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    let halfway = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    var up = vec3(1.0, 0.0, 0.0);
+    if abs(normal.z) < 0.999 {
+        up = vec3(0.0, 0.0, 1.0);
+    }
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+    return normalize(tangent * halfway.x + bitangent * halfway.y + normal * halfway.z);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denominator = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denominator * denominator);
+}
+
+fn geometry_schlick_ggx(n_dot_v: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    return n_dot_v / (n_dot_v * (1.0 - k) + k);
+}
+
+@fragment
+fn equirectangular_to_cube_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(sample_equirectangular(cube_direction(params.face, in.uv)), 1.0);
+}
+
+// Bilinear filtering at the center of each texel averages the four texels of the mip above
+@fragment
+fn downsample_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = cube_direction(params.face, in.uv);
+    return textureSampleLevel(environment_texture, environment_sampler, direction, 0.0);
+}
+
+// Integrates the cosine weighted light arriving over the hemisphere around each direction
+@fragment
+fn irradiance_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = cube_direction(params.face, in.uv);
+    var up = vec3(0.0, 1.0, 0.0);
+    if abs(normal.y) > 0.999 {
+        up = vec3(0.0, 0.0, 1.0);
+    }
+    let right = normalize(cross(up, normal));
+    up = cross(normal, right);
+
+    var irradiance = vec3(0.0);
+    var sample_count = 0.0;
+    for (var phi = 0.0; phi < TAU; phi += IRRADIANCE_SAMPLE_DELTA) {
+        for (var theta = 0.0; theta < 0.5 * PI; theta += IRRADIANCE_SAMPLE_DELTA) {
+            let tangent = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let direction = tangent.x * right + tangent.y * up + tangent.z * normal;
+            let radiance = textureSampleLevel(
+                environment_texture,
+                environment_sampler,
+                direction,
+                params.source_lod
+            ).rgb;
+            irradiance += radiance * cos(theta) * sin(theta);
+            sample_count += 1.0;
+        }
+    }
+    return vec4(PI * irradiance / sample_count, 1.0);
+}
+
+// Convolves the environment with the ggx distribution, assuming the view is along the normal,
+// sampling blurrier mips where samples are sparse
+@fragment
+fn prefilter_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = cube_direction(params.face, in.uv);
+    if params.roughness == 0.0 {
+        return textureSampleLevel(environment_texture, environment_sampler, normal, params.source_lod);
+    }
+
+    let texel_solid_angle = 4.0 * PI / (6.0 * params.source_size * params.source_size);
+    var color = vec3(0.0);
+    var total_weight = 0.0;
+    for (var index = 0u; index < PREFILTER_SAMPLE_COUNT; index += 1u) {
+        let xi = hammersley(index, PREFILTER_SAMPLE_COUNT);
+        let halfway = importance_sample_ggx(xi, normal, params.roughness);
+        let light = normalize(2.0 * dot(normal, halfway) * halfway - normal);
+        let n_dot_l = dot(normal, light);
+        if n_dot_l > 0.0 {
+            let n_dot_h = max(dot(normal, halfway), 0.0);
+            let pdf = distribution_ggx(n_dot_h, params.roughness) / 4.0 + 0.0001;
+            let sample_solid_angle = 1.0 / (f32(PREFILTER_SAMPLE_COUNT) * pdf + 0.0001);
+            let lod = max(0.5 * log2(sample_solid_angle / texel_solid_angle), params.source_lod);
+            color += textureSampleLevel(environment_texture, environment_sampler, light, lod).rgb
+                * n_dot_l;
+            total_weight += n_dot_l;
+        }
+    }
+    return vec4(color / max(total_weight, 0.0001), 1.0);
+}
+
+// The scale and bias applied to the fresnel reflectance at normal incidence,
+// for the cosine of the view angle along u and roughness along v
+@fragment
+fn brdf_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n_dot_v = max(in.uv.x, 0.001);
+    let roughness = in.uv.y;
+    let view = vec3(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let normal = vec3(0.0, 0.0, 1.0);
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var index = 0u; index < BRDF_SAMPLE_COUNT; index += 1u) {
+        let xi = hammersley(index, BRDF_SAMPLE_COUNT);
+        let halfway = importance_sample_ggx(xi, normal, roughness);
+        let light = normalize(2.0 * dot(view, halfway) * halfway - view);
+        let n_dot_l = max(light.z, 0.0);
+        if n_dot_l > 0.0 {
+            let n_dot_h = max(halfway.z, 0.0);
+            let v_dot_h = max(dot(view, halfway), 0.0);
+            let geometry = geometry_schlick_ggx(n_dot_v, roughness)
+                * geometry_schlick_ggx(n_dot_l, roughness);
+            let visibility = geometry * v_dot_h / (n_dot_h * n_dot_v);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+    return vec4(scale, bias, 0.0, 1.0) / vec4(f32(BRDF_SAMPLE_COUNT), f32(BRDF_SAMPLE_COUNT), 1.0, 1.0);
+}
+";
+
+const SKYBOX_SHADER_SOURCE: &str = "
+struct SkyboxUniform {
+    inverse_view_projection: mat4x4<f32>,
+    intensity: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> skybox: SkyboxUniform;
+
+@group(0) @binding(1)
+var environment_texture: texture_cube<f32>;
+
+@group(0) @binding(2)
+var environment_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) clip_position: vec2<f32>,
+};
+
+// A fullscreen triangle on the far plane,
+// with the direction to each pixel found by unprojecting it onto the near plane
+@vertex
+fn vertex_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let position = vec2(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u)) * 2.0 - 1.0;
+    var out: VertexOutput;
+    out.position = vec4(position, 1.0, 1.0);
+    out.clip_position = position;
+    return out;
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let world = skybox.inverse_view_projection * vec4(in.clip_position, 0.0, 1.0);
+    let direction = normalize(world.xyz / world.w);
+    let color = textureSampleLevel(environment_texture, environment_sampler, direction, 0.0).rgb;
+    return vec4(color * skybox.intensity, 1.0);
+}
+";
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn environment_lights_scene() {
+        let Some(mut fixture) = crate::render::HelmetFixture::new() else {
+            return;
+        };
+        let unlit = fixture.image.clone();
+
+        // Missing images fall back to the constant ambient light
+        fixture.scene.environment = Some(crate::scene::Environment {
+            path: "resources/textures/missing.hdr".to_string(),
+            ..Default::default()
+        });
+        let missing = fixture.render();
+        assert!(!fixture.renderer.view.lighting.environment.is_loaded());
+        assert_eq!(missing, unlit);
+
+        fixture.scene.environment = Some(crate::scene::Environment::default());
+        let lit = fixture.render();
+        assert!(fixture.renderer.view.lighting.environment.is_loaded());
+
+        // The environment is brighter than the ambient light on the helmet
+        let background = *unlit.get_pixel(0, 0);
+        let helmet_brightness = |image: &image::RgbaImage| {
+            image
+                .pixels()
+                .zip(unlit.pixels())
+                .filter(|(_, unlit_pixel)| **unlit_pixel != background)
+                .map(|(pixel, _)| pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32)
+                .sum::<u32>()
+        };
+        assert!(helmet_brightness(&lit) > helmet_brightness(&unlit));
+        assert_ne!(*lit.get_pixel(0, 0), background, "The skybox wasn't drawn");
+    }
+}
@@ -0,0 +1,344 @@
+#[derive(Default, Debug, Copy, Clone)]
+pub struct Ray {
+    pub origin: nalgebra_glm::Vec3,
+    pub direction: nalgebra_glm::Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: nalgebra_glm::Vec3, direction: nalgebra_glm::Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    /// Creates a world space ray passing through a pixel of the viewport.
+    /// The screen position is in physical pixels with the origin at the top left.
+    pub fn from_screen(
+        screen_position: nalgebra_glm::Vec2,
+        viewport_size: nalgebra_glm::Vec2,
+        projection: &nalgebra_glm::Mat4,
+        view: &nalgebra_glm::Mat4,
+    ) -> Option<Self> {
+        let ndc = nalgebra_glm::vec2(
+            (2.0 * screen_position.x / viewport_size.x.max(1.0)) - 1.0,
+            1.0 - (2.0 * screen_position.y / viewport_size.y.max(1.0)),
+        );
+        let inverse_view_projection = nalgebra_glm::inverse(&(projection * view));
+
+        // Depth 1.0 is at infinity for infinite perspective projections,
+        // so a point halfway into the depth range is used to find the direction
+        let unproject = |depth: f32| {
+            let point = inverse_view_projection * nalgebra_glm::vec4(ndc.x, ndc.y, depth, 1.0);
+            nalgebra_glm::vec4_to_vec3(&point) / point.w
+        };
+        let near = unproject(0.0);
+        let far = unproject(0.5);
+
+        let direction = far - near;
+        if !direction.iter().all(|component| component.is_finite())
+            || nalgebra_glm::length(&direction) <= f32::EPSILON
+        {
+            return None;
+        }
+        Some(Self::new(near, direction))
+    }
+
+    pub fn at(&self, distance: f32) -> nalgebra_glm::Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    pub fn transform(&self, matrix: &nalgebra_glm::Mat4) -> Self {
+        let origin = matrix * nalgebra_glm::vec4(self.origin.x, self.origin.y, self.origin.z, 1.0);
+        let direction =
+            matrix * nalgebra_glm::vec4(self.direction.x, self.direction.y, self.direction.z, 0.0);
+        Self::new(
+            nalgebra_glm::vec4_to_vec3(&origin),
+            nalgebra_glm::vec4_to_vec3(&direction),
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Aabb {
+    pub min: nalgebra_glm::Vec3,
+    pub max: nalgebra_glm::Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Aabb {
+    pub fn new(min: nalgebra_glm::Vec3, max: nalgebra_glm::Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// An inverted box that becomes valid as soon as a point is added to it
+    pub fn empty() -> Self {
+        Self {
+            min: nalgebra_glm::Vec3::repeat(f32::MAX),
+            max: nalgebra_glm::Vec3::repeat(f32::MIN),
+        }
+    }
+
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a nalgebra_glm::Vec3>) -> Self {
+        let mut aabb = Self::empty();
+        points
+            .into_iter()
+            .for_each(|point| aabb.expand_to_include(point));
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn center(&self) -> nalgebra_glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extents(&self) -> nalgebra_glm::Vec3 {
+        self.max - self.min
+    }
+
+    pub fn half_extents(&self) -> nalgebra_glm::Vec3 {
+        self.extents() * 0.5
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let extents = self.extents();
+        2.0 * (extents.x * extents.y + extents.y * extents.z + extents.z * extents.x)
+    }
+
+    pub fn expand_to_include(&mut self, point: &nalgebra_glm::Vec3) {
+        self.min = nalgebra_glm::min2(&self.min, point);
+        self.max = nalgebra_glm::max2(&self.max, point);
+    }
+
+    pub fn merge(&self, other: &Self) -> Self {
+        Self {
+            min: nalgebra_glm::min2(&self.min, &other.min),
+            max: nalgebra_glm::max2(&self.max, &other.max),
+        }
+    }
+
+    pub fn corners(&self) -> [nalgebra_glm::Vec3; 8] {
+        [
+            nalgebra_glm::vec3(self.min.x, self.min.y, self.min.z),
+            nalgebra_glm::vec3(self.max.x, self.min.y, self.min.z),
+            nalgebra_glm::vec3(self.min.x, self.max.y, self.min.z),
+            nalgebra_glm::vec3(self.max.x, self.max.y, self.min.z),
+            nalgebra_glm::vec3(self.min.x, self.min.y, self.max.z),
+            nalgebra_glm::vec3(self.max.x, self.min.y, self.max.z),
+            nalgebra_glm::vec3(self.min.x, self.max.y, self.max.z),
+            nalgebra_glm::vec3(self.max.x, self.max.y, self.max.z),
+        ]
+    }
+
+    /// Returns the box enclosing this box after it has been transformed
+    pub fn transform(&self, matrix: &nalgebra_glm::Mat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        let corners = self.corners().map(|corner| {
+            nalgebra_glm::vec4_to_vec3(
+                &(matrix * nalgebra_glm::vec4(corner.x, corner.y, corner.z, 1.0)),
+            )
+        });
+        Self::from_points(corners.iter())
+    }
+
+    pub fn contains_point(&self, point: &nalgebra_glm::Vec3) -> bool {
+        (0..3).all(|axis| point[axis] >= self.min[axis] && point[axis] <= self.max[axis])
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.max[axis] && self.max[axis] >= other.min[axis])
+    }
+
+    pub fn closest_point(&self, point: &nalgebra_glm::Vec3) -> nalgebra_glm::Vec3 {
+        nalgebra_glm::clamp_vec(point, &self.min, &self.max)
+    }
+
+    pub fn distance_squared_to_point(&self, point: &nalgebra_glm::Vec3) -> f32 {
+        nalgebra_glm::length2(&(self.closest_point(point) - point))
+    }
+
+    pub fn intersects_sphere(&self, center: &nalgebra_glm::Vec3, radius: f32) -> bool {
+        self.distance_squared_to_point(center) <= radius * radius
+    }
+
+    /// Slab test returning the distance along the ray at which it enters the box,
+    /// or zero if the ray starts inside of it
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<f32> {
+        let mut t_min = 0.0_f32;
+        let mut t_max = f32::MAX;
+        for axis in 0..3 {
+            let inverse_direction = 1.0 / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inverse_direction;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inverse_direction;
+            if inverse_direction < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN comparisons fall through here, which happens when the ray
+            // is parallel to and exactly on a slab boundary
+            if t0 > t_min {
+                t_min = t0;
+            }
+            if t1 < t_max {
+                t_max = t1;
+            }
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some(t_min)
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone)]
+pub struct TriangleHit {
+    pub distance: f32,
+    /// Weights of the three triangle vertices at the hit point
+    pub barycentrics: nalgebra_glm::Vec3,
+}
+
+/// Möller–Trumbore ray-triangle intersection, hitting both faces of the triangle
+pub fn intersect_ray_triangle(
+    ray: &Ray,
+    a: &nalgebra_glm::Vec3,
+    b: &nalgebra_glm::Vec3,
+    c: &nalgebra_glm::Vec3,
+) -> Option<TriangleHit> {
+    let edge_1 = b - a;
+    let edge_2 = c - a;
+    let p = ray.direction.cross(&edge_2);
+    let determinant = edge_1.dot(&p);
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+    let s = ray.origin - a;
+    let u = s.dot(&p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&edge_1);
+    let v = ray.direction.dot(&q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = edge_2.dot(&q) * inverse_determinant;
+    if distance < 0.0 {
+        return None;
+    }
+    Some(TriangleHit {
+        distance,
+        barycentrics: nalgebra_glm::vec3(1.0 - u - v, u, v),
+    })
+}
+
+pub fn closest_point_on_triangle(
+    point: &nalgebra_glm::Vec3,
+    a: &nalgebra_glm::Vec3,
+    b: &nalgebra_glm::Vec3,
+    c: &nalgebra_glm::Vec3,
+) -> nalgebra_glm::Vec3 {
+    // Voronoi region tests from Real-Time Collision Detection, section 5.1.5
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+    let d1 = ab.dot(&ap);
+    let d2 = ac.dot(&ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return *a;
+    }
+
+    let bp = point - b;
+    let d3 = ab.dot(&bp);
+    let d4 = ac.dot(&bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return *b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return a + ab * v;
+    }
+
+    let cp = point - c;
+    let d5 = ab.dot(&cp);
+    let d6 = ac.dot(&cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return *c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return a + ac * w;
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return b + (c - b) * w;
+    }
+
+    let denominator = 1.0 / (va + vb + vc);
+    let v = vb * denominator;
+    let w = vc * denominator;
+    a + ab * v + ac * w
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn ray_hits_triangle_with_barycentrics() {
+        let ray = crate::geometry::Ray::new(
+            nalgebra_glm::vec3(0.25, 0.25, 5.0),
+            nalgebra_glm::vec3(0.0, 0.0, -1.0),
+        );
+        let hit = crate::geometry::intersect_ray_triangle(
+            &ray,
+            &nalgebra_glm::vec3(0.0, 0.0, 0.0),
+            &nalgebra_glm::vec3(1.0, 0.0, 0.0),
+            &nalgebra_glm::vec3(0.0, 1.0, 0.0),
+        )
+        .expect("Ray should hit the triangle");
+        assert!((hit.distance - 5.0).abs() < 1e-5);
+        assert!((hit.barycentrics - nalgebra_glm::vec3(0.5, 0.25, 0.25)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn ray_from_screen_center_points_forward() {
+        let projection = nalgebra_glm::infinite_perspective_rh_zo(1.0, 90_f32.to_radians(), 0.01);
+        let view = nalgebra_glm::look_at(
+            &nalgebra_glm::vec3(0.0, 0.0, 4.0),
+            &nalgebra_glm::vec3(0.0, 0.0, 0.0),
+            &nalgebra_glm::Vec3::y(),
+        );
+        let ray = crate::geometry::Ray::from_screen(
+            nalgebra_glm::vec2(50.0, 50.0),
+            nalgebra_glm::vec2(100.0, 100.0),
+            &projection,
+            &view,
+        )
+        .unwrap();
+        assert!((ray.direction - nalgebra_glm::vec3(0.0, 0.0, -1.0)).norm() < 1e-4);
+
+        let aabb = crate::geometry::Aabb::new(
+            nalgebra_glm::vec3(-1.0, -1.0, -1.0),
+            nalgebra_glm::vec3(1.0, 1.0, 1.0),
+        );
+        let distance = aabb.intersect_ray(&ray).unwrap();
+        assert!((ray.at(distance).z - 1.0).abs() < 1e-3);
+    }
+}
@@ -17,6 +17,7 @@ pub fn import_gltf(path: impl AsRef<std::path::Path>) -> crate::scene::Scene {
         meshes,
         animations,
         skins,
+        environment: None,
     }
 }
 
@@ -137,12 +138,26 @@ fn import_materials(
             let id = uuid::Uuid::new_v4().to_string();
             let mut material = crate::scene::Material {
                 base_color_factor: nalgebra_glm::Vec4::from(pbr.base_color_factor()),
+                emissive_factor: nalgebra_glm::Vec3::from(primitive_material.emissive_factor()),
+                metallic_factor: pbr.metallic_factor(),
+                roughness_factor: pbr.roughness_factor(),
+                alpha_mode: primitive_material.alpha_mode().into(),
+                alpha_cutoff: primitive_material.alpha_cutoff().unwrap_or(0.5),
+                double_sided: primitive_material.double_sided(),
                 ..Default::default()
             };
             if let Some(base_color_texture) = pbr.base_color_texture() {
                 material.base_color_texture =
                     texture_ids[base_color_texture.texture().index()].to_string();
             }
+            if let Some(emissive_texture) = primitive_material.emissive_texture() {
+                material.emissive_texture =
+                    texture_ids[emissive_texture.texture().index()].to_string();
+            }
+            if let Some(metallic_roughness_texture) = pbr.metallic_roughness_texture() {
+                material.metallic_roughness_texture =
+                    texture_ids[metallic_roughness_texture.texture().index()].to_string();
+            }
             materials.insert(id.to_string(), material);
             id
         })
@@ -564,6 +579,9 @@ impl From<gltf::Camera<'_>> for crate::scene::Camera {
                 }
             },
             orientation: crate::scene::Orientation::default(),
+            post_effects: None,
+            viewport: None,
+            render_target: None,
         }
     }
 }
@@ -575,6 +593,11 @@ impl From<gltf::khr_lights_punctual::Light<'_>> for crate::scene::Light {
             intensity: light.intensity(),
             range: light.range().unwrap_or(0.0),
             kind: light.kind().into(),
+            // Shadows are opted into per light, since every shadow map costs memory and a depth pass
+            shadow: crate::scene::ShadowSettings {
+                enabled: false,
+                ..Default::default()
+            },
         }
     }
 }
@@ -614,7 +637,7 @@ mod tests {
     #[ignore]
     #[test]
     fn import() {
-        let scene = crate::gltf::import_gltf(&"resources/models/DamagedHelmet.glb");
+        let scene = crate::gltf::import_gltf("resources/models/DamagedHelmet.glb");
         dbg!(scene.textures);
         dbg!(scene.materials);
     }
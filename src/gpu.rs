@@ -1,14 +1,21 @@
 pub struct Gpu {
-    pub surface: wgpu::Surface,
+    /// Headless devices render into offscreen textures and have no surface
+    pub surface: Option<wgpu::Surface>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub surface_config: wgpu::SurfaceConfiguration,
     pub surface_format: wgpu::TextureFormat,
+    /// The number of msaa samples of every scene and gui render target,
+    /// validated against the adapter when the device is created
+    pub sample_count: u32,
 }
 
 impl Gpu {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
+    /// The msaa sample counts that can be requested
+    pub const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
     pub fn alignment(&self) -> u64 {
         self.device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress
     }
@@ -28,10 +35,19 @@ impl Gpu {
         log::info!("Resizing renderer surface to: ({width}, {height})");
         self.surface_config.width = width;
         self.surface_config.height = height;
-        self.surface.configure(&self.device, &self.surface_config);
+        if let Some(surface) = self.surface.as_ref() {
+            surface.configure(&self.device, &self.surface_config);
+        }
     }
 
     pub fn create_depth_texture(&self, width: u32, height: u32) -> wgpu::TextureView {
+        // Multisampled depth is only ever an attachment,
+        // and making it bindable breaks msaa resolves on the gl backend
+        let usage = if self.sample_count == 1 {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        };
         let texture = self.device.create_texture(
             &(wgpu::TextureDescriptor {
                 label: Some("Depth Texture"),
@@ -41,11 +57,10 @@ impl Gpu {
                     depth_or_array_layers: 1,
                 },
                 mip_level_count: 1,
-                sample_count: 1,
+                sample_count: self.sample_count,
                 dimension: wgpu::TextureDimension::D2,
                 format: wgpu::TextureFormat::Depth32Float,
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
-                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                usage,
                 view_formats: &[],
             }),
         );
@@ -61,17 +76,51 @@ impl Gpu {
         })
     }
 
+    /// Creates a multisampled color target that resolves into a texture of the same format,
+    /// or `None` if msaa is disabled
+    pub fn create_msaa_texture(
+        &self,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Option<wgpu::TextureView> {
+        if self.sample_count == 1 {
+            return None;
+        }
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("MSAA Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: self.sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
+    pub fn multisample_state(&self) -> wgpu::MultisampleState {
+        wgpu::MultisampleState {
+            count: self.sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        }
+    }
+
     pub async fn new_async<
         W: raw_window_handle::HasRawWindowHandle + raw_window_handle::HasRawDisplayHandle,
     >(
         window: &W,
         width: u32,
         height: u32,
+        sample_count: u32,
     ) -> Self {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::util::backend_bits_from_env().unwrap_or_else(wgpu::Backends::all),
-            ..Default::default()
-        });
+        let instance = create_instance();
 
         let surface = unsafe { instance.create_surface(window) }.unwrap();
 
@@ -84,24 +133,9 @@ impl Gpu {
             .await
             .expect("Failed to request adapter!");
 
-        let required_features = wgpu::Features::empty();
-        let optional_features = wgpu::Features::all();
-
-        let (device, queue) = {
-            log::info!("WGPU Adapter Features: {:#?}", adapter.features());
-            adapter
-                .request_device(
-                    &wgpu::DeviceDescriptor {
-                        features: (optional_features & adapter.features()) | required_features,
-                        // Use the texture resolution limits from the adapter to support images the size of the surface
-                        limits: wgpu::Limits::default().using_resolution(adapter.limits()),
-                        label: Some("Render Device"),
-                    },
-                    None,
-                )
-                .await
-                .expect("Failed to request a device!")
-        };
+        let (device, queue) = request_device(&adapter)
+            .await
+            .expect("Failed to request a device!");
 
         let surface_capabilities = surface.get_capabilities(&adapter);
 
@@ -125,12 +159,134 @@ impl Gpu {
 
         surface.configure(&device, &surface_config);
 
+        let sample_count = supported_sample_count(&adapter, &device, surface_format, sample_count);
+
         Self {
-            surface,
+            surface: Some(surface),
             device,
             queue,
             surface_config,
             surface_format,
+            sample_count,
+        }
+    }
+
+    /// Creates a device without a window for offscreen rendering,
+    /// falling back to a software adapter when no hardware adapter is available
+    pub async fn new_headless_async(width: u32, height: u32, sample_count: u32) -> Option<Self> {
+        let instance = create_instance();
+
+        let mut adapter = None;
+        for force_fallback_adapter in [false, true] {
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    compatible_surface: None,
+                    force_fallback_adapter,
+                })
+                .await;
+            if adapter.is_some() {
+                break;
+            }
         }
+        let adapter = adapter?;
+        log::info!("Headless WGPU Adapter: {:#?}", adapter.get_info());
+
+        let (device, queue) = request_device(&adapter).await.ok()?;
+
+        let surface_format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+
+        let sample_count = supported_sample_count(&adapter, &device, surface_format, sample_count);
+
+        Some(Self {
+            surface: None,
+            device,
+            queue,
+            surface_config,
+            surface_format,
+            sample_count,
+        })
+    }
+}
+
+fn create_instance() -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::util::backend_bits_from_env().unwrap_or_else(wgpu::Backends::all),
+        ..Default::default()
+    })
+}
+
+/// The highest sample count up to the requested one
+/// that the surface, hdr and depth formats can all be rendered with
+fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    surface_format: wgpu::TextureFormat,
+    requested: u32,
+) -> u32 {
+    // Devices only allow the sample counts guaranteed by WebGPU
+    // unless adapter specific format features are enabled or the adapter is downlevel
+    let adapter_specific = device
+        .features()
+        .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+        || !adapter.get_downlevel_capabilities().is_webgpu_compliant();
+    let formats = [
+        surface_format,
+        crate::postprocess::HDR_FORMAT,
+        Gpu::DEPTH_FORMAT,
+    ];
+    let sample_count = Gpu::SAMPLE_COUNTS
+        .into_iter()
+        .rev()
+        .filter(|count| *count <= requested)
+        .find(|count| {
+            formats.iter().all(|format| {
+                let features = if adapter_specific {
+                    adapter.get_texture_format_features(*format)
+                } else {
+                    format.guaranteed_format_features(device.features())
+                };
+                features.flags.sample_count_supported(*count)
+            })
+        })
+        .unwrap_or(1);
+    if sample_count != requested {
+        log::warn!("{requested}x msaa is not supported, using {sample_count}x instead");
     }
+    sample_count
+}
+
+async fn request_device(
+    adapter: &wgpu::Adapter,
+) -> Result<(wgpu::Device, wgpu::Queue), wgpu::RequestDeviceError> {
+    let required_features = wgpu::Features::empty();
+    let optional_features = wgpu::Features::all();
+    log::info!("WGPU Adapter Features: {:#?}", adapter.features());
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                features: (optional_features & adapter.features()) | required_features,
+                // Use the texture resolution limits from the adapter to support images the size of the surface,
+                // and its texture and sampler limits to support large bindless texture arrays
+                limits: wgpu::Limits {
+                    max_sampled_textures_per_shader_stage: adapter
+                        .limits()
+                        .max_sampled_textures_per_shader_stage,
+                    max_samplers_per_shader_stage: adapter.limits().max_samplers_per_shader_stage,
+                    ..wgpu::Limits::default().using_resolution(adapter.limits())
+                },
+                label: Some("Render Device"),
+            },
+            None,
+        )
+        .await
 }
@@ -23,7 +23,7 @@ impl Gui {
                 &gpu.device,
                 gpu.surface_config.format,
                 Some(crate::gpu::Gpu::DEPTH_FORMAT),
-                1,
+                gpu.sample_count,
             ),
         }
     }
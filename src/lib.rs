@@ -1,15 +1,31 @@
 pub mod app;
+pub mod bvh;
+pub mod character;
+pub mod debug;
+pub mod environment;
+pub mod geometry;
 pub mod gltf;
 pub mod gpu;
 pub mod gui;
 pub mod io;
+pub mod lighting;
+pub mod material;
+pub mod material_shader;
+pub mod outline;
+pub mod physics;
+pub mod picking;
+pub mod postprocess;
 pub mod render;
+pub mod render_target;
 pub mod scene;
+pub mod shader;
+pub mod texture;
 pub mod view;
 
 pub use egui;
 pub use log;
 pub use nalgebra_glm;
 pub use petgraph;
+pub use rapier3d;
 pub use uuid;
 pub use winit;
@@ -0,0 +1,1296 @@
+pub const MAX_LIGHTS: usize = 64;
+pub const MAX_SHADOW_VIEWS: usize = 32;
+pub const MAX_SHADOW_MAP_RESOLUTION: u32 = 4096;
+
+/// The largest the shadow atlas grows to, which is 256 MiB of depth
+pub const MAX_SHADOW_ATLAS_RESOLUTION: u32 = 8192;
+
+/// The number of shadow maps a directional light's view distance is split into
+pub const CASCADE_COUNT: usize = 4;
+
+/// Blends between logarithmic and uniform cascade splits
+const CASCADE_SPLIT_LAMBDA: f32 = 0.5;
+
+/// The distance from the camera where the first shadow cascade starts
+const CASCADE_NEAR: f32 = 0.1;
+
+/// Shadow casting range of point and spot lights without a range
+const DEFAULT_SHADOW_RANGE: f32 = 100.0;
+const SHADOW_NEAR: f32 = 0.05;
+
+const LIGHT_KIND_DIRECTIONAL: u32 = 0;
+const LIGHT_KIND_POINT: u32 = 1;
+const LIGHT_KIND_SPOT: u32 = 2;
+
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuLight {
+    pub position: nalgebra_glm::Vec4,
+    pub direction: nalgebra_glm::Vec4,
+    /// The light's color, with its intensity in the w component
+    pub color: nalgebra_glm::Vec4,
+    pub range: f32,
+    pub kind: u32,
+    pub inner_cone_cos: f32,
+    pub outer_cone_cos: f32,
+    /// The view space distance where each of a directional light's cascades ends
+    pub cascade_splits: nalgebra_glm::Vec4,
+    /// The index of the light's first shadow view, or -1 if it casts no shadows
+    pub first_shadow_view: i32,
+    pub shadow_view_count: u32,
+    pub shadow_bias: f32,
+    pub shadow_normal_bias: f32,
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuShadowView {
+    pub view_projection: nalgebra_glm::Mat4,
+    /// Where the view's tile starts in the shadow atlas, in texture coordinates
+    pub uv_offset: nalgebra_glm::Vec2,
+    /// The fraction of the shadow atlas the view's tile covers
+    pub uv_scale: f32,
+    /// The size of a shadow atlas texel in texture coordinates
+    pub texel_size: f32,
+    /// The size of a shadow map texel in world units, at a distance of one for perspective views
+    pub world_texel_size: f32,
+    pub _padding: [u32; 3],
+}
+
+/// Where a shadow view is rendered in the shadow atlas, in texels
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+struct ShadowTile {
+    x: u32,
+    y: u32,
+    resolution: u32,
+}
+
+/// Laid out like the leading fields of the view's uniforms,
+/// so the view's vertex stage renders shadow maps from the shadow view
+#[repr(C, align(256))]
+#[derive(Default, Copy, Clone, Debug, bytemuck::Zeroable)]
+struct ShadowViewUniform {
+    /// The shadow view's whole view projection
+    view: nalgebra_glm::Mat4,
+    /// Always the identity
+    projection: nalgebra_glm::Mat4,
+}
+
+/// Scene lights, their shadow maps and the environment lighting the scene.
+///
+/// Every shadow map is a tile of one depth texture atlas, sized to fit the tiles requested.
+/// Lights whose shadows don't fit in `MAX_SHADOW_VIEWS` views or the largest atlas cast none.
+/// Directional lights use cascades fit to the camera frustum,
+/// spot lights use a single perspective view and point lights use one view per cube face.
+pub struct Lighting {
+    pub light_buffer: wgpu::Buffer,
+    pub shadow_view_buffer: wgpu::Buffer,
+    pub shadow_view_uniform_buffer: wgpu::Buffer,
+    pub shadow_view_uniform_bind_group: wgpu::BindGroup,
+    pub shadow_atlas: wgpu::Texture,
+    pub shadow_atlas_view: wgpu::TextureView,
+    /// The width and height of the shadow atlas in texels
+    pub shadow_atlas_resolution: u32,
+    pub comparison_sampler: wgpu::Sampler,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    pub shadow_view_uniform_bind_group_layout: wgpu::BindGroupLayout,
+    pub environment: crate::environment::Environment,
+    pub number_of_lights: u32,
+    /// Where each shadow view is rendered in the atlas this frame
+    shadow_tiles: Vec<ShadowTile>,
+    /// The number of lights whose shadows didn't fit, so it is only logged when it changes
+    lights_without_shadows: usize,
+}
+
+impl Lighting {
+    pub fn new(gpu: &crate::gpu::Gpu) -> Self {
+        let light_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Light Buffer"),
+            size: (MAX_LIGHTS * std::mem::size_of::<GpuLight>()) as _,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let shadow_view_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow View Buffer"),
+            size: (MAX_SHADOW_VIEWS * std::mem::size_of::<GpuShadowView>()) as _,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let shadow_view_uniform_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow View Uniform Buffer"),
+            size: MAX_SHADOW_VIEWS as wgpu::BufferAddress * gpu.alignment(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let shadow_view_uniform_bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Shadow View Uniform Bind Group Layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
+                                ShadowViewUniform,
+                            >()
+                                as _),
+                        },
+                        count: None,
+                    }],
+                });
+        let shadow_view_uniform_bind_group =
+            gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Shadow View Uniform Bind Group"),
+                layout: &shadow_view_uniform_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &shadow_view_uniform_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<ShadowViewUniform>() as _),
+                    }),
+                }],
+            });
+
+        let comparison_sampler = gpu.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Comparison Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let bind_group_layout = create_bind_group_layout(gpu);
+        let (shadow_atlas, shadow_atlas_view) = create_shadow_atlas(gpu, 1);
+        let environment = crate::environment::Environment::new(gpu);
+        let bind_group = create_bind_group(
+            gpu,
+            &bind_group_layout,
+            &light_buffer,
+            &shadow_view_buffer,
+            &shadow_atlas_view,
+            &comparison_sampler,
+            &environment,
+        );
+
+        Self {
+            light_buffer,
+            shadow_view_buffer,
+            shadow_view_uniform_buffer,
+            shadow_view_uniform_bind_group,
+            shadow_atlas,
+            shadow_atlas_view,
+            shadow_atlas_resolution: 1,
+            comparison_sampler,
+            bind_group_layout,
+            bind_group,
+            shadow_view_uniform_bind_group_layout,
+            environment,
+            number_of_lights: 0,
+            shadow_tiles: Vec::new(),
+            lights_without_shadows: 0,
+        }
+    }
+
+    /// Gathers the scene's lights, fits their shadow views to the camera and uploads both,
+    /// and loads the scene's environment if it changed
+    pub fn prepare(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        scene: &crate::scene::Scene,
+        view: &nalgebra_glm::Mat4,
+        projection: &nalgebra_glm::Mat4,
+    ) {
+        let environment_reloaded =
+            self.environment
+                .prepare(gpu, scene.environment.as_ref(), view, projection);
+
+        let max_atlas_resolution = 1
+            << MAX_SHADOW_ATLAS_RESOLUTION
+                .min(gpu.device.limits().max_texture_dimension_2d)
+                .ilog2();
+        let mut shadow_budget = ShadowBudget::new(max_atlas_resolution);
+        let mut lights_without_shadows = 0;
+
+        let mut lights = Vec::new();
+        let mut shadow_views = Vec::new();
+        scene.walk_dfs(|node, node_index| {
+            node.components.iter().for_each(|component| {
+                let crate::scene::NodeComponent::Light(light) = component else {
+                    return;
+                };
+                if lights.len() == MAX_LIGHTS {
+                    return;
+                }
+
+                let transform = scene.graph.global_transform(node_index);
+                let position = transform.column(3).xyz();
+                // Lights point down their local -Z axis
+                let direction = (transform * nalgebra_glm::vec4(0.0, 0.0, -1.0, 0.0))
+                    .xyz()
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or_else(|| -nalgebra_glm::Vec3::z());
+
+                let (kind, inner_cone_cos, outer_cone_cos) = match light.kind {
+                    crate::scene::LightKind::Directional => (LIGHT_KIND_DIRECTIONAL, 0.0, 0.0),
+                    crate::scene::LightKind::Point => (LIGHT_KIND_POINT, 0.0, 0.0),
+                    crate::scene::LightKind::Spot {
+                        inner_cone_angle,
+                        outer_cone_angle,
+                    } => (
+                        LIGHT_KIND_SPOT,
+                        inner_cone_angle.cos(),
+                        outer_cone_angle.cos(),
+                    ),
+                };
+
+                let resolution = shadow_resolution(&light.shadow);
+                let (views, cascade_splits) = if light.shadow.enabled {
+                    light_shadow_views(light, &position, &direction, view, projection)
+                } else {
+                    (Vec::new(), nalgebra_glm::Vec4::zeros())
+                };
+                let casts_shadows =
+                    !views.is_empty() && shadow_budget.reserve(views.len(), resolution);
+                if !casts_shadows && !views.is_empty() {
+                    lights_without_shadows += 1;
+                }
+                let first_shadow_view = if casts_shadows {
+                    shadow_views.len() as i32
+                } else {
+                    -1
+                };
+                let shadow_view_count = if casts_shadows { views.len() as u32 } else { 0 };
+                if casts_shadows {
+                    shadow_views.extend(views.into_iter().map(
+                        |(view_projection, world_texel_size)| {
+                            (
+                                view_projection,
+                                world_texel_size / resolution as f32,
+                                resolution,
+                            )
+                        },
+                    ));
+                }
+
+                lights.push(GpuLight {
+                    position: nalgebra_glm::vec3_to_vec4(&position),
+                    direction: nalgebra_glm::vec3_to_vec4(&direction),
+                    color: nalgebra_glm::vec4(
+                        light.color.x,
+                        light.color.y,
+                        light.color.z,
+                        light.intensity,
+                    ),
+                    range: light.range,
+                    kind,
+                    inner_cone_cos,
+                    outer_cone_cos,
+                    cascade_splits,
+                    first_shadow_view,
+                    shadow_view_count,
+                    shadow_bias: light.shadow.bias,
+                    shadow_normal_bias: light.shadow.normal_bias,
+                });
+            });
+        });
+
+        if lights_without_shadows != self.lights_without_shadows && lights_without_shadows > 0 {
+            log::warn!(
+                "{lights_without_shadows} lights cast no shadows, their shadow maps exceed \
+                 {MAX_SHADOW_VIEWS} views or a {max_atlas_resolution}x{max_atlas_resolution} atlas"
+            );
+        }
+        self.lights_without_shadows = lights_without_shadows;
+
+        // The atlas is only recreated when the tiles need a different power of two size
+        let (atlas_resolution, shadow_tiles) = pack_shadow_tiles(
+            &shadow_views
+                .iter()
+                .map(|(_, _, resolution)| *resolution)
+                .collect::<Vec<_>>(),
+        );
+        let atlas_resized = atlas_resolution != self.shadow_atlas_resolution;
+        if atlas_resized {
+            (self.shadow_atlas, self.shadow_atlas_view) =
+                create_shadow_atlas(gpu, atlas_resolution);
+            self.shadow_atlas_resolution = atlas_resolution;
+        }
+        if atlas_resized || environment_reloaded {
+            self.bind_group = create_bind_group(
+                gpu,
+                &self.bind_group_layout,
+                &self.light_buffer,
+                &self.shadow_view_buffer,
+                &self.shadow_atlas_view,
+                &self.comparison_sampler,
+                &self.environment,
+            );
+        }
+
+        let texel_size = 1.0 / atlas_resolution as f32;
+        let gpu_shadow_views = shadow_views
+            .iter()
+            .zip(shadow_tiles.iter())
+            .map(
+                |((view_projection, world_texel_size, _), tile)| GpuShadowView {
+                    view_projection: *view_projection,
+                    uv_offset: nalgebra_glm::vec2(tile.x as f32, tile.y as f32) * texel_size,
+                    uv_scale: tile.resolution as f32 * texel_size,
+                    texel_size,
+                    world_texel_size: *world_texel_size,
+                    ..Default::default()
+                },
+            )
+            .collect::<Vec<_>>();
+        let mut shadow_view_uniforms = vec![ShadowViewUniform::default(); MAX_SHADOW_VIEWS];
+        shadow_views
+            .iter()
+            .enumerate()
+            .for_each(|(index, (view_projection, _, _))| {
+                shadow_view_uniforms[index] = ShadowViewUniform {
+                    view: *view_projection,
+                    projection: nalgebra_glm::Mat4::identity(),
+                };
+            });
+
+        if !lights.is_empty() {
+            gpu.queue
+                .write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&lights));
+        }
+        if !gpu_shadow_views.is_empty() {
+            gpu.queue.write_buffer(
+                &self.shadow_view_buffer,
+                0,
+                bytemuck::cast_slice(&gpu_shadow_views),
+            );
+        }
+        gpu.queue
+            .write_buffer(&self.shadow_view_uniform_buffer, 0, unsafe {
+                std::slice::from_raw_parts(
+                    shadow_view_uniforms.as_ptr() as *const u8,
+                    shadow_view_uniforms.len() * gpu.alignment() as usize,
+                )
+            });
+
+        self.number_of_lights = lights.len() as u32;
+        self.shadow_tiles = shadow_tiles;
+    }
+
+    /// Renders the depth of every primitive drawn by the view into each shadow map's tile of the atlas,
+    /// except for blended primitives, which don't cast shadows
+    pub fn render_shadows(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        gpu: &crate::gpu::Gpu,
+        view: &crate::view::View,
+    ) {
+        if self.shadow_tiles.is_empty() {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Render Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.shadow_atlas_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        view.materials.bind_all(&mut render_pass, 2);
+        render_pass.set_vertex_buffer(0, view.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(view.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        self.shadow_tiles
+            .iter()
+            .enumerate()
+            .for_each(|(shadow_view_index, tile)| {
+                render_pass.set_viewport(
+                    tile.x as f32,
+                    tile.y as f32,
+                    tile.resolution as f32,
+                    tile.resolution as f32,
+                    0.0,
+                    1.0,
+                );
+                render_pass.set_bind_group(
+                    0,
+                    &self.shadow_view_uniform_bind_group,
+                    &[(shadow_view_index as u64 * gpu.alignment()) as wgpu::DynamicOffset],
+                );
+                view.draws
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, draw)| draw.alpha_mode != crate::scene::AlphaMode::Blend)
+                    .for_each(|(draw_index, draw)| {
+                        render_pass.set_pipeline(&view.pass_pipelines.shadow[draw.pass_variant()]);
+                        render_pass.set_bind_group(
+                            1,
+                            &view.dynamic_uniform_bind_group,
+                            &[(draw_index as u64 * gpu.alignment()) as wgpu::DynamicOffset],
+                        );
+                        view.materials
+                            .bind(&mut render_pass, 2, draw.material_index);
+                        render_pass.draw_indexed(draw.indices.clone(), draw.base_vertex, 0..1);
+                    });
+            });
+    }
+}
+
+/// The resolution of a light's shadow maps, rounded down to a power of two so they pack into the atlas
+fn shadow_resolution(settings: &crate::scene::ShadowSettings) -> u32 {
+    1 << settings
+        .resolution
+        .clamp(1, MAX_SHADOW_MAP_RESOLUTION)
+        .ilog2()
+}
+
+/// The shadow views and atlas area handed out to lights so far
+struct ShadowBudget {
+    views: usize,
+    atlas_area: u64,
+    max_atlas_area: u64,
+}
+
+impl ShadowBudget {
+    fn new(max_atlas_resolution: u32) -> Self {
+        Self {
+            views: 0,
+            atlas_area: 0,
+            max_atlas_area: (max_atlas_resolution as u64).pow(2),
+        }
+    }
+
+    /// Reserves a light's shadow views if they fit, returning whether they did.
+    /// Power of two tiles always pack into the atlas when their total area fits.
+    fn reserve(&mut self, view_count: usize, resolution: u32) -> bool {
+        let area = view_count as u64 * (resolution as u64).pow(2);
+        let fits = self.views + view_count <= MAX_SHADOW_VIEWS
+            && self.atlas_area + area <= self.max_atlas_area;
+        if fits {
+            self.views += view_count;
+            self.atlas_area += area;
+        }
+        fits
+    }
+}
+
+/// Places square power of two tiles in the smallest atlas they fit in, returning its resolution
+/// and where each tile is placed. Tiles are placed from largest to smallest along a z-order curve,
+/// which leaves no gaps, so they fit whenever the atlas has room for their total area.
+fn pack_shadow_tiles(resolutions: &[u32]) -> (u32, Vec<ShadowTile>) {
+    let mut order = (0..resolutions.len()).collect::<Vec<_>>();
+    order.sort_by_key(|index| std::cmp::Reverse(resolutions[*index]));
+    let mut tiles = vec![ShadowTile::default(); resolutions.len()];
+    let mut used_area = 0;
+    order.into_iter().for_each(|index| {
+        let resolution = resolutions[index];
+        let area = (resolution as u64).pow(2);
+        // Every tile placed so far is at least as large, so the area used is a whole number of tiles
+        let (x, y) = z_order_position(used_area / area);
+        tiles[index] = ShadowTile {
+            x: x * resolution,
+            y: y * resolution,
+            resolution,
+        };
+        used_area += area;
+    });
+    let mut atlas_resolution = resolutions.iter().copied().max().unwrap_or(1);
+    while (atlas_resolution as u64).pow(2) < used_area {
+        atlas_resolution *= 2;
+    }
+    (atlas_resolution, tiles)
+}
+
+/// Splits the bits of an index along a z-order curve into x and y coordinates
+fn z_order_position(index: u64) -> (u32, u32) {
+    (0..32).fold((0, 0), |(x, y), bit| {
+        (
+            x | (((index >> (2 * bit)) & 1) as u32) << bit,
+            y | (((index >> (2 * bit + 1)) & 1) as u32) << bit,
+        )
+    })
+}
+
+/// Returns each of the light's shadow view projections with the world size of its texels,
+/// and the cascade split distances for directional lights
+fn light_shadow_views(
+    light: &crate::scene::Light,
+    position: &nalgebra_glm::Vec3,
+    direction: &nalgebra_glm::Vec3,
+    camera_view: &nalgebra_glm::Mat4,
+    camera_projection: &nalgebra_glm::Mat4,
+) -> (Vec<(nalgebra_glm::Mat4, f32)>, nalgebra_glm::Vec4) {
+    let far = if light.range > 0.0 {
+        light.range
+    } else {
+        DEFAULT_SHADOW_RANGE
+    };
+    match light.kind {
+        crate::scene::LightKind::Directional => {
+            directional_cascades(&light.shadow, direction, camera_view, camera_projection)
+        }
+        crate::scene::LightKind::Spot {
+            outer_cone_angle, ..
+        } => {
+            let field_of_view = (outer_cone_angle * 2.0).clamp(0.01, 179_f32.to_radians());
+            let projection = nalgebra_glm::perspective_rh_zo(1.0, field_of_view, SHADOW_NEAR, far);
+            let view = nalgebra_glm::look_at(position, &(position + direction), &up_for(direction));
+            let world_texel_size = 2.0 * (field_of_view / 2.0).tan();
+            (
+                vec![(projection * view, world_texel_size)],
+                nalgebra_glm::Vec4::zeros(),
+            )
+        }
+        crate::scene::LightKind::Point => {
+            // The face order matches the face selection in the shader
+            let projection =
+                nalgebra_glm::perspective_rh_zo(1.0, 90_f32.to_radians(), SHADOW_NEAR, far);
+            let views = [
+                (nalgebra_glm::Vec3::x(), -nalgebra_glm::Vec3::y()),
+                (-nalgebra_glm::Vec3::x(), -nalgebra_glm::Vec3::y()),
+                (nalgebra_glm::Vec3::y(), nalgebra_glm::Vec3::z()),
+                (-nalgebra_glm::Vec3::y(), -nalgebra_glm::Vec3::z()),
+                (nalgebra_glm::Vec3::z(), -nalgebra_glm::Vec3::y()),
+                (-nalgebra_glm::Vec3::z(), -nalgebra_glm::Vec3::y()),
+            ]
+            .into_iter()
+            .map(|(face_direction, up)| {
+                let view = nalgebra_glm::look_at(position, &(position + face_direction), &up);
+                (projection * view, 2.0)
+            })
+            .collect();
+            (views, nalgebra_glm::Vec4::zeros())
+        }
+    }
+}
+
+/// Splits the camera frustum up to the shadow distance into cascades,
+/// and fits an orthographic projection around the bounding sphere of each one.
+/// Cascades are snapped to whole texels so shadows don't shimmer as the camera moves.
+/// This assumes a perspective camera projection.
+fn directional_cascades(
+    settings: &crate::scene::ShadowSettings,
+    direction: &nalgebra_glm::Vec3,
+    camera_view: &nalgebra_glm::Mat4,
+    camera_projection: &nalgebra_glm::Mat4,
+) -> (Vec<(nalgebra_glm::Mat4, f32)>, nalgebra_glm::Vec4) {
+    let Some(inverse_camera_view) = camera_view.try_inverse() else {
+        return (Vec::new(), nalgebra_glm::Vec4::zeros());
+    };
+    let tan_half_fov_x = 1.0 / camera_projection[(0, 0)];
+    let tan_half_fov_y = 1.0 / camera_projection[(1, 1)];
+    let resolution = shadow_resolution(settings) as f32;
+    let near = CASCADE_NEAR;
+    let far = settings.distance.max(near * 2.0);
+
+    let mut splits = nalgebra_glm::Vec4::zeros();
+    (0..CASCADE_COUNT).for_each(|cascade| {
+        let ratio = (cascade + 1) as f32 / CASCADE_COUNT as f32;
+        let logarithmic = near * (far / near).powf(ratio);
+        let uniform = near + (far - near) * ratio;
+        splits[cascade] =
+            CASCADE_SPLIT_LAMBDA * logarithmic + (1.0 - CASCADE_SPLIT_LAMBDA) * uniform;
+    });
+
+    let light_view =
+        nalgebra_glm::look_at(&nalgebra_glm::Vec3::zeros(), direction, &up_for(direction));
+
+    let views = (0..CASCADE_COUNT)
+        .map(|cascade| {
+            let start = if cascade == 0 {
+                near
+            } else {
+                splits[cascade - 1]
+            };
+            let end = splits[cascade];
+            let corners = [start, end]
+                .into_iter()
+                .flat_map(|distance| {
+                    [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].map(|(x, y)| {
+                        (inverse_camera_view
+                            * nalgebra_glm::vec4(
+                                x * distance * tan_half_fov_x,
+                                y * distance * tan_half_fov_y,
+                                -distance,
+                                1.0,
+                            ))
+                        .xyz()
+                    })
+                })
+                .collect::<Vec<_>>();
+            let center = corners
+                .iter()
+                .fold(nalgebra_glm::Vec3::zeros(), |sum, corner| sum + corner)
+                / corners.len() as f32;
+            let radius = corners
+                .iter()
+                .map(|corner| nalgebra_glm::distance(corner, &center))
+                .fold(0.0, f32::max);
+            // Rounding keeps the projection size stable as the camera rotates
+            let radius = (radius * 16.0).ceil() / 16.0;
+
+            let world_texel_size = 2.0 * radius / resolution;
+            let light_space_center = (light_view * nalgebra_glm::vec3_to_vec4(&center)).xyz();
+            let light_space_center = nalgebra_glm::vec3(
+                (light_space_center.x / world_texel_size).floor() * world_texel_size,
+                (light_space_center.y / world_texel_size).floor() * world_texel_size,
+                light_space_center.z,
+            );
+
+            // The near plane is pulled back so casters outside the cascade still cast shadows into it
+            let projection = nalgebra_glm::ortho_rh_zo(
+                light_space_center.x - radius,
+                light_space_center.x + radius,
+                light_space_center.y - radius,
+                light_space_center.y + radius,
+                -light_space_center.z - radius - far,
+                -light_space_center.z + radius,
+            );
+            (projection * light_view, world_texel_size)
+        })
+        .collect();
+    (views, splits)
+}
+
+fn up_for(direction: &nalgebra_glm::Vec3) -> nalgebra_glm::Vec3 {
+    if direction.y.abs() > 0.99 {
+        nalgebra_glm::Vec3::z()
+    } else {
+        nalgebra_glm::Vec3::y()
+    }
+}
+
+fn create_shadow_atlas(
+    gpu: &crate::gpu::Gpu,
+    resolution: u32,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let shadow_atlas = gpu.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Shadow Atlas"),
+        size: wgpu::Extent3d {
+            width: resolution,
+            height: resolution,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: crate::gpu::Gpu::DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let shadow_atlas_view = shadow_atlas.create_view(&wgpu::TextureViewDescriptor::default());
+    (shadow_atlas, shadow_atlas_view)
+}
+
+/// The textures the lighting bind group adds to the fragment stage,
+/// which are the shadow atlas, both environment cubes and the brdf lookup table
+pub const FRAGMENT_TEXTURE_BINDINGS: u32 = 4;
+
+/// The shadow comparison sampler and the environment sampler
+pub const FRAGMENT_SAMPLER_BINDINGS: u32 = 2;
+
+fn create_bind_group_layout(gpu: &crate::gpu::Gpu) -> wgpu::BindGroupLayout {
+    let storage_buffer = |binding, size: usize| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(size as _),
+        },
+        count: None,
+    };
+    gpu.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lighting Bind Group Layout"),
+            entries: &[
+                storage_buffer(0, std::mem::size_of::<GpuLight>()),
+                storage_buffer(1, std::mem::size_of::<GpuShadowView>()),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+                crate::environment::cube_texture_entry(4, wgpu::ShaderStages::FRAGMENT),
+                crate::environment::cube_texture_entry(5, wgpu::ShaderStages::FRAGMENT),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                crate::environment::sampler_entry(7, wgpu::ShaderStages::FRAGMENT),
+            ],
+        })
+}
+
+fn create_bind_group(
+    gpu: &crate::gpu::Gpu,
+    layout: &wgpu::BindGroupLayout,
+    light_buffer: &wgpu::Buffer,
+    shadow_view_buffer: &wgpu::Buffer,
+    shadow_atlas_view: &wgpu::TextureView,
+    comparison_sampler: &wgpu::Sampler,
+    environment: &crate::environment::Environment,
+) -> wgpu::BindGroup {
+    gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Lighting Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: light_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: shadow_view_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(shadow_atlas_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(comparison_sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(&environment.irradiance_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(&environment.prefiltered_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::TextureView(&environment.brdf_lut_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: wgpu::BindingResource::Sampler(&environment.sampler),
+            },
+        ],
+    })
+}
+
+/// Creates a depth pipeline for each pass variant, binding the shadow view in place of the view's uniforms
+pub fn create_depth_pipelines(
+    gpu: &crate::gpu::Gpu,
+    shadow_view_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    context: &crate::view::PassContext,
+) -> Vec<wgpu::RenderPipeline> {
+    let pipeline_layout = gpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Depth Pipeline Layout"),
+            bind_group_layouts: &[
+                shadow_view_uniform_bind_group_layout,
+                context.dynamic_uniform_bind_group_layout,
+                &context.materials.bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+    crate::view::pass_variants()
+        .iter()
+        .map(|variant| {
+            let shader_module = crate::view::create_pass_shader_module(
+                gpu,
+                "Shadow Depth Shader",
+                DEPTH_SHADER_SOURCE,
+                variant,
+                context,
+            );
+            gpu.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Shadow Depth Pipeline"),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader_module,
+                        entry_point: "vertex_main",
+                        buffers: &[crate::scene::Vertex::description(
+                            &crate::scene::Vertex::attributes(),
+                        )],
+                    },
+                    primitive: wgpu::PrimitiveState {
+                        front_face: wgpu::FrontFace::Ccw,
+                        cull_mode: (!variant.contains_key("DOUBLE_SIDED"))
+                            .then_some(wgpu::Face::Back),
+                        ..Default::default()
+                    },
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: crate::gpu::Gpu::DEPTH_FORMAT,
+                        depth_write_enabled: true,
+                        depth_compare: wgpu::CompareFunction::Less,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState {
+                            constant: 2,
+                            slope_scale: 2.0,
+                            clamp: 0.0,
+                        },
+                    }),
+                    multisample: wgpu::MultisampleState::default(),
+                    // Only masked primitives need a fragment stage, to discard what they cut out
+                    fragment: variant
+                        .contains_key("ALPHA_MASK")
+                        .then_some(wgpu::FragmentState {
+                            module: &shader_module,
+                            entry_point: "fragment_main",
+                            targets: &[],
+                        }),
+                    multiview: None,
+                })
+        })
+        .collect()
+}
+
+/// Renders depth with the view's vertex stage, which reads the shadow view from the view's uniforms
+pub const DEPTH_SHADER_SOURCE: &str = "
+#include \"view.wgsl\"
+#include \"material.wgsl\"
+
+#ifdef ALPHA_MASK
+@fragment
+fn fragment_main(in: VertexOutput) {
+    if is_masked_out(materials[mesh_ubo.material_index], in.uv_0) {
+        discard;
+    }
+}
+#endif
+";
+
+/// Declares the lighting bindings and a `light_contribution` function
+/// returning the diffuse light a surface receives from a light, including shadowing
+pub const SHADER_SOURCE: &str = "
+struct Light {
+    position: vec4<f32>,
+    direction: vec4<f32>,
+    color: vec4<f32>,
+    range: f32,
+    kind: u32,
+    inner_cone_cos: f32,
+    outer_cone_cos: f32,
+    cascade_splits: vec4<f32>,
+    first_shadow_view: i32,
+    shadow_view_count: u32,
+    shadow_bias: f32,
+    shadow_normal_bias: f32,
+};
+
+struct ShadowView {
+    view_projection: mat4x4<f32>,
+    uv_offset: vec2<f32>,
+    uv_scale: f32,
+    texel_size: f32,
+    world_texel_size: f32,
+};
+
+@group(3) @binding(0)
+var<storage, read> lights: array<Light>;
+
+@group(3) @binding(1)
+var<storage, read> shadow_views: array<ShadowView>;
+
+@group(3) @binding(2)
+var shadow_atlas: texture_depth_2d;
+
+@group(3) @binding(3)
+var shadow_sampler: sampler_comparison;
+
+// Filters a 3x3 block of shadow map comparisons
+fn sample_shadow(view_index: u32, light: Light, world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let shadow_view = shadow_views[view_index];
+
+    // Texels cover more of the world further from perspective views, where w is the view depth
+    let w = max((shadow_view.view_projection * vec4(world_position, 1.0)).w, 0.0);
+    let offset_position = world_position
+        + normal * light.shadow_normal_bias * shadow_view.world_texel_size * w;
+
+    let clip = shadow_view.view_projection * vec4(offset_position, 1.0);
+    if clip.w <= 0.0 {
+        return 1.0;
+    }
+    let ndc = clip.xyz / clip.w;
+    if abs(ndc.x) > 1.0 || abs(ndc.y) > 1.0 || ndc.z > 1.0 {
+        return 1.0;
+    }
+
+    let uv = shadow_view.uv_offset + (ndc.xy * vec2(0.5, -0.5) + 0.5) * shadow_view.uv_scale;
+    // Filtering stays half a texel inside the view's tile so neighbouring tiles don't bleed in
+    let min_uv = shadow_view.uv_offset + 0.5 * shadow_view.texel_size;
+    let max_uv = shadow_view.uv_offset + shadow_view.uv_scale - 0.5 * shadow_view.texel_size;
+    let depth = ndc.z - light.shadow_bias;
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x += 1) {
+        for (var y = -1; y <= 1; y += 1) {
+            let offset = vec2(f32(x), f32(y)) * shadow_view.texel_size;
+            lit += textureSampleCompareLevel(
+                shadow_atlas,
+                shadow_sampler,
+                clamp(uv + offset, min_uv, max_uv),
+                depth
+            );
+        }
+    }
+    return lit / 9.0;
+}
+
+fn light_shadow(light: Light, world_position: vec3<f32>, normal: vec3<f32>, view_depth: f32) -> f32 {
+    if light.first_shadow_view < 0 {
+        return 1.0;
+    }
+    var view_index = u32(light.first_shadow_view);
+
+    if light.kind == 0u {
+        // Directional lights use the first cascade containing the fragment
+        var cascade = 0u;
+        loop {
+            if cascade >= light.shadow_view_count {
+                return 1.0;
+            }
+            if view_depth <= light.cascade_splits[cascade] {
+                break;
+            }
+            cascade += 1u;
+        }
+        view_index += cascade;
+    } else if light.kind == 1u {
+        // Point lights use the cube face the fragment is in
+        let to_fragment = world_position - light.position.xyz;
+        let distance = abs(to_fragment);
+        var face = 0u;
+        if distance.x >= distance.y && distance.x >= distance.z {
+            face = select(1u, 0u, to_fragment.x > 0.0);
+        } else if distance.y >= distance.z {
+            face = select(3u, 2u, to_fragment.y > 0.0);
+        } else {
+            face = select(5u, 4u, to_fragment.z > 0.0);
+        }
+        view_index += face;
+    }
+
+    return sample_shadow(view_index, light, world_position, normal);
+}
+
+fn light_contribution(light: Light, world_position: vec3<f32>, normal: vec3<f32>, view_depth: f32) -> vec3<f32> {
+    var to_light = -light.direction.xyz;
+    var attenuation = 1.0;
+    if light.kind != 0u {
+        let offset = light.position.xyz - world_position;
+        let distance = length(offset);
+        to_light = offset / max(distance, 0.0001);
+        attenuation = 1.0 / max(distance * distance, 0.0001);
+        if light.range > 0.0 {
+            attenuation *= clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+        }
+        if light.kind == 2u {
+            let cos_angle = dot(light.direction.xyz, -to_light);
+            attenuation *= smoothstep(light.outer_cone_cos, light.inner_cone_cos, cos_angle);
+        }
+    }
+
+    let n_dot_l = max(dot(normal, to_light), 0.0);
+    if n_dot_l <= 0.0 || attenuation <= 0.0 {
+        return vec3(0.0);
+    }
+    let shadow = light_shadow(light, world_position, normal, view_depth);
+    return light.color.rgb * light.color.w * attenuation * n_dot_l * shadow;
+}
+";
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn cascade_splits_cover_shadow_distance() {
+        let settings = crate::scene::ShadowSettings::default();
+        let projection = nalgebra_glm::perspective_rh_zo(1.0, 45_f32.to_radians(), 0.1, 1000.0);
+        let (views, splits) = super::directional_cascades(
+            &settings,
+            &-nalgebra_glm::Vec3::y(),
+            &nalgebra_glm::Mat4::identity(),
+            &projection,
+        );
+        assert_eq!(views.len(), super::CASCADE_COUNT);
+        assert!((0..super::CASCADE_COUNT - 1).all(|cascade| splits[cascade] < splits[cascade + 1]));
+        assert!((splits[super::CASCADE_COUNT - 1] - settings.distance).abs() < 1e-3);
+    }
+
+    #[test]
+    fn shadow_tiles_pack_without_overlapping() {
+        let resolutions = [512, 2048, 1024, 2048, 512, 512, 512, 1024];
+        let (atlas_resolution, tiles) = super::pack_shadow_tiles(&resolutions);
+        assert_eq!(atlas_resolution, 4096);
+        assert!(tiles
+            .iter()
+            .zip(resolutions)
+            .all(|(tile, resolution)| tile.resolution == resolution
+                && tile.x + resolution <= atlas_resolution
+                && tile.y + resolution <= atlas_resolution));
+        tiles.iter().enumerate().for_each(|(index, a)| {
+            tiles[index + 1..].iter().for_each(|b| {
+                let overlaps = a.x < b.x + b.resolution
+                    && b.x < a.x + a.resolution
+                    && a.y < b.y + b.resolution
+                    && b.y < a.y + a.resolution;
+                assert!(!overlaps, "{a:?} overlaps {b:?}");
+            });
+        });
+        assert_eq!(super::pack_shadow_tiles(&[]).0, 1);
+    }
+
+    #[test]
+    fn shadow_tiles_follow_the_z_order_curve() {
+        // Equal tiles fill each 2x2 block before moving on to the next one
+        let (atlas_resolution, tiles) = super::pack_shadow_tiles(&[512; 6]);
+        assert_eq!(atlas_resolution, 2048);
+        let positions = tiles
+            .iter()
+            .map(|tile| (tile.x, tile.y))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            [(0, 0), (512, 0), (0, 512), (512, 512), (1024, 0), (1536, 0)]
+        );
+
+        // Smaller tiles continue after the larger ones, whatever order they are requested in
+        let (_, tiles) = super::pack_shadow_tiles(&[1024, 2048, 1024]);
+        assert_eq!((tiles[1].x, tiles[1].y), (0, 0));
+        assert_eq!((tiles[0].x, tiles[0].y), (2048, 0));
+        assert_eq!((tiles[2].x, tiles[2].y), (3072, 0));
+    }
+
+    #[test]
+    fn shadow_budget_rejects_lights_past_the_limits() {
+        let mut budget = super::ShadowBudget::new(4096);
+        // A point light's six faces at 2048 need more than a 4096 atlas
+        assert!(!budget.reserve(6, 2048));
+        assert!(budget.reserve(4, 1024));
+        assert!(budget.reserve(2, 2048));
+        assert!(!budget.reserve(2, 2048));
+        // Smaller tiles still fit into the area that is left
+        assert!(budget.reserve(16, 256));
+        assert!(budget.reserve(3, 1024));
+        assert!(!budget.reserve(1, 1));
+
+        let mut budget = super::ShadowBudget::new(super::MAX_SHADOW_ATLAS_RESOLUTION);
+        assert!(budget.reserve(super::MAX_SHADOW_VIEWS, 1));
+        assert!(!budget.reserve(1, 1));
+    }
+
+    #[test]
+    fn spot_light_casts_shadows() {
+        assert_occluder_casts_shadow(crate::scene::LightKind::Spot {
+            inner_cone_angle: 0.0,
+            outer_cone_angle: 60_f32.to_radians(),
+        });
+    }
+
+    #[test]
+    fn point_light_casts_shadows() {
+        assert_occluder_casts_shadow(crate::scene::LightKind::Point);
+    }
+
+    /// Lights a floor past a floating quad from above and to the side, seen from straight above,
+    /// and checks the floor is darker where the quad shadows it and unchanged elsewhere
+    fn assert_occluder_casts_shadow(kind: crate::scene::LightKind) {
+        let Some(mut renderer) = crate::render::test_renderer(64, 64, 1) else {
+            return;
+        };
+        let mut scene = crate::scene::Scene::default();
+        scene.graph.add_node(crate::scene::Node::default());
+        scene.materials.insert(
+            "material".to_string(),
+            crate::scene::Material {
+                double_sided: true,
+                ..Default::default()
+            },
+        );
+        let mut add_quad = |label: &str, half_size: f32, height: f32| {
+            let vertex = |x: f32, z: f32| crate::scene::Vertex {
+                position: nalgebra_glm::vec3(x, height, z),
+                normal: nalgebra_glm::Vec3::y(),
+                ..Default::default()
+            };
+            scene.meshes.insert(
+                label.to_string(),
+                crate::scene::Mesh {
+                    label: label.to_string(),
+                    primitives: vec![crate::scene::Primitive {
+                        material: "material".to_string(),
+                        vertices: vec![
+                            vertex(-half_size, -half_size),
+                            vertex(-half_size, half_size),
+                            vertex(half_size, half_size),
+                            vertex(half_size, -half_size),
+                        ],
+                        indices: vec![0, 1, 2, 0, 2, 3],
+                        ..Default::default()
+                    }],
+                },
+            );
+            scene.add_root_node(crate::scene::Node {
+                label: label.to_string(),
+                components: vec![crate::scene::NodeComponent::Mesh(label.to_string())],
+                ..Default::default()
+            });
+        };
+        add_quad("Floor", 4.0, 0.0);
+        add_quad("Occluder", 0.5, 1.0);
+
+        let mut camera_node = crate::scene::create_camera_node(1.0);
+        camera_node.transform.translation = nalgebra_glm::vec3(0.0, 6.0, 0.0);
+        camera_node.transform.rotation =
+            nalgebra_glm::quat_angle_axis(-90_f32.to_radians(), &nalgebra_glm::Vec3::x());
+        let camera = scene.add_root_node(camera_node);
+
+        // The quad's shadow falls on the floor between x = -1.5 and x = 0,
+        // and the camera sees the floor past x = -0.6 around the quad
+        let position = nalgebra_glm::vec3(1.5, 3.0, 0.0);
+        let direction = nalgebra_glm::vec3(-1.5, -3.0, 0.0).normalize();
+        let mut light_node = crate::scene::Node::default();
+        light_node.transform.translation = position;
+        light_node.transform.rotation = nalgebra_glm::quat_conjugate(&nalgebra_glm::quat_look_at(
+            &direction,
+            &nalgebra_glm::Vec3::y(),
+        ));
+        light_node
+            .components
+            .push(crate::scene::NodeComponent::Light(crate::scene::Light {
+                intensity: 20.0,
+                range: 0.0,
+                color: nalgebra_glm::vec3(1.0, 1.0, 1.0),
+                kind,
+                shadow: crate::scene::ShadowSettings {
+                    resolution: 512,
+                    ..Default::default()
+                },
+            }));
+        let light = scene.add_root_node(light_node);
+        renderer.view.import_scene(&scene, &renderer.gpu);
+
+        let (_, projection, view) =
+            crate::view::create_camera_matrices_for_node(&scene, camera, 1.0)
+                .expect("The camera node should have a camera!");
+        let pixel = |x: f32| {
+            let clip = projection * view * nalgebra_glm::vec4(x, 0.0, 0.0, 1.0);
+            let ndc = clip.xy() / clip.w;
+            (
+                ((ndc.x * 0.5 + 0.5) * 64.0) as u32,
+                ((0.5 - ndc.y * 0.5) * 64.0) as u32,
+            )
+        };
+        let (shadow, lit) = (pixel(-1.1), pixel(1.1));
+
+        let shadowed = renderer.render_to_image(&scene, camera, 64, 64);
+        scene.graph[light]
+            .components
+            .iter_mut()
+            .for_each(|component| {
+                if let crate::scene::NodeComponent::Light(light) = component {
+                    light.shadow.enabled = false;
+                }
+            });
+        let unshadowed = renderer.render_to_image(&scene, camera, 64, 64);
+
+        let brightness = |image: &image::RgbaImage, (x, y): (u32, u32)| {
+            let pixel = image.get_pixel(x, y);
+            pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32
+        };
+        assert!(
+            brightness(&shadowed, shadow) < brightness(&unshadowed, shadow),
+            "The floor behind the occluder isn't shadowed"
+        );
+        assert_eq!(
+            shadowed.get_pixel(lit.0, lit.1),
+            unshadowed.get_pixel(lit.0, lit.1),
+            "The floor away from the occluder is shadowed"
+        );
+    }
+
+    #[test]
+    fn directional_light_casts_shadows() {
+        let Some(mut renderer) = crate::render::test_renderer(64, 64, 1) else {
+            return;
+        };
+        let mut scene = crate::gltf::import_gltf("resources/models/blocklevel.glb");
+        let bounds = scene.bounds().unwrap_or_default();
+        let mut camera_node = crate::scene::create_camera_node(1.0);
+        if let Some(crate::scene::NodeComponent::Camera(camera)) =
+            camera_node.components.first_mut()
+        {
+            camera.orientation.offset = bounds.center();
+            camera.orientation.radius = bounds.half_extents().norm() * 1.5;
+            camera_node.transform.translation = camera.orientation.position();
+            camera_node.transform.rotation = camera.orientation.look_at_offset();
+        }
+        let camera = scene.add_root_node(camera_node);
+
+        let mut light_node = crate::scene::Node::default();
+        light_node.transform.rotation = nalgebra_glm::quat_angle_axis(
+            -60_f32.to_radians(),
+            &nalgebra_glm::vec3(1.0, 0.0, 0.3).normalize(),
+        );
+        light_node
+            .components
+            .push(crate::scene::NodeComponent::Light(crate::scene::Light {
+                intensity: 1.0,
+                range: 0.0,
+                color: nalgebra_glm::vec3(1.0, 1.0, 1.0),
+                kind: crate::scene::LightKind::Directional,
+                shadow: crate::scene::ShadowSettings {
+                    resolution: 512,
+                    distance: bounds.half_extents().norm() * 4.0,
+                    ..Default::default()
+                },
+            }));
+        let light = scene.add_root_node(light_node);
+        renderer.view.import_scene(&scene, &renderer.gpu);
+
+        let shadowed = renderer.render_to_image(&scene, camera, 64, 64);
+        scene.graph[light]
+            .components
+            .iter_mut()
+            .for_each(|component| {
+                if let crate::scene::NodeComponent::Light(light) = component {
+                    light.shadow.enabled = false;
+                }
+            });
+        let unshadowed = renderer.render_to_image(&scene, camera, 64, 64);
+
+        let brightness = |image: &image::RgbaImage| {
+            image
+                .pixels()
+                .map(|pixel| pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32)
+                .sum::<u32>()
+        };
+        assert!(
+            brightness(&shadowed) < brightness(&unshadowed),
+            "Enabling shadows did not darken the scene"
+        );
+    }
+}
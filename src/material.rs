@@ -0,0 +1,432 @@
+/// A material as laid out in the material storage buffer
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuMaterial {
+    pub base_color_factor: nalgebra_glm::Vec4,
+    /// The emitted color, with an unused w component for alignment
+    pub emissive_factor: nalgebra_glm::Vec4,
+    /// An index into the scene's texture array, where zero is the default white texture
+    pub base_color_texture_index: u32,
+    pub emissive_texture_index: u32,
+    pub metallic_roughness_texture_index: u32,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub alpha_mode: u32,
+    pub alpha_cutoff: f32,
+    _padding: u32,
+}
+
+/// How material textures are bound to the pipeline
+pub enum MaterialBindings {
+    /// All textures are bound once as a texture array indexed by material
+    Bindless(wgpu::BindGroup),
+    /// Each material has its own bind group containing its base color, emissive
+    /// and metallic-roughness textures, indexed by material
+    PerMaterial(Vec<wgpu::BindGroup>),
+}
+
+/// Every material in the scene uploaded to the gpu, with the default material first
+pub struct GpuMaterials {
+    pub buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bindings: MaterialBindings,
+    /// Indices into the material buffer, keyed by material id
+    pub indices: std::collections::HashMap<String, u32>,
+    /// Bound in place of missing textures so materials without textures sample white
+    pub default_texture: crate::render::Texture,
+}
+
+impl GpuMaterials {
+    pub const DEFAULT_MATERIAL_INDEX: u32 = 0;
+
+    pub fn new(
+        gpu: &crate::gpu::Gpu,
+        texture_uploader: &mut crate::texture::TextureUploader,
+        scene: &crate::scene::Scene,
+        textures: &std::collections::HashMap<String, crate::render::Texture>,
+    ) -> Self {
+        let default_texture = {
+            let texture = texture_uploader.upload_image(
+                gpu,
+                &crate::scene::Image {
+                    pixels: vec![u8::MAX; 4],
+                    format: crate::scene::ImageFormat::R8G8B8A8,
+                    width: 1,
+                    height: 1,
+                },
+                crate::texture::ColorSpace::Srgb,
+                "Default Texture",
+            );
+            crate::render::Texture {
+                view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                texture,
+                sampler: texture_uploader.sampler(gpu, &crate::scene::Sampler::default()),
+            }
+        };
+
+        // The default texture takes the first slot in the texture array
+        let mut texture_ids = textures.keys().collect::<Vec<_>>();
+        texture_ids.sort();
+        let texture_array = std::iter::once(&default_texture)
+            .chain(texture_ids.iter().map(|texture_id| &textures[*texture_id]))
+            .collect::<Vec<_>>();
+        let texture_indices = texture_ids
+            .iter()
+            .enumerate()
+            .map(|(index, texture_id)| (texture_id.as_str(), index as u32 + 1))
+            .collect::<std::collections::HashMap<_, _>>();
+
+        let mut material_ids = scene.materials.keys().collect::<Vec<_>>();
+        material_ids.sort();
+        let default_material = crate::scene::Material::default();
+        let materials = std::iter::once(&default_material)
+            .chain(
+                material_ids
+                    .iter()
+                    .map(|material_id| &scene.materials[*material_id]),
+            )
+            .collect::<Vec<_>>();
+        let indices = material_ids
+            .iter()
+            .enumerate()
+            .map(|(index, material_id)| (material_id.to_string(), index as u32 + 1))
+            .collect::<std::collections::HashMap<_, _>>();
+
+        let gpu_materials = materials
+            .iter()
+            .map(|material| GpuMaterial {
+                base_color_factor: material.base_color_factor,
+                emissive_factor: nalgebra_glm::vec3_to_vec4(&material.emissive_factor),
+                base_color_texture_index: texture_indices
+                    .get(material.base_color_texture.as_str())
+                    .copied()
+                    .unwrap_or_default(),
+                emissive_texture_index: texture_indices
+                    .get(material.emissive_texture.as_str())
+                    .copied()
+                    .unwrap_or_default(),
+                metallic_roughness_texture_index: texture_indices
+                    .get(material.metallic_roughness_texture.as_str())
+                    .copied()
+                    .unwrap_or_default(),
+                metallic_factor: material.metallic_factor,
+                roughness_factor: material.roughness_factor,
+                alpha_mode: material.alpha_mode as u32,
+                alpha_cutoff: material.alpha_cutoff,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let buffer = wgpu::util::DeviceExt::create_buffer_init(
+            &gpu.device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Material Buffer"),
+                contents: bytemuck::cast_slice(&gpu_materials),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let bindless = supports_bindless(&gpu.device, texture_array.len() as u32);
+        let bind_group_layout =
+            create_bind_group_layout(gpu, bindless.then_some(texture_array.len() as u32));
+        let bindings = if bindless {
+            let views = texture_array
+                .iter()
+                .map(|texture| &texture.view)
+                .collect::<Vec<_>>();
+            let samplers = texture_array
+                .iter()
+                .map(|texture| texture.sampler.as_ref())
+                .collect::<Vec<_>>();
+            MaterialBindings::Bindless(gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Bindless Material Bind Group"),
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureViewArray(&views),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::SamplerArray(&samplers),
+                    },
+                ],
+            }))
+        } else {
+            MaterialBindings::PerMaterial(
+                gpu_materials
+                    .iter()
+                    .map(|material| {
+                        let texture = texture_array[material.base_color_texture_index as usize];
+                        let emissive_texture =
+                            texture_array[material.emissive_texture_index as usize];
+                        let metallic_roughness_texture =
+                            texture_array[material.metallic_roughness_texture_index as usize];
+                        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                            label: Some("Material Bind Group"),
+                            layout: &bind_group_layout,
+                            entries: &[
+                                wgpu::BindGroupEntry {
+                                    binding: 0,
+                                    resource: buffer.as_entire_binding(),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 1,
+                                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 2,
+                                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 3,
+                                    resource: wgpu::BindingResource::TextureView(
+                                        &emissive_texture.view,
+                                    ),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 4,
+                                    resource: wgpu::BindingResource::Sampler(
+                                        &emissive_texture.sampler,
+                                    ),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 5,
+                                    resource: wgpu::BindingResource::TextureView(
+                                        &metallic_roughness_texture.view,
+                                    ),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 6,
+                                    resource: wgpu::BindingResource::Sampler(
+                                        &metallic_roughness_texture.sampler,
+                                    ),
+                                },
+                            ],
+                        })
+                    })
+                    .collect(),
+            )
+        };
+
+        Self {
+            buffer,
+            bind_group_layout,
+            bindings,
+            indices,
+            default_texture,
+        }
+    }
+
+    pub fn is_bindless(&self) -> bool {
+        matches!(self.bindings, MaterialBindings::Bindless(_))
+    }
+
+    /// The index of a material in the material buffer, or the default material if it doesn't exist
+    pub fn index(&self, material_id: &str) -> u32 {
+        self.indices
+            .get(material_id)
+            .copied()
+            .unwrap_or(Self::DEFAULT_MATERIAL_INDEX)
+    }
+
+    /// Binds every material at once when bindless
+    pub fn bind_all<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>, group: u32) {
+        if let MaterialBindings::Bindless(bind_group) = &self.bindings {
+            render_pass.set_bind_group(group, bind_group, &[]);
+        }
+    }
+
+    /// Binds a single material's textures when not bindless
+    pub fn bind<'rp>(
+        &'rp self,
+        render_pass: &mut wgpu::RenderPass<'rp>,
+        group: u32,
+        material_index: u32,
+    ) {
+        if let MaterialBindings::PerMaterial(bind_groups) = &self.bindings {
+            render_pass.set_bind_group(group, &bind_groups[material_index as usize], &[]);
+        }
+    }
+
+    /// The defines `SHADER_SOURCE` is preprocessed with for these bindings. It declares the
+    /// material bindings, and `sample_base_color`, `sample_emissive`, `sample_metallic_roughness`
+    /// and `is_masked_out` functions that take a material and uv coordinates.
+    pub fn shader_defines(&self) -> crate::shader::ShaderDefines {
+        let mut defines = crate::shader::ShaderDefines::new();
+        if self.is_bindless() {
+            defines.insert("BINDLESS".to_string(), String::new());
+        }
+        defines
+    }
+}
+
+/// Texture arrays can be used if the device supports them and they fit within its limits
+pub fn supports_bindless(device: &wgpu::Device, number_of_textures: u32) -> bool {
+    fits_texture_array(device.features(), &device.limits(), number_of_textures)
+}
+
+/// The texture array shares the fragment stage with the lighting textures and samplers,
+/// so they have to fit within the per stage limits together
+fn fits_texture_array(
+    features: wgpu::Features,
+    limits: &wgpu::Limits,
+    number_of_textures: u32,
+) -> bool {
+    features.contains(wgpu::Features::TEXTURE_BINDING_ARRAY)
+        && number_of_textures + crate::lighting::FRAGMENT_TEXTURE_BINDINGS
+            <= limits.max_sampled_textures_per_shader_stage
+        && number_of_textures + crate::lighting::FRAGMENT_SAMPLER_BINDINGS
+            <= limits.max_samplers_per_shader_stage
+}
+
+fn create_bind_group_layout(
+    gpu: &crate::gpu::Gpu,
+    texture_array_length: Option<u32>,
+) -> wgpu::BindGroupLayout {
+    let count = texture_array_length.and_then(std::num::NonZeroU32::new);
+    let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count,
+    };
+    let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count,
+    };
+    let mut entries = vec![
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<GpuMaterial>() as _),
+            },
+            count: None,
+        },
+        texture_entry(1),
+        sampler_entry(2),
+    ];
+    // Without texture arrays, the emissive and metallic-roughness textures are bound separately
+    if texture_array_length.is_none() {
+        entries.extend([
+            texture_entry(3),
+            sampler_entry(4),
+            texture_entry(5),
+            sampler_entry(6),
+        ]);
+    }
+    gpu.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Material Bind Group Layout"),
+            entries: &entries,
+        })
+}
+
+/// Declares the material bindings, with texture arrays when `BINDLESS` is defined
+pub const SHADER_SOURCE: &str = "
+struct Material {
+    base_color_factor: vec4<f32>,
+    emissive_factor: vec4<f32>,
+    base_color_texture_index: u32,
+    emissive_texture_index: u32,
+    metallic_roughness_texture_index: u32,
+    metallic_factor: f32,
+    roughness_factor: f32,
+    alpha_mode: u32,
+    alpha_cutoff: f32,
+};
+
+@group(2) @binding(0)
+var<storage, read> materials: array<Material>;
+
+#ifdef BINDLESS
+@group(2) @binding(1)
+var textures: binding_array<texture_2d<f32>>;
+
+@group(2) @binding(2)
+var samplers: binding_array<sampler>;
+
+fn sample_texture(index: u32, uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(textures[index], samplers[index], uv);
+}
+
+fn sample_base_color(material: Material, uv: vec2<f32>) -> vec4<f32> {
+    return sample_texture(material.base_color_texture_index, uv);
+}
+
+fn sample_emissive(material: Material, uv: vec2<f32>) -> vec3<f32> {
+    return sample_texture(material.emissive_texture_index, uv).rgb;
+}
+
+fn sample_metallic_roughness(material: Material, uv: vec2<f32>) -> vec2<f32> {
+    return sample_texture(material.metallic_roughness_texture_index, uv).bg;
+}
+#else
+@group(2) @binding(1)
+var base_color_texture: texture_2d<f32>;
+
+@group(2) @binding(2)
+var base_color_sampler: sampler;
+
+@group(2) @binding(3)
+var emissive_texture: texture_2d<f32>;
+
+@group(2) @binding(4)
+var emissive_sampler: sampler;
+
+@group(2) @binding(5)
+var metallic_roughness_texture: texture_2d<f32>;
+
+@group(2) @binding(6)
+var metallic_roughness_sampler: sampler;
+
+// Only the bound material's textures are available, so the texture indices are unused
+fn sample_base_color(material: Material, uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(base_color_texture, base_color_sampler, uv);
+}
+
+fn sample_emissive(material: Material, uv: vec2<f32>) -> vec3<f32> {
+    return textureSample(emissive_texture, emissive_sampler, uv).rgb;
+}
+
+fn sample_metallic_roughness(material: Material, uv: vec2<f32>) -> vec2<f32> {
+    return textureSample(metallic_roughness_texture, metallic_roughness_sampler, uv).bg;
+}
+#endif
+
+// Whether a fragment of a masked material is cut out by its alpha cutoff
+fn is_masked_out(material: Material, uv: vec2<f32>) -> bool {
+    return material.base_color_factor.a * sample_base_color(material, uv).a < material.alpha_cutoff;
+}
+";
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn texture_arrays_leave_room_for_lighting_bindings() {
+        let features = wgpu::Features::TEXTURE_BINDING_ARRAY;
+        let limits = wgpu::Limits::default();
+        let at_limit = limits
+            .max_sampled_textures_per_shader_stage
+            .min(limits.max_samplers_per_shader_stage);
+        assert!(!super::fits_texture_array(features, &limits, at_limit));
+        assert!(super::fits_texture_array(features, &limits, 1));
+        assert!(!super::fits_texture_array(
+            wgpu::Features::empty(),
+            &limits,
+            1
+        ));
+    }
+}
@@ -0,0 +1,455 @@
+/// A shader drawing the materials that name it in place of the built in shader,
+/// such as water, foliage or toon shading.
+///
+/// The source is preprocessed with the scene shader's includes, so it can `#include "view.wgsl"`
+/// for the view bindings, `VertexOutput` and a `vertex_main` entry point, and `#include "lighting.wgsl"`
+/// for `light_contribution`. It must declare a `fragment_main` entry point and its own bindings in group 2,
+/// where binding 0 is a uniform buffer holding the material's packed parameters,
+/// followed by a texture and a sampler for each of its texture parameters.
+pub trait MaterialShader {
+    fn source(&self) -> String;
+
+    /// Packs a material's parameters into the contents of its uniform buffer
+    fn pack_uniform(&self, material: &crate::scene::Material) -> Vec<u8>;
+
+    /// The names of the texture parameters bound after the uniform buffer, in binding order.
+    /// Missing textures are bound as the default white texture.
+    fn texture_parameters(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The layout of group 2, which binds the uniform buffer and then the texture parameters
+    fn bind_group_layout(&self, device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let mut entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }];
+        (0..self.texture_parameters().len() as u32).for_each(|index| {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 1 + index * 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            });
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 2 + index * 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            });
+        });
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Material Shader Bind Group Layout"),
+            entries: &entries,
+        })
+    }
+
+    /// Blended shaders are drawn after everything else from back to front, without writing depth
+    fn blend_state(&self) -> Option<wgpu::BlendState> {
+        None
+    }
+
+    fn cull_mode(&self) -> Option<wgpu::Face> {
+        Some(wgpu::Face::Back)
+    }
+}
+
+/// What the view provides for registered shaders to be compiled with
+pub struct ShaderContext<'a> {
+    pub uniform_bind_group_layout: &'a wgpu::BindGroupLayout,
+    pub dynamic_uniform_bind_group_layout: &'a wgpu::BindGroupLayout,
+    pub lighting_bind_group_layout: &'a wgpu::BindGroupLayout,
+    /// The sources shaders can include by name
+    pub includes: &'a std::collections::HashMap<String, String>,
+}
+
+/// A registered shader with the pipeline it draws with
+struct RegisteredShader {
+    name: String,
+    shader: Box<dyn MaterialShader>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+/// The uniform buffer and textures bound for a material drawn with a registered shader
+struct MaterialBinding {
+    shader_index: usize,
+    /// The ids of the textures bound, so the bind group is recreated when they change
+    textures: Vec<String>,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// The registered material shaders and the bindings of the materials drawn with them
+#[derive(Default)]
+pub struct MaterialShaders {
+    shaders: Vec<RegisteredShader>,
+    bindings: Vec<MaterialBinding>,
+    /// Indices into the bindings, keyed by material id
+    binding_indices: std::collections::HashMap<String, usize>,
+}
+
+impl MaterialShaders {
+    /// Compiles a shader's pipeline with the view's camera, per draw and lighting bind group layouts
+    /// and registers it under the name, replacing any shader already registered with it.
+    /// Shaders that fail to compile or that the device rejects leave the registered shaders unchanged.
+    pub fn register(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        name: &str,
+        shader: Box<dyn MaterialShader>,
+        context: &ShaderContext,
+    ) -> Result<(), crate::shader::ShaderError> {
+        let bind_group_layout = shader.bind_group_layout(&gpu.device);
+        let pipeline =
+            create_shader_pipeline(gpu, name, shader.as_ref(), &bind_group_layout, context)?;
+
+        let registered = RegisteredShader {
+            name: name.to_string(),
+            shader,
+            bind_group_layout,
+            pipeline,
+        };
+        match self.shaders.iter().position(|shader| shader.name == name) {
+            Some(index) => self.shaders[index] = registered,
+            None => self.shaders.push(registered),
+        }
+        // Bindings are recreated with the new shader's layout when next prepared
+        self.clear_bindings();
+        Ok(())
+    }
+
+    /// Recompiles every registered shader, such as when the sources they include change.
+    /// Shaders that no longer compile are logged and keep drawing with their previous pipeline.
+    pub fn rebuild(&mut self, gpu: &crate::gpu::Gpu, context: &ShaderContext) {
+        self.shaders.iter_mut().for_each(|registered| {
+            match create_shader_pipeline(
+                gpu,
+                &registered.name,
+                registered.shader.as_ref(),
+                &registered.bind_group_layout,
+                context,
+            ) {
+                Ok(pipeline) => registered.pipeline = pipeline,
+                Err(error) => log::error!("{error}"),
+            }
+        });
+    }
+
+    /// Drops the bindings of every material, such as when a scene is imported
+    pub fn clear_bindings(&mut self) {
+        self.bindings.clear();
+        self.binding_indices.clear();
+    }
+
+    /// Packs the parameters of every material drawn with a registered shader into its uniform buffer,
+    /// creating its bind group the first time or whenever its textures change
+    pub fn prepare(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        scene: &crate::scene::Scene,
+        textures: &std::collections::HashMap<String, crate::render::Texture>,
+        default_texture: &crate::render::Texture,
+    ) {
+        if self.shaders.is_empty() {
+            return;
+        }
+        scene.materials.iter().for_each(|(material_id, material)| {
+            let Some(shader_index) = material.shader.as_ref().and_then(|shader_name| {
+                self.shaders
+                    .iter()
+                    .position(|shader| &shader.name == shader_name)
+            }) else {
+                self.binding_indices.remove(material_id);
+                return;
+            };
+            let shader = &self.shaders[shader_index];
+
+            // Uniform buffers are sized in multiples of 16 bytes, like wgsl structs
+            let mut contents = shader.shader.pack_uniform(material);
+            contents.resize(contents.len().max(1).next_multiple_of(16), 0);
+            let texture_ids = shader
+                .shader
+                .texture_parameters()
+                .iter()
+                .map(|name| {
+                    material
+                        .texture_parameter(name)
+                        .unwrap_or_default()
+                        .to_string()
+                })
+                .collect::<Vec<_>>();
+
+            let existing = self.binding_indices.get(material_id).copied();
+            if let Some(index) = existing {
+                let binding = &self.bindings[index];
+                if binding.shader_index == shader_index
+                    && binding.textures == texture_ids
+                    && binding.uniform_buffer.size() == contents.len() as u64
+                {
+                    gpu.queue
+                        .write_buffer(&binding.uniform_buffer, 0, &contents);
+                    return;
+                }
+            }
+
+            let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+                &gpu.device,
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Material Shader Uniform Buffer"),
+                    contents: &contents,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                },
+            );
+            let bound_textures = texture_ids
+                .iter()
+                .map(|texture_id| textures.get(texture_id).unwrap_or(default_texture))
+                .collect::<Vec<_>>();
+            let mut entries = vec![wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }];
+            bound_textures
+                .iter()
+                .enumerate()
+                .for_each(|(index, texture)| {
+                    entries.push(wgpu::BindGroupEntry {
+                        binding: 1 + index as u32 * 2,
+                        resource: wgpu::BindingResource::TextureView(&texture.view),
+                    });
+                    entries.push(wgpu::BindGroupEntry {
+                        binding: 2 + index as u32 * 2,
+                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                    });
+                });
+            let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(material_id),
+                layout: &shader.bind_group_layout,
+                entries: &entries,
+            });
+
+            let binding = MaterialBinding {
+                shader_index,
+                textures: texture_ids,
+                uniform_buffer,
+                bind_group,
+            };
+            match existing {
+                Some(index) => self.bindings[index] = binding,
+                None => {
+                    self.binding_indices
+                        .insert(material_id.clone(), self.bindings.len());
+                    self.bindings.push(binding);
+                }
+            }
+        });
+    }
+
+    /// The index of the material's binding, if it is drawn with a registered shader
+    pub fn binding_index(&self, material_id: &str) -> Option<usize> {
+        self.binding_indices.get(material_id).copied()
+    }
+
+    /// The index of the shader a binding's material is drawn with, which draws are batched by
+    pub fn shader_index(&self, binding_index: usize) -> usize {
+        self.bindings[binding_index].shader_index
+    }
+
+    pub fn is_blended(&self, binding_index: usize) -> bool {
+        self.shaders[self.shader_index(binding_index)]
+            .shader
+            .blend_state()
+            .is_some()
+    }
+
+    pub fn pipeline(&self, binding_index: usize) -> &wgpu::RenderPipeline {
+        &self.shaders[self.shader_index(binding_index)].pipeline
+    }
+
+    pub fn bind<'rp>(
+        &'rp self,
+        render_pass: &mut wgpu::RenderPass<'rp>,
+        group: u32,
+        binding_index: usize,
+    ) {
+        render_pass.set_bind_group(group, &self.bindings[binding_index].bind_group, &[]);
+    }
+}
+
+fn create_shader_pipeline(
+    gpu: &crate::gpu::Gpu,
+    name: &str,
+    shader: &dyn MaterialShader,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    context: &ShaderContext,
+) -> Result<wgpu::RenderPipeline, crate::shader::ShaderError> {
+    let source = crate::shader::preprocess(
+        name,
+        &shader.source(),
+        &crate::shader::ShaderDefines::new(),
+        context.includes,
+    )?;
+    crate::shader::validate(name, &source)?;
+
+    crate::shader::create_validated(gpu, name, || {
+        let layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(name),
+                bind_group_layouts: &[
+                    context.uniform_bind_group_layout,
+                    context.dynamic_uniform_bind_group_layout,
+                    bind_group_layout,
+                    context.lighting_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let shader_module = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(name),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(source)),
+            });
+        let blend_state = shader.blend_state();
+        crate::view::create_pipeline(
+            gpu,
+            &layout,
+            &shader_module,
+            "fragment_main",
+            wgpu::PrimitiveTopology::TriangleList,
+            (blend_state.is_none(), wgpu::CompareFunction::Less),
+            blend_state.unwrap_or(wgpu::BlendState::REPLACE),
+            shader.cull_mode(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    /// Draws its materials in a flat color parameter
+    struct SolidColor;
+
+    impl super::MaterialShader for SolidColor {
+        fn source(&self) -> String {
+            "
+#include \"view.wgsl\"
+
+struct SolidColor {
+    color: vec4<f32>,
+};
+
+@group(2) @binding(0)
+var<uniform> solid_color: SolidColor;
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return solid_color.color;
+}
+"
+            .to_string()
+        }
+
+        fn pack_uniform(&self, material: &crate::scene::Material) -> Vec<u8> {
+            let color = material
+                .vec4_parameter("color")
+                .unwrap_or(nalgebra_glm::vec4(1.0, 1.0, 1.0, 1.0));
+            bytemuck::cast_slice(color.as_slice()).to_vec()
+        }
+    }
+
+    #[test]
+    fn materials_draw_with_registered_shaders() {
+        let Some(crate::render::HelmetFixture {
+            mut renderer,
+            mut scene,
+            camera,
+            image: lit,
+        }) = crate::render::HelmetFixture::new()
+        else {
+            return;
+        };
+
+        renderer
+            .view
+            .register_material_shader(&renderer.gpu, "solid_color", SolidColor)
+            .expect("The solid color shader should compile!");
+        scene.materials.values_mut().for_each(|material| {
+            material.shader = Some("solid_color".to_string());
+            material.parameters.insert(
+                "color".to_string(),
+                crate::scene::MaterialParameter::Vec4(nalgebra_glm::vec4(0.0, 1.0, 0.0, 1.0)),
+            );
+        });
+        let solid = renderer.render_to_image(&scene, camera, 64, 64);
+        assert_ne!(solid, lit);
+        assert!(renderer
+            .view
+            .draws
+            .iter()
+            .all(|draw| matches!(draw.pipeline, crate::view::DrawPipeline::MaterialShader(_))));
+        let center = solid.get_pixel(32, 32);
+        assert!(center[1] > center[0] && center[1] > center[2]);
+
+        assert!(matches!(
+            renderer
+                .view
+                .register_material_shader(&renderer.gpu, "broken", BrokenShader),
+            Err(crate::shader::ShaderError::Parse { .. })
+        ));
+
+        // Shaders the device rejects don't replace the shader registered under their name
+        assert!(matches!(
+            renderer
+                .view
+                .register_material_shader(&renderer.gpu, "solid_color", UnboundShader),
+            Err(crate::shader::ShaderError::Pipeline { .. })
+        ));
+        assert_eq!(renderer.render_to_image(&scene, camera, 64, 64), solid);
+    }
+
+    /// Valid wgsl reading a binding the material shader layout doesn't have
+    struct UnboundShader;
+
+    impl super::MaterialShader for UnboundShader {
+        fn source(&self) -> String {
+            "
+#include \"view.wgsl\"
+
+@group(2) @binding(7)
+var<uniform> color: vec4<f32>;
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return color;
+}
+"
+            .to_string()
+        }
+
+        fn pack_uniform(&self, _material: &crate::scene::Material) -> Vec<u8> {
+            Vec::new()
+        }
+    }
+
+    struct BrokenShader;
+
+    impl super::MaterialShader for BrokenShader {
+        fn source(&self) -> String {
+            "fn fragment_main( {".to_string()
+        }
+
+        fn pack_uniform(&self, _material: &crate::scene::Material) -> Vec<u8> {
+            Vec::new()
+        }
+    }
+}
@@ -0,0 +1,754 @@
+/// The thickest outline that can be drawn, in pixels
+pub const MAX_THICKNESS: u32 = 32;
+
+/// The seed pass, one flood pass per halving of the step size and the composite pass
+const MAX_PASSES: usize = 2 + MAX_THICKNESS.ilog2() as usize + 1;
+
+const MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Each texel holds the nearest selected and hovered pixels found so far
+const FLOOD_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Uint;
+
+#[repr(C, align(256))]
+#[derive(Default, Copy, Clone, Debug, bytemuck::Zeroable)]
+struct OutlineUniform {
+    selected_color: nalgebra_glm::Vec4,
+    hovered_color: nalgebra_glm::Vec4,
+    /// The distance in pixels between the texels a flood pass compares
+    step: i32,
+    thickness: f32,
+    occluded_opacity: f32,
+}
+
+/// Outlines the silhouettes of the selected and hovered nodes and their children.
+///
+/// The nodes are drawn into a mask, once tested against the scene depth and once not,
+/// then a jump flood finds the nearest masked pixel of every pixel around them,
+/// and the outline is composited over the post processed output.
+/// Occluded parts of the outline are drawn with reduced opacity.
+pub struct Outline {
+    pub selected: Option<petgraph::graph::NodeIndex>,
+    pub hovered: Option<petgraph::graph::NodeIndex>,
+    pub selected_color: nalgebra_glm::Vec4,
+    pub hovered_color: nalgebra_glm::Vec4,
+    /// The width of the outline in pixels, up to `MAX_THICKNESS`
+    pub thickness: u32,
+    /// The opacity of the outline where the outlined node is hidden behind other geometry
+    pub occluded_opacity: f32,
+    /// The view draws of the selected and hovered nodes, prepared each frame
+    selected_draws: Vec<usize>,
+    hovered_draws: Vec<usize>,
+    passes: usize,
+    width: u32,
+    height: u32,
+    msaa_mask_view: Option<wgpu::TextureView>,
+    mask_view: wgpu::TextureView,
+    flood_views: [wgpu::TextureView; 2],
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// Each reads the mask and one of the flood textures
+    bind_groups: [wgpu::BindGroup; 2],
+    seed_pipeline: wgpu::RenderPipeline,
+    flood_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+}
+
+impl Outline {
+    pub const DEFAULT_SELECTED_COLOR: nalgebra_glm::Vec4 =
+        nalgebra_glm::Vec4::new(1.0, 0.6, 0.1, 1.0);
+    pub const DEFAULT_HOVERED_COLOR: nalgebra_glm::Vec4 =
+        nalgebra_glm::Vec4::new(0.4, 0.7, 1.0, 1.0);
+
+    pub fn new(gpu: &crate::gpu::Gpu, width: u32, height: u32) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        let uniform_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Outline Uniform Buffer"),
+            size: MAX_PASSES as wgpu::BufferAddress * gpu.alignment(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform_size = wgpu::BufferSize::new(std::mem::size_of::<OutlineUniform>() as _);
+        let bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Outline Bind Group Layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: true,
+                                min_binding_size: uniform_size,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Uint,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+        let (msaa_mask_view, mask_view, flood_views) = create_targets(gpu, width, height);
+        let bind_groups = create_bind_groups(
+            gpu,
+            &bind_group_layout,
+            &uniform_buffer,
+            &mask_view,
+            &flood_views,
+        );
+
+        let shader_module = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Outline Shader"),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(SHADER_SOURCE)),
+            });
+        let fullscreen_pipeline_layout =
+            gpu.device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Outline Pipeline Layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let fullscreen_pipeline =
+            |vertex_entry_point: &str,
+             entry_point: &str,
+             format: wgpu::TextureFormat,
+             blend: Option<wgpu::BlendState>,
+             multisample: wgpu::MultisampleState| {
+                gpu.device
+                    .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some(entry_point),
+                        layout: Some(&fullscreen_pipeline_layout),
+                        vertex: wgpu::VertexState {
+                            module: &shader_module,
+                            entry_point: vertex_entry_point,
+                            buffers: &[],
+                        },
+                        primitive: wgpu::PrimitiveState::default(),
+                        depth_stencil: None,
+                        multisample,
+                        fragment: Some(wgpu::FragmentState {
+                            module: &shader_module,
+                            entry_point,
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format,
+                                blend,
+                                write_mask: wgpu::ColorWrites::ALL,
+                            })],
+                        }),
+                        multiview: None,
+                    })
+            };
+
+        Self {
+            selected: None,
+            hovered: None,
+            selected_color: Self::DEFAULT_SELECTED_COLOR,
+            hovered_color: Self::DEFAULT_HOVERED_COLOR,
+            thickness: 3,
+            occluded_opacity: 0.3,
+            selected_draws: Vec::new(),
+            hovered_draws: Vec::new(),
+            passes: 0,
+            width,
+            height,
+            msaa_mask_view,
+            mask_view,
+            flood_views,
+            uniform_buffer,
+            bind_group_layout,
+            bind_groups,
+            seed_pipeline: fullscreen_pipeline(
+                "fullscreen_vertex_main",
+                "seed_fragment_main",
+                FLOOD_FORMAT,
+                None,
+                wgpu::MultisampleState::default(),
+            ),
+            flood_pipeline: fullscreen_pipeline(
+                "fullscreen_vertex_main",
+                "flood_fragment_main",
+                FLOOD_FORMAT,
+                None,
+                wgpu::MultisampleState::default(),
+            ),
+            // The outline is composited over the output, which is multisampled with msaa
+            composite_pipeline: fullscreen_pipeline(
+                "composite_vertex_main",
+                "composite_fragment_main",
+                gpu.surface_format,
+                Some(wgpu::BlendState::ALPHA_BLENDING),
+                gpu.multisample_state(),
+            ),
+        }
+    }
+
+    /// Outlines the same nodes in the same style as another outline,
+    /// for outlining them in more than one view
+    pub fn copy_settings_from(&mut self, other: &Self) {
+        self.selected = other.selected;
+        self.hovered = other.hovered;
+        self.selected_color = other.selected_color;
+        self.hovered_color = other.hovered_color;
+        self.thickness = other.thickness;
+        self.occluded_opacity = other.occluded_opacity;
+    }
+
+    pub fn resize(&mut self, gpu: &crate::gpu::Gpu, width: u32, height: u32) {
+        let (width, height) = (width.max(1), height.max(1));
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+        (self.msaa_mask_view, self.mask_view, self.flood_views) =
+            create_targets(gpu, width, height);
+        self.bind_groups = create_bind_groups(
+            gpu,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            &self.mask_view,
+            &self.flood_views,
+        );
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Finds the prepared view's draws of the outlined nodes and their children,
+    /// and writes the uniforms of the outline passes
+    pub fn prepare(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        scene: &crate::scene::Scene,
+        view: &crate::view::View,
+    ) {
+        let draws_of = |node_index: Option<petgraph::graph::NodeIndex>| {
+            let Some(node_index) =
+                node_index.filter(|node_index| node_index.index() < scene.graph.node_count())
+            else {
+                return Vec::new();
+            };
+            let mut nodes = std::collections::HashSet::new();
+            let mut dfs = petgraph::visit::Dfs::new(&scene.graph.0, node_index);
+            while let Some(node_index) = dfs.next(&scene.graph.0) {
+                nodes.insert(node_index);
+            }
+            view.draws
+                .iter()
+                .enumerate()
+                .filter(|(_, draw)| nodes.contains(&draw.node_index))
+                .map(|(draw_index, _)| draw_index)
+                .collect()
+        };
+        self.selected_draws = draws_of(self.selected);
+        self.hovered_draws = if self.hovered == self.selected {
+            Vec::new()
+        } else {
+            draws_of(self.hovered)
+        };
+
+        // The steps halve from the thickness rounded up to a power of two down to a single pixel
+        let thickness = self.thickness.clamp(1, MAX_THICKNESS);
+        let uniform = |step: u32| OutlineUniform {
+            selected_color: self.selected_color,
+            hovered_color: self.hovered_color,
+            step: step as i32,
+            thickness: thickness as f32,
+            occluded_opacity: self.occluded_opacity,
+        };
+        let mut uniforms = vec![uniform(0)];
+        let mut step = thickness.next_power_of_two();
+        while step >= 1 {
+            uniforms.push(uniform(step));
+            step /= 2;
+        }
+        uniforms.push(uniform(0));
+        self.passes = uniforms.len();
+
+        let mut padded_uniforms = vec![OutlineUniform::default(); MAX_PASSES];
+        padded_uniforms[..uniforms.len()].copy_from_slice(&uniforms);
+        gpu.queue.write_buffer(&self.uniform_buffer, 0, unsafe {
+            std::slice::from_raw_parts(
+                padded_uniforms.as_ptr() as *const u8,
+                padded_uniforms.len() * gpu.alignment() as usize,
+            )
+        });
+    }
+
+    /// Draws the outlines over the output, or over a viewport of it,
+    /// which must be the size the outline was resized to.
+    /// Reads the scene depth of the post process, so it must be called after the scene was rendered.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        gpu: &crate::gpu::Gpu,
+        view: &crate::view::View,
+        post_process: &crate::postprocess::PostProcess,
+        output_view: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        viewport: Option<crate::view::PixelRect>,
+    ) {
+        if self.selected_draws.is_empty() && self.hovered_draws.is_empty() {
+            return;
+        }
+        let offset = |pass: usize| (pass as u64 * gpu.alignment()) as wgpu::DynamicOffset;
+
+        {
+            let (mask_view, mask_resolve_target) = match self.msaa_mask_view.as_ref() {
+                Some(msaa_mask_view) => (msaa_mask_view, Some(&self.mask_view)),
+                None => (&self.mask_view, None),
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Outline Mask Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: mask_view,
+                    resolve_target: mask_resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &post_process.depth_texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            render_pass.set_bind_group(0, &view.uniform_bind_group, &[]);
+            view.materials.bind_all(&mut render_pass, 2);
+            render_pass.set_vertex_buffer(0, view.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(view.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            [&self.selected_draws, &self.hovered_draws]
+                .into_iter()
+                .enumerate()
+                .for_each(|(channel, draws)| {
+                    (0..2).for_each(|visibility| {
+                        draws.iter().for_each(|draw_index| {
+                            let draw = &view.draws[*draw_index];
+                            render_pass.set_pipeline(
+                                &view.pass_pipelines.outline_mask[draw.pass_variant()][channel]
+                                    [visibility],
+                            );
+                            render_pass.set_bind_group(
+                                1,
+                                &view.dynamic_uniform_bind_group,
+                                &[offset(*draw_index)],
+                            );
+                            view.materials
+                                .bind(&mut render_pass, 2, draw.material_index);
+                            render_pass.draw_indexed(draw.indices.clone(), draw.base_vertex, 0..1);
+                        });
+                    });
+                });
+        }
+
+        // The seed pass writes into the first flood texture, and each flood pass swaps them
+        let flood_passes = self.passes - 2;
+        (0..=flood_passes).for_each(|pass| {
+            let (pipeline, label) = if pass == 0 {
+                (&self.seed_pipeline, "Outline Seed Render Pass")
+            } else {
+                (&self.flood_pipeline, "Outline Flood Render Pass")
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.flood_views[pass % 2],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &self.bind_groups[(pass + 1) % 2], &[offset(pass)]);
+            render_pass.draw(0..3, 0..1);
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Outline Composite Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output_view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        if let Some(viewport) = viewport {
+            viewport.apply(&mut render_pass);
+        }
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(
+            0,
+            &self.bind_groups[flood_passes % 2],
+            &[offset(self.passes - 1)],
+        );
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// Creates the mask pipelines of each pass variant, which bind the view's materials
+pub fn create_mask_pipelines(
+    gpu: &crate::gpu::Gpu,
+    context: &crate::view::PassContext,
+) -> Vec<[[wgpu::RenderPipeline; 2]; 2]> {
+    let pipeline_layout = gpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Outline Mask Pipeline Layout"),
+            bind_group_layouts: &[
+                context.uniform_bind_group_layout,
+                context.dynamic_uniform_bind_group_layout,
+                &context.materials.bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+    crate::view::pass_variants()
+        .iter()
+        .map(|variant| {
+            let shader_module = crate::view::create_pass_shader_module(
+                gpu,
+                "Outline Mask Shader",
+                MASK_SHADER_SOURCE,
+                variant,
+                context,
+            );
+            let mask_pipeline = |write_mask: wgpu::ColorWrites, depth_compare| {
+                gpu.device
+                    .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some("Outline Mask Pipeline"),
+                        layout: Some(&pipeline_layout),
+                        vertex: wgpu::VertexState {
+                            module: &shader_module,
+                            entry_point: "vertex_main",
+                            buffers: &[crate::scene::Vertex::description(
+                                &crate::scene::Vertex::attributes(),
+                            )],
+                        },
+                        primitive: wgpu::PrimitiveState {
+                            front_face: wgpu::FrontFace::Ccw,
+                            cull_mode: (!variant.contains_key("DOUBLE_SIDED"))
+                                .then_some(wgpu::Face::Back),
+                            ..Default::default()
+                        },
+                        depth_stencil: Some(wgpu::DepthStencilState {
+                            format: crate::gpu::Gpu::DEPTH_FORMAT,
+                            depth_write_enabled: false,
+                            depth_compare,
+                            stencil: wgpu::StencilState::default(),
+                            bias: wgpu::DepthBiasState::default(),
+                        }),
+                        multisample: gpu.multisample_state(),
+                        fragment: Some(wgpu::FragmentState {
+                            module: &shader_module,
+                            entry_point: "mask_fragment_main",
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format: MASK_FORMAT,
+                                blend: None,
+                                write_mask,
+                            })],
+                        }),
+                        multiview: None,
+                    })
+            };
+            [
+                [wgpu::ColorWrites::RED, wgpu::ColorWrites::GREEN],
+                [wgpu::ColorWrites::BLUE, wgpu::ColorWrites::ALPHA],
+            ]
+            .map(|[any, visible]| {
+                [
+                    mask_pipeline(any, wgpu::CompareFunction::Always),
+                    mask_pipeline(visible, wgpu::CompareFunction::LessEqual),
+                ]
+            })
+        })
+        .collect()
+}
+
+fn create_targets(
+    gpu: &crate::gpu::Gpu,
+    width: u32,
+    height: u32,
+) -> (
+    Option<wgpu::TextureView>,
+    wgpu::TextureView,
+    [wgpu::TextureView; 2],
+) {
+    let create_texture = |label: &str, format: wgpu::TextureFormat| {
+        gpu.device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    };
+    (
+        gpu.create_msaa_texture(width, height, MASK_FORMAT),
+        create_texture("Outline Mask Texture", MASK_FORMAT),
+        [
+            create_texture("Outline Flood Texture", FLOOD_FORMAT),
+            create_texture("Outline Flood Texture", FLOOD_FORMAT),
+        ],
+    )
+}
+
+fn create_bind_groups(
+    gpu: &crate::gpu::Gpu,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    mask_view: &wgpu::TextureView,
+    flood_views: &[wgpu::TextureView; 2],
+) -> [wgpu::BindGroup; 2] {
+    [&flood_views[0], &flood_views[1]].map(|flood_view| {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Outline Bind Group"),
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: uniform_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<OutlineUniform>() as _),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(mask_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(flood_view),
+                },
+            ],
+        })
+    })
+}
+
+/// Draws the outlined primitives into the mask, cutting out masked primitives like the scene shader does
+pub const MASK_SHADER_SOURCE: &str = "
+#include \"view.wgsl\"
+#include \"material.wgsl\"
+
+// The pipeline's write mask selects the channel this is written to
+@fragment
+fn mask_fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+#ifdef ALPHA_MASK
+    if is_masked_out(materials[mesh_ubo.material_index], in.uv_0) {
+        discard;
+    }
+#endif
+    return vec4(1.0);
+}
+";
+
+const SHADER_SOURCE: &str = "
+struct OutlineUniform {
+    selected_color: vec4<f32>,
+    hovered_color: vec4<f32>,
+    step: i32,
+    thickness: f32,
+    occluded_opacity: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> outline: OutlineUniform;
+
+// The selected mask in red, visible selected in green, hovered in blue and visible hovered in alpha
+@group(0) @binding(1)
+var mask_texture: texture_2d<f32>;
+
+// The nearest selected pixel in xy and hovered pixel in zw
+@group(0) @binding(2)
+var flood_texture: texture_2d<u32>;
+
+const NO_SEED: u32 = 65535u;
+
+@vertex
+fn fullscreen_vertex_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let position = vec2(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u)) * 2.0 - 1.0;
+    return vec4(position, 0.0, 1.0);
+}
+
+@fragment
+fn seed_fragment_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<u32> {
+    let texel = vec2<i32>(position.xy);
+    let mask = textureLoad(mask_texture, texel, 0);
+    var seeds = vec4(NO_SEED);
+    if mask.r > 0.5 {
+        seeds.x = u32(texel.x);
+        seeds.y = u32(texel.y);
+    }
+    if mask.b > 0.5 {
+        seeds.z = u32(texel.x);
+        seeds.w = u32(texel.y);
+    }
+    return seeds;
+}
+
+fn seed_distance(texel: vec2<i32>, seed: vec2<u32>) -> f32 {
+    if seed.x == NO_SEED {
+        return 1e20;
+    }
+    return distance(vec2<f32>(texel), vec2<f32>(seed));
+}
+
+@fragment
+fn flood_fragment_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<u32> {
+    let texel = vec2<i32>(position.xy);
+    let size = vec2<i32>(textureDimensions(flood_texture));
+    var nearest = vec4(NO_SEED);
+    var nearest_distances = vec2(1e20);
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let neighbor = texel + vec2(x, y) * outline.step;
+            if any(neighbor < vec2(0)) || any(neighbor >= size) {
+                continue;
+            }
+            let seeds = textureLoad(flood_texture, neighbor, 0);
+            let selected_distance = seed_distance(texel, seeds.xy);
+            if selected_distance < nearest_distances.x {
+                nearest_distances.x = selected_distance;
+                nearest.x = seeds.x;
+                nearest.y = seeds.y;
+            }
+            let hovered_distance = seed_distance(texel, seeds.zw);
+            if hovered_distance < nearest_distances.y {
+                nearest_distances.y = hovered_distance;
+                nearest.z = seeds.z;
+                nearest.w = seeds.w;
+            }
+        }
+    }
+    return nearest;
+}
+
+// The opacity of the outline at a distance from its nearest seed,
+// reduced if that seed was hidden behind other geometry
+fn outline_opacity(texel: vec2<i32>, seed: vec2<u32>, visible: f32) -> f32 {
+    let coverage = clamp(outline.thickness + 0.5 - seed_distance(texel, seed), 0.0, 1.0);
+    return coverage * mix(outline.occluded_opacity, 1.0, step(0.5, visible));
+}
+
+struct CompositeOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// The output may be larger than the outline targets when compositing into a viewport,
+// so texels are found from the uv instead of the fragment position
+@vertex
+fn composite_vertex_main(@builtin(vertex_index) vertex_index: u32) -> CompositeOutput {
+    let position = vec2(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u)) * 2.0 - 1.0;
+    var out: CompositeOutput;
+    out.position = vec4(position, 0.0, 1.0);
+    out.uv = vec2(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+    return out;
+}
+
+@fragment
+fn composite_fragment_main(in: CompositeOutput) -> @location(0) vec4<f32> {
+    let texel = vec2<i32>(in.uv * vec2<f32>(textureDimensions(mask_texture)));
+    let mask = textureLoad(mask_texture, texel, 0);
+    let seeds = textureLoad(flood_texture, texel, 0);
+
+    // Outlines are only drawn around the silhouettes, and the selection is drawn over the hover
+    var color = vec4(0.0);
+    if mask.r < 0.5 && seeds.x != NO_SEED {
+        let visible = textureLoad(mask_texture, vec2<i32>(seeds.xy), 0).g;
+        color = outline.selected_color;
+        color.a *= outline_opacity(texel, seeds.xy, visible);
+    }
+    if color.a <= 0.0 && mask.r < 0.5 && mask.b < 0.5 && seeds.z != NO_SEED {
+        let visible = textureLoad(mask_texture, vec2<i32>(seeds.zw), 0).a;
+        color = outline.hovered_color;
+        color.a *= outline_opacity(texel, seeds.zw, visible);
+    }
+    if color.a <= 0.0 {
+        discard;
+    }
+    return color;
+}
+";
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn outline_surrounds_selection() {
+        let Some(mut fixture) = crate::render::HelmetFixture::new() else {
+            return;
+        };
+        let unselected = fixture.image.clone();
+        let background = *unselected.get_pixel(0, 0);
+        let size = crate::render::HelmetFixture::SIZE as i64;
+        let covered = |x: i64, y: i64| {
+            (0..size).contains(&x)
+                && (0..size).contains(&y)
+                && *unselected.get_pixel(x as u32, y as u32) != background
+        };
+        let covered_within = |x: i64, y: i64, distance: i64| {
+            (-distance..=distance)
+                .any(|dy| (-distance..=distance).any(|dx| covered(x + dx, y + dy)))
+        };
+
+        // Selecting the root outlines every mesh below it
+        fixture.renderer.outline.selected = Some(petgraph::graph::NodeIndex::new(0));
+        let thickness = fixture.renderer.outline.thickness as i64;
+        let selected = fixture.render();
+
+        // The outline only covers the helmet's silhouette and the background right around it
+        let outlined = selected
+            .enumerate_pixels()
+            .filter(|(x, y, pixel)| *pixel != unselected.get_pixel(*x, *y))
+            .map(|(x, y, _)| (x as i64, y as i64))
+            .collect::<Vec<_>>();
+        assert!(!outlined.is_empty(), "No outline was drawn");
+        outlined.iter().for_each(|&(x, y)| {
+            let inside = (-1..=1).all(|dy| (-1..=1).all(|dx| covered(x + dx, y + dy)));
+            assert!(
+                !inside,
+                "The outline was drawn inside the helmet at {x}, {y}"
+            );
+            assert!(
+                covered_within(x, y, thickness),
+                "The outline at {x}, {y} is away from the helmet"
+            );
+        });
+    }
+}
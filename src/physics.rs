@@ -0,0 +1,523 @@
+/// A rigid body simulation built from the rigid body and collider components of a scene,
+/// stepped at a fixed rate independent of the frame rate
+pub struct PhysicsWorld {
+    pub gravity: nalgebra_glm::Vec3,
+    /// Seconds simulated by each step
+    pub timestep: f32,
+    /// Upper bound on steps taken per update, so a long frame can't stall the simulation
+    pub max_steps_per_update: usize,
+    pub bodies: rapier3d::dynamics::RigidBodySet,
+    pub colliders: rapier3d::geometry::ColliderSet,
+    pub node_bodies:
+        std::collections::HashMap<petgraph::graph::NodeIndex, rapier3d::dynamics::RigidBodyHandle>,
+    pub query_pipeline: rapier3d::pipeline::QueryPipeline,
+    /// Collision events from the most recent update
+    pub collision_events: Vec<CollisionEvent>,
+    accumulator: f32,
+    /// Body poses before and after the latest step, interpolated for rendering
+    poses: std::collections::HashMap<
+        petgraph::graph::NodeIndex,
+        (nalgebra::Isometry3<f32>, nalgebra::Isometry3<f32>),
+    >,
+    pipeline: rapier3d::pipeline::PhysicsPipeline,
+    integration_parameters: rapier3d::dynamics::IntegrationParameters,
+    islands: rapier3d::dynamics::IslandManager,
+    broad_phase: rapier3d::geometry::BroadPhase,
+    narrow_phase: rapier3d::geometry::NarrowPhase,
+    impulse_joints: rapier3d::dynamics::ImpulseJointSet,
+    multibody_joints: rapier3d::dynamics::MultibodyJointSet,
+    ccd_solver: rapier3d::dynamics::CCDSolver,
+    event_collector: EventCollector,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct CollisionEvent {
+    pub nodes: (petgraph::graph::NodeIndex, petgraph::graph::NodeIndex),
+    /// Whether the colliders started or stopped touching
+    pub started: bool,
+    /// Whether either collider is a trigger
+    pub is_trigger: bool,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        Self {
+            gravity: nalgebra_glm::vec3(0.0, -9.81, 0.0),
+            timestep: 1.0 / 60.0,
+            max_steps_per_update: 5,
+            bodies: rapier3d::dynamics::RigidBodySet::new(),
+            colliders: rapier3d::geometry::ColliderSet::new(),
+            node_bodies: std::collections::HashMap::new(),
+            query_pipeline: rapier3d::pipeline::QueryPipeline::new(),
+            collision_events: Vec::new(),
+            accumulator: 0.0,
+            poses: std::collections::HashMap::new(),
+            pipeline: rapier3d::pipeline::PhysicsPipeline::new(),
+            integration_parameters: rapier3d::dynamics::IntegrationParameters::default(),
+            islands: rapier3d::dynamics::IslandManager::new(),
+            broad_phase: rapier3d::geometry::BroadPhase::new(),
+            narrow_phase: rapier3d::geometry::NarrowPhase::new(),
+            impulse_joints: rapier3d::dynamics::ImpulseJointSet::new(),
+            multibody_joints: rapier3d::dynamics::MultibodyJointSet::new(),
+            ccd_solver: rapier3d::dynamics::CCDSolver::new(),
+            event_collector: EventCollector::default(),
+        }
+    }
+}
+
+impl PhysicsWorld {
+    pub fn from_scene(scene: &crate::scene::Scene) -> Self {
+        let mut world = Self::default();
+        world.rebuild(scene);
+        world
+    }
+
+    /// Recreates every body and collider from the scene's components
+    pub fn rebuild(&mut self, scene: &crate::scene::Scene) {
+        *self = Self {
+            gravity: self.gravity,
+            timestep: self.timestep,
+            max_steps_per_update: self.max_steps_per_update,
+            ..Default::default()
+        };
+
+        scene.walk_dfs(|node, node_index| {
+            let (position, scale) = decompose(&scene.graph.global_transform(node_index));
+
+            let rigid_body = node
+                .components
+                .iter()
+                .find_map(|component| match component {
+                    crate::scene::NodeComponent::RigidBody(rigid_body) => Some(*rigid_body),
+                    _ => None,
+                });
+            let colliders = node
+                .components
+                .iter()
+                .filter_map(|component| match component {
+                    crate::scene::NodeComponent::Collider(collider) => Some(collider),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            let body_handle = rigid_body.map(|rigid_body| {
+                let builder = match rigid_body.kind {
+                    crate::scene::RigidBodyKind::Dynamic => {
+                        rapier3d::dynamics::RigidBodyBuilder::dynamic()
+                    }
+                    crate::scene::RigidBodyKind::Kinematic => {
+                        rapier3d::dynamics::RigidBodyBuilder::kinematic_position_based()
+                    }
+                    crate::scene::RigidBodyKind::Static => {
+                        rapier3d::dynamics::RigidBodyBuilder::fixed()
+                    }
+                };
+                let number_of_solid_colliders = colliders
+                    .iter()
+                    .filter(|collider| !collider.is_trigger)
+                    .count();
+                let mut builder = builder
+                    .position(position)
+                    .linear_damping(rigid_body.linear_damping)
+                    .angular_damping(rigid_body.angular_damping)
+                    .user_data(node_index.index() as u128);
+                if number_of_solid_colliders == 0 {
+                    builder = builder.additional_mass(rigid_body.mass);
+                }
+                let handle = self.bodies.insert(builder.build());
+                self.node_bodies.insert(node_index, handle);
+                self.poses.insert(node_index, (position, position));
+                handle
+            });
+
+            let number_of_solid_colliders = colliders
+                .iter()
+                .filter(|collider| !collider.is_trigger)
+                .count()
+                .max(1);
+            colliders.iter().for_each(|collider| {
+                let Some(shape) = create_shape(&collider.shape, &scale) else {
+                    log::warn!("Skipping degenerate collider on node '{}'", node.label);
+                    return;
+                };
+                let mut builder = rapier3d::geometry::ColliderBuilder::new(shape)
+                    .sensor(collider.is_trigger)
+                    .collision_groups(rapier3d::geometry::InteractionGroups::new(
+                        rapier3d::geometry::Group::from_bits_truncate(collider.layers),
+                        rapier3d::geometry::Group::from_bits_truncate(collider.mask),
+                    ))
+                    .active_events(rapier3d::pipeline::ActiveEvents::COLLISION_EVENTS)
+                    .user_data(node_index.index() as u128);
+                match (body_handle, rigid_body) {
+                    (Some(body_handle), Some(rigid_body)) => {
+                        builder = builder
+                            .restitution(rigid_body.restitution)
+                            .friction(rigid_body.friction);
+                        if collider.is_trigger {
+                            builder = builder.density(0.0);
+                        } else {
+                            builder =
+                                builder.mass(rigid_body.mass / number_of_solid_colliders as f32);
+                        }
+                        self.colliders.insert_with_parent(
+                            builder.build(),
+                            body_handle,
+                            &mut self.bodies,
+                        );
+                    }
+                    _ => {
+                        // Colliders without a rigid body on their node are static
+                        self.colliders.insert(builder.position(position).build());
+                    }
+                }
+            });
+        });
+
+        self.query_pipeline.update(&self.bodies, &self.colliders);
+    }
+
+    /// Adds static triangle mesh colliders for every mesh node that has no physics components,
+    /// so that imported level geometry can be collided with as-is
+    pub fn add_static_mesh_colliders(&mut self, scene: &crate::scene::Scene) {
+        scene.walk_dfs(|node, node_index| {
+            let has_physics_components = node.components.iter().any(|component| {
+                matches!(
+                    component,
+                    crate::scene::NodeComponent::Collider(_)
+                        | crate::scene::NodeComponent::RigidBody(_)
+                        | crate::scene::NodeComponent::CharacterController(_)
+                )
+            });
+            if has_physics_components {
+                return;
+            }
+            let (position, scale) = decompose(&scene.graph.global_transform(node_index));
+            node.components
+                .iter()
+                .filter_map(|component| match component {
+                    crate::scene::NodeComponent::Mesh(mesh_id) => scene.meshes.get(mesh_id),
+                    _ => None,
+                })
+                .filter_map(|mesh| {
+                    create_shape(
+                        &crate::scene::ColliderShape::triangle_mesh_from_mesh(mesh),
+                        &scale,
+                    )
+                })
+                .for_each(|shape| {
+                    self.colliders.insert(
+                        rapier3d::geometry::ColliderBuilder::new(shape)
+                            .position(position)
+                            .user_data(node_index.index() as u128)
+                            .build(),
+                    );
+                });
+        });
+        self.query_pipeline.update(&self.bodies, &self.colliders);
+    }
+
+    /// Advances the simulation by as many fixed steps as fit in the elapsed time,
+    /// then writes interpolated body transforms back to the scene
+    pub fn update(&mut self, scene: &mut crate::scene::Scene, delta_time: f64) {
+        self.collision_events.clear();
+        self.accumulator += delta_time as f32;
+
+        let mut steps = 0;
+        while self.accumulator >= self.timestep {
+            if steps == self.max_steps_per_update {
+                // Drop the time that couldn't be simulated instead of falling further behind
+                self.accumulator %= self.timestep;
+                break;
+            }
+            self.sync_kinematic_bodies(scene);
+            self.step();
+            self.accumulator -= self.timestep;
+            steps += 1;
+        }
+
+        self.write_transforms(scene, self.accumulator / self.timestep);
+    }
+
+    /// Advances the simulation by a single fixed step
+    pub fn step(&mut self) {
+        self.integration_parameters.dt = self.timestep;
+        self.pipeline.step(
+            &self.gravity,
+            &self.integration_parameters,
+            &mut self.islands,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            &mut self.ccd_solver,
+            Some(&mut self.query_pipeline),
+            &(),
+            &self.event_collector,
+        );
+
+        self.poses
+            .iter_mut()
+            .for_each(|(node_index, (previous, current))| {
+                *previous = *current;
+                if let Some(body) = self
+                    .node_bodies
+                    .get(node_index)
+                    .and_then(|handle| self.bodies.get(*handle))
+                {
+                    *current = *body.position();
+                }
+            });
+
+        let events = std::mem::take(
+            &mut *self
+                .event_collector
+                .0
+                .lock()
+                .expect("Failed to lock physics event collector!"),
+        );
+        self.collision_events
+            .extend(events.into_iter().filter_map(|event| {
+                let node_of = |handle| {
+                    self.colliders.get(handle).map(|collider| {
+                        petgraph::graph::NodeIndex::new(collider.user_data as usize)
+                    })
+                };
+                Some(CollisionEvent {
+                    nodes: (node_of(event.collider1())?, node_of(event.collider2())?),
+                    started: event.started(),
+                    is_trigger: event.sensor(),
+                })
+            }));
+    }
+
+    pub fn body(
+        &self,
+        node_index: petgraph::graph::NodeIndex,
+    ) -> Option<&rapier3d::dynamics::RigidBody> {
+        self.bodies.get(*self.node_bodies.get(&node_index)?)
+    }
+
+    pub fn body_mut(
+        &mut self,
+        node_index: petgraph::graph::NodeIndex,
+    ) -> Option<&mut rapier3d::dynamics::RigidBody> {
+        self.bodies.get_mut(*self.node_bodies.get(&node_index)?)
+    }
+
+    fn sync_kinematic_bodies(&mut self, scene: &crate::scene::Scene) {
+        self.node_bodies.iter().for_each(|(node_index, handle)| {
+            let Some(body) = self.bodies.get_mut(*handle) else {
+                return;
+            };
+            if body.is_kinematic() {
+                let (position, _scale) = decompose(&scene.graph.global_transform(*node_index));
+                body.set_next_kinematic_position(position);
+            }
+        });
+    }
+
+    fn write_transforms(&self, scene: &mut crate::scene::Scene, alpha: f32) {
+        self.node_bodies.iter().for_each(|(node_index, handle)| {
+            let Some(body) = self.bodies.get(*handle) else {
+                return;
+            };
+            if !body.is_dynamic() {
+                return;
+            }
+            let Some((previous, current)) = self.poses.get(node_index) else {
+                return;
+            };
+            let world_pose = previous.lerp_slerp(current, alpha.clamp(0.0, 1.0));
+
+            let (translation, rotation) = world_to_local(scene, *node_index, &world_pose);
+            let transform = &mut scene.graph[*node_index].transform;
+            transform.translation = translation;
+            transform.rotation = rotation;
+        });
+    }
+}
+
+#[derive(Default)]
+struct EventCollector(std::sync::Mutex<Vec<rapier3d::geometry::CollisionEvent>>);
+
+impl rapier3d::pipeline::EventHandler for EventCollector {
+    fn handle_collision_event(
+        &self,
+        _bodies: &rapier3d::dynamics::RigidBodySet,
+        _colliders: &rapier3d::geometry::ColliderSet,
+        event: rapier3d::geometry::CollisionEvent,
+        _contact_pair: Option<&rapier3d::geometry::ContactPair>,
+    ) {
+        self.0
+            .lock()
+            .expect("Failed to lock physics event collector!")
+            .push(event);
+    }
+
+    fn handle_contact_force_event(
+        &self,
+        _dt: f32,
+        _bodies: &rapier3d::dynamics::RigidBodySet,
+        _colliders: &rapier3d::geometry::ColliderSet,
+        _contact_pair: &rapier3d::geometry::ContactPair,
+        _total_force_magnitude: f32,
+    ) {
+    }
+}
+
+/// Converts a world space pose into a translation and rotation relative to the node's parent
+pub fn world_to_local(
+    scene: &crate::scene::Scene,
+    node_index: petgraph::graph::NodeIndex,
+    world_pose: &nalgebra::Isometry3<f32>,
+) -> (nalgebra_glm::Vec3, nalgebra_glm::Quat) {
+    let (parent_pose, parent_scale) = match scene
+        .graph
+        .neighbors_directed(node_index, petgraph::Direction::Incoming)
+        .next()
+    {
+        Some(parent_index) => decompose(&scene.graph.global_transform(parent_index)),
+        None => (
+            nalgebra::Isometry3::identity(),
+            nalgebra_glm::vec3(1.0, 1.0, 1.0),
+        ),
+    };
+    let local_pose = parent_pose.inverse() * world_pose;
+    (
+        local_pose.translation.vector.component_div(&parent_scale),
+        local_pose.rotation.into_inner(),
+    )
+}
+
+/// Splits a transform into a rigid pose and a scale, since physics bodies can't be scaled
+pub fn decompose(matrix: &nalgebra_glm::Mat4) -> (nalgebra::Isometry3<f32>, nalgebra_glm::Vec3) {
+    let column = |index: usize| {
+        nalgebra_glm::vec3(matrix[(0, index)], matrix[(1, index)], matrix[(2, index)])
+    };
+    let scale = nalgebra_glm::vec3(column(0).norm(), column(1).norm(), column(2).norm());
+    let rotation_matrix = nalgebra::Matrix3::from_columns(&[
+        column(0) / scale.x.max(f32::EPSILON),
+        column(1) / scale.y.max(f32::EPSILON),
+        column(2) / scale.z.max(f32::EPSILON),
+    ]);
+    let rotation = nalgebra::UnitQuaternion::from_matrix(&rotation_matrix);
+    let translation = nalgebra::Translation3::new(matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)]);
+    (
+        nalgebra::Isometry3::from_parts(translation, rotation),
+        scale,
+    )
+}
+
+/// Converts a collider shape to a physics shape, baking in the node's scale
+pub fn create_shape(
+    shape: &crate::scene::ColliderShape,
+    scale: &nalgebra_glm::Vec3,
+) -> Option<rapier3d::geometry::SharedShape> {
+    let scale_points = |points: &[nalgebra_glm::Vec3]| {
+        points
+            .iter()
+            .map(|point| nalgebra::Point3::from(point.component_mul(scale)))
+            .collect::<Vec<_>>()
+    };
+    match shape {
+        crate::scene::ColliderShape::Sphere { radius } => {
+            Some(rapier3d::geometry::SharedShape::ball(radius * scale.max()))
+        }
+        crate::scene::ColliderShape::Box {
+            half_extents,
+            center,
+        } => {
+            let half_extents = half_extents.component_mul(scale);
+            let cuboid = rapier3d::geometry::SharedShape::cuboid(
+                half_extents.x,
+                half_extents.y,
+                half_extents.z,
+            );
+            if *center == nalgebra_glm::Vec3::zeros() {
+                return Some(cuboid);
+            }
+            // Offset boxes are wrapped in a compound shape, since colliders are placed at the node's origin
+            let offset = nalgebra::Isometry3::translation(
+                center.x * scale.x,
+                center.y * scale.y,
+                center.z * scale.z,
+            );
+            Some(rapier3d::geometry::SharedShape::compound(vec![(
+                offset, cuboid,
+            )]))
+        }
+        crate::scene::ColliderShape::Capsule {
+            half_height,
+            radius,
+        } => Some(rapier3d::geometry::SharedShape::capsule_y(
+            half_height * scale.y,
+            radius * scale.x.max(scale.z),
+        )),
+        crate::scene::ColliderShape::ConvexHull { points, indices } => {
+            rapier3d::geometry::SharedShape::convex_mesh(scale_points(points), indices)
+        }
+        crate::scene::ColliderShape::TriangleMesh { vertices, indices } => {
+            if indices.is_empty() {
+                return None;
+            }
+            Some(rapier3d::geometry::SharedShape::trimesh(
+                scale_points(vertices),
+                indices.clone(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn dynamic_body_lands_on_static_collider() {
+        let mut scene = crate::scene::Scene::default();
+        scene.graph.add_node(crate::scene::Node {
+            label: "Root".to_string(),
+            ..Default::default()
+        });
+        scene.add_root_node(crate::scene::Node {
+            label: "Ground".to_string(),
+            components: vec![crate::scene::NodeComponent::Collider(
+                crate::scene::Collider {
+                    shape: crate::scene::ColliderShape::Box {
+                        half_extents: nalgebra_glm::vec3(10.0, 0.5, 10.0),
+                        center: nalgebra_glm::Vec3::zeros(),
+                    },
+                    ..Default::default()
+                },
+            )],
+            ..Default::default()
+        });
+        let ball = scene.add_root_node(crate::scene::Node {
+            label: "Ball".to_string(),
+            transform: crate::scene::Transform {
+                translation: nalgebra_glm::vec3(0.0, 5.0, 0.0),
+                ..Default::default()
+            },
+            components: vec![
+                crate::scene::NodeComponent::RigidBody(crate::scene::RigidBody::default()),
+                crate::scene::NodeComponent::Collider(crate::scene::Collider::default()),
+            ],
+            ..Default::default()
+        });
+
+        let mut physics = crate::physics::PhysicsWorld::from_scene(&scene);
+        let mut started_touching = false;
+        for _ in 0..300 {
+            physics.update(&mut scene, 1.0 / 60.0);
+            started_touching |= physics
+                .collision_events
+                .iter()
+                .any(|event| event.started && !event.is_trigger);
+        }
+
+        // The ball's radius is 0.5 and the ground's top is at 0.5
+        let height = scene.graph[ball].transform.translation.y;
+        assert!((height - 1.0).abs() < 0.05, "Ball came to rest at {height}");
+        assert!(started_touching);
+    }
+}
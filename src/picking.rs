@@ -0,0 +1,568 @@
+#[derive(Debug, Copy, Clone)]
+pub struct RaycastHit {
+    pub node_index: petgraph::graph::NodeIndex,
+    pub primitive_index: usize,
+    pub triangle_index: usize,
+    pub distance: f32,
+    pub position: nalgebra_glm::Vec3,
+    /// Weights of the hit triangle's three vertices at the hit position
+    pub barycentrics: nalgebra_glm::Vec3,
+}
+
+/// Creates a world space ray from the active camera through the mouse cursor
+pub fn mouse_ray(
+    scene: &crate::scene::Scene,
+    mouse: &crate::io::Mouse,
+    viewport_size: nalgebra_glm::Vec2,
+) -> Option<crate::geometry::Ray> {
+    let aspect_ratio = viewport_size.x / viewport_size.y.max(1.0);
+    let (_camera_position, projection, view) =
+        crate::view::create_camera_matrices(scene, aspect_ratio)?;
+    crate::geometry::Ray::from_screen(mouse.position, viewport_size, &projection, &view)
+}
+
+/// Finds the nearest mesh primitive triangle under the mouse cursor
+pub fn pick(
+    scene: &crate::scene::Scene,
+    mouse: &crate::io::Mouse,
+    viewport_size: nalgebra_glm::Vec2,
+) -> Option<RaycastHit> {
+    raycast(scene, &mouse_ray(scene, mouse, viewport_size)?)
+}
+
+/// Casts a world space ray against the triangles of every mesh in the scene,
+/// returning the nearest hit
+pub fn raycast(scene: &crate::scene::Scene, ray: &crate::geometry::Ray) -> Option<RaycastHit> {
+    let mut nearest_hit: Option<RaycastHit> = None;
+    scene.walk_dfs(|node, node_index| {
+        let mut global_transform = None;
+        node.components.iter().for_each(|component| {
+            let crate::scene::NodeComponent::Mesh(mesh_id) = component else {
+                return;
+            };
+            let Some(mesh) = scene.meshes.get(mesh_id) else {
+                return;
+            };
+            let global_transform =
+                *global_transform.get_or_insert_with(|| scene.graph.global_transform(node_index));
+            mesh.primitives
+                .iter()
+                .enumerate()
+                .for_each(|(primitive_index, primitive)| {
+                    let nearest_distance = nearest_hit.map(|hit| hit.distance).unwrap_or(f32::MAX);
+
+                    // Broadphase against the primitive's world space bounds
+                    let world_bounds = primitive.bounding_box().transform(&global_transform);
+                    match world_bounds.intersect_ray(ray) {
+                        Some(distance) if distance <= nearest_distance => {}
+                        _ => return,
+                    }
+
+                    if let Some((triangle_index, hit)) =
+                        raycast_primitive(primitive, &global_transform, ray, nearest_distance)
+                    {
+                        nearest_hit = Some(RaycastHit {
+                            node_index,
+                            primitive_index,
+                            triangle_index,
+                            distance: hit.distance,
+                            position: ray.at(hit.distance),
+                            barycentrics: hit.barycentrics,
+                        });
+                    }
+                });
+        });
+    });
+    nearest_hit
+}
+
+pub(crate) fn raycast_primitive(
+    primitive: &crate::scene::Primitive,
+    global_transform: &nalgebra_glm::Mat4,
+    ray: &crate::geometry::Ray,
+    max_distance: f32,
+) -> Option<(usize, crate::geometry::TriangleHit)> {
+    let world_positions = primitive
+        .vertices
+        .iter()
+        .map(|vertex| {
+            nalgebra_glm::vec4_to_vec3(
+                &(global_transform
+                    * nalgebra_glm::vec4(
+                        vertex.position.x,
+                        vertex.position.y,
+                        vertex.position.z,
+                        1.0,
+                    )),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut nearest_hit = None;
+    let mut nearest_distance = max_distance;
+    primitive
+        .triangles()
+        .iter()
+        .enumerate()
+        .for_each(|(triangle_index, triangle)| {
+            let [Some(a), Some(b), Some(c)] =
+                triangle.map(|index| world_positions.get(index as usize))
+            else {
+                return;
+            };
+            let Some(hit) = crate::geometry::intersect_ray_triangle(ray, a, b, c) else {
+                return;
+            };
+            if hit.distance < nearest_distance {
+                nearest_distance = hit.distance;
+                nearest_hit = Some((triangle_index, hit));
+            }
+        });
+    nearest_hit
+}
+
+/// The id written to the id buffer for a node, where zero means nothing was drawn
+pub fn node_id(node_index: petgraph::graph::NodeIndex) -> u32 {
+    node_index.index() as u32 + 1
+}
+
+/// The node an id read back from the id buffer belongs to
+pub fn node_index_from_id(node_id: u32) -> Option<petgraph::graph::NodeIndex> {
+    node_id
+        .checked_sub(1)
+        .map(|index| petgraph::graph::NodeIndex::new(index as usize))
+}
+
+/// The result of picking a pixel of the id buffer
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GpuPick {
+    /// The pixel that was picked, in physical pixels with the origin at the top left
+    pub x: u32,
+    pub y: u32,
+    /// The node drawn at the pixel, or `None` if nothing was drawn there
+    pub node_index: Option<petgraph::graph::NodeIndex>,
+}
+
+/// A readback of a picked pixel that hasn't been mapped yet
+struct PendingPick {
+    x: u32,
+    y: u32,
+    receiver: std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+}
+
+/// Renders the id of the node drawn at each pixel into an `R32Uint` target,
+/// and reads back single pixels of it without stalling the frame.
+/// The id pass is only rendered in frames a pick was requested in.
+/// Masked primitives are cut out like they are when shaded and blended primitives can't be picked.
+pub struct IdBuffer {
+    id_texture: wgpu::Texture,
+    id_texture_view: wgpu::TextureView,
+    /// Picking isn't multisampled, so the id pass has its own depth target
+    depth_texture_view: wgpu::TextureView,
+    readback_buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    requested: Option<(u32, u32)>,
+    pending: Option<PendingPick>,
+    latest: Option<GpuPick>,
+}
+
+impl IdBuffer {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+    pub fn new(gpu: &crate::gpu::Gpu, width: u32, height: u32) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        let (id_texture, id_texture_view, depth_texture_view) =
+            create_id_targets(gpu, width, height);
+        let readback_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Id Readback Buffer"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Self {
+            id_texture,
+            id_texture_view,
+            depth_texture_view,
+            readback_buffer,
+            width,
+            height,
+            requested: None,
+            pending: None,
+            latest: None,
+        }
+    }
+
+    pub fn resize(&mut self, gpu: &crate::gpu::Gpu, width: u32, height: u32) {
+        let (width, height) = (width.max(1), height.max(1));
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+        (
+            self.id_texture,
+            self.id_texture_view,
+            self.depth_texture_view,
+        ) = create_id_targets(gpu, width, height);
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Requests the pixel to be read back after the next id pass,
+    /// returning the latest pick that finished reading back, which may be a few frames old
+    pub fn pick(&mut self, gpu: &crate::gpu::Gpu, x: u32, y: u32) -> Option<GpuPick> {
+        self.poll(gpu);
+        if x < self.width && y < self.height {
+            self.requested = Some((x, y));
+        }
+        self.latest
+    }
+
+    /// The latest pick that finished reading back
+    pub fn latest(&self) -> Option<GpuPick> {
+        self.latest
+    }
+
+    /// The pixel the next id pass reads back
+    pub fn requested(&self) -> Option<(u32, u32)> {
+        self.requested
+    }
+
+    /// Renders the prepared view's ids, into a viewport of the id buffer if one is given,
+    /// and copies the requested pixel for readback.
+    /// Does nothing if no pick was requested or the previous readback is still in flight.
+    pub fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        gpu: &crate::gpu::Gpu,
+        view: &crate::view::View,
+        viewport: Option<crate::view::PixelRect>,
+    ) -> bool {
+        if self.pending.is_some() {
+            return false;
+        }
+        let Some((x, y)) = self.requested else {
+            return false;
+        };
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Id Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.id_texture_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            if let Some(viewport) = viewport {
+                viewport.apply(&mut render_pass);
+            }
+            render_pass.set_bind_group(0, &view.uniform_bind_group, &[]);
+            view.materials.bind_all(&mut render_pass, 2);
+            render_pass.set_vertex_buffer(0, view.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(view.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            view.draws
+                .iter()
+                .enumerate()
+                .filter(|(_, draw)| draw.alpha_mode != crate::scene::AlphaMode::Blend)
+                .for_each(|(draw_index, draw)| {
+                    render_pass.set_pipeline(&view.pass_pipelines.id[draw.pass_variant()]);
+                    let offset = (draw_index as u64 * gpu.alignment()) as wgpu::DynamicOffset;
+                    render_pass.set_bind_group(1, &view.dynamic_uniform_bind_group, &[offset]);
+                    view.materials
+                        .bind(&mut render_pass, 2, draw.material_index);
+                    render_pass.draw_indexed(draw.indices.clone(), draw.base_vertex, 0..1);
+                });
+        }
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.id_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: Some(1),
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        true
+    }
+
+    /// Starts mapping the pixel copied by `render`, which must be called after the copy was submitted
+    pub fn begin_readback(&mut self) {
+        let Some((x, y)) = self.requested.take() else {
+            return;
+        };
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.pending = Some(PendingPick { x, y, receiver });
+    }
+
+    /// Finishes the pending readback if the buffer has been mapped
+    pub fn poll(&mut self, gpu: &crate::gpu::Gpu) {
+        let Some(pending) = self.pending.as_ref() else {
+            return;
+        };
+        gpu.device.poll(wgpu::Maintain::Poll);
+        let result = match pending.receiver.try_recv() {
+            Ok(result) => result,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => Err(wgpu::BufferAsyncError),
+        };
+        let PendingPick { x, y, .. } = self.pending.take().expect("A pick is pending!");
+        if result.is_err() {
+            log::warn!("Failed to read back the picked pixel");
+            return;
+        }
+        let node_id = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            bytemuck::pod_read_unaligned::<u32>(&data[..std::mem::size_of::<u32>()])
+        };
+        self.readback_buffer.unmap();
+        self.latest = Some(GpuPick {
+            x,
+            y,
+            node_index: node_index_from_id(node_id),
+        });
+    }
+}
+
+fn create_id_targets(
+    gpu: &crate::gpu::Gpu,
+    width: u32,
+    height: u32,
+) -> (wgpu::Texture, wgpu::TextureView, wgpu::TextureView) {
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let id_texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Id Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: IdBuffer::FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let id_texture_view = id_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let depth_texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Id Depth Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: crate::gpu::Gpu::DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    let depth_texture_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (id_texture, id_texture_view, depth_texture_view)
+}
+
+/// Creates an id pipeline for each pass variant
+pub fn create_id_pipelines(
+    gpu: &crate::gpu::Gpu,
+    context: &crate::view::PassContext,
+) -> Vec<wgpu::RenderPipeline> {
+    let pipeline_layout = gpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Id Pipeline Layout"),
+            bind_group_layouts: &[
+                context.uniform_bind_group_layout,
+                context.dynamic_uniform_bind_group_layout,
+                &context.materials.bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+    crate::view::pass_variants()
+        .iter()
+        .map(|variant| {
+            let shader_module = crate::view::create_pass_shader_module(
+                gpu,
+                "Id Shader",
+                ID_SHADER_SOURCE,
+                variant,
+                context,
+            );
+            gpu.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Id Pipeline"),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader_module,
+                        entry_point: "vertex_main",
+                        buffers: &[crate::scene::Vertex::description(
+                            &crate::scene::Vertex::attributes(),
+                        )],
+                    },
+                    primitive: wgpu::PrimitiveState {
+                        front_face: wgpu::FrontFace::Ccw,
+                        cull_mode: (!variant.contains_key("DOUBLE_SIDED"))
+                            .then_some(wgpu::Face::Back),
+                        ..Default::default()
+                    },
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: crate::gpu::Gpu::DEPTH_FORMAT,
+                        depth_write_enabled: true,
+                        depth_compare: wgpu::CompareFunction::Less,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState::default(),
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader_module,
+                        entry_point: "fragment_main",
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: IdBuffer::FORMAT,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    multiview: None,
+                })
+        })
+        .collect()
+}
+
+/// Writes the id of each drawn node, drawn with the view's vertex stage
+pub const ID_SHADER_SOURCE: &str = "
+#include \"view.wgsl\"
+#include \"material.wgsl\"
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) u32 {
+#ifdef ALPHA_MASK
+    if is_masked_out(materials[mesh_ubo.material_index], in.uv_0) {
+        discard;
+    }
+#endif
+    return mesh_ubo.node_id;
+}
+";
+
+#[cfg(test)]
+mod tests {
+    /// Renders the id buffer and reads back the node under a pixel
+    fn read_id(
+        renderer: &crate::render::HeadlessRenderer,
+        x: u32,
+        y: u32,
+        viewport: Option<crate::view::PixelRect>,
+    ) -> Option<petgraph::graph::NodeIndex> {
+        let mut id_buffer = super::IdBuffer::new(&renderer.gpu, 64, 64);
+        assert_eq!(id_buffer.pick(&renderer.gpu, x, y), None);
+        let mut encoder = renderer
+            .gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        assert!(id_buffer.render(&mut encoder, &renderer.gpu, &renderer.view, viewport));
+        renderer.gpu.queue.submit(std::iter::once(encoder.finish()));
+        id_buffer.begin_readback();
+        renderer.gpu.device.poll(wgpu::Maintain::Wait);
+        id_buffer.poll(&renderer.gpu);
+        let pick = id_buffer.latest().expect("The pick wasn't read back!");
+        assert_eq!((pick.x, pick.y), (x, y));
+        pick.node_index
+    }
+
+    /// The node holding the helmet's mesh
+    fn helmet_node(scene: &crate::scene::Scene) -> petgraph::graph::NodeIndex {
+        scene
+            .graph
+            .node_indices()
+            .find(|node_index| {
+                scene.graph[*node_index]
+                    .components
+                    .iter()
+                    .any(|component| matches!(component, crate::scene::NodeComponent::Mesh(_)))
+            })
+            .expect("The helmet should have a mesh node!")
+    }
+
+    #[test]
+    fn id_buffer_picks_nodes() {
+        let Some(crate::render::HelmetFixture {
+            renderer, scene, ..
+        }) = crate::render::HelmetFixture::new()
+        else {
+            return;
+        };
+
+        assert_eq!(read_id(&renderer, 32, 32, None), Some(helmet_node(&scene)));
+        assert_eq!(read_id(&renderer, 0, 0, None), None);
+    }
+
+    #[test]
+    fn views_are_picked_inside_their_viewport() {
+        let Some(crate::render::HelmetFixture {
+            renderer, scene, ..
+        }) = crate::render::HelmetFixture::new()
+        else {
+            return;
+        };
+
+        // The view drawn into the top right quarter shows the helmet at that quarter's center
+        let viewport = crate::view::PixelRect {
+            x: 32,
+            y: 0,
+            width: 32,
+            height: 32,
+        };
+        assert_eq!(
+            read_id(&renderer, 48, 16, Some(viewport)),
+            Some(helmet_node(&scene))
+        );
+        assert_eq!(read_id(&renderer, 32, 32, Some(viewport)), None);
+    }
+
+    #[test]
+    fn cut_out_and_blended_primitives_are_not_picked() {
+        let Some(mut fixture) = crate::render::HelmetFixture::new() else {
+            return;
+        };
+        [
+            (crate::scene::AlphaMode::Mask, 2.0),
+            (crate::scene::AlphaMode::Blend, 0.5),
+        ]
+        .into_iter()
+        .for_each(|(alpha_mode, alpha_cutoff)| {
+            fixture.scene.materials.values_mut().for_each(|material| {
+                material.alpha_mode = alpha_mode;
+                material.alpha_cutoff = alpha_cutoff;
+            });
+            fixture.reimport_and_render();
+            assert_eq!(
+                read_id(&fixture.renderer, 32, 32, None),
+                None,
+                "{alpha_mode:?} primitives were picked"
+            );
+        });
+    }
+}
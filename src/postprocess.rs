@@ -0,0 +1,1135 @@
+/// The format scenes are rendered in before post processing maps them to the output
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// The maximum number of effects in a post process chain
+pub const MAX_EFFECTS: usize = 16;
+
+/// The width, height and depth of the identity color grading lut
+const IDENTITY_LUT_SIZE: u32 = 16;
+
+/// The number of successively halved textures bloom blurs through
+const MAX_BLOOM_MIPS: u32 = 6;
+
+/// Every fragment entry point in the post process shader
+const ENTRY_POINTS: [&str; 9] = [
+    "copy_main",
+    "tone_mapping_main",
+    "color_grading_main",
+    "vignette_main",
+    "fxaa_main",
+    "bloom_prefilter_main",
+    "bloom_downsample_main",
+    "bloom_upsample_main",
+    "bloom_composite_main",
+];
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ToneMapping {
+    /// Clamps colors to the displayable range
+    None,
+    Reinhard,
+    /// The filmic curve of the Academy Color Encoding System, as fit by Krzysztof Narkowicz
+    #[default]
+    Aces,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum PostEffect {
+    /// Spreads light brighter than the threshold to its surroundings, which expects hdr input.
+    /// The knee softens the threshold so bloom fades in rather than cutting off.
+    Bloom {
+        threshold: f32,
+        knee: f32,
+        intensity: f32,
+    },
+    /// Maps hdr colors into the displayable range.
+    /// Gamma is applied on top of the sRGB encoding of the output, so 1.0 leaves colors unchanged.
+    ToneMapping {
+        operator: ToneMapping,
+        exposure: f32,
+        gamma: f32,
+    },
+    /// Blends colors toward their entry in the color grading lut
+    ColorGrading { intensity: f32 },
+    /// Darkens the edges of the image, starting at a radius from the center
+    Vignette {
+        intensity: f32,
+        radius: f32,
+        smoothness: f32,
+    },
+    /// Fast approximate anti-aliasing, which expects tone mapped input
+    Fxaa,
+}
+
+impl PostEffect {
+    pub const ALL: [PostEffect; 5] = [
+        PostEffect::Bloom {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.05,
+        },
+        PostEffect::ToneMapping {
+            operator: ToneMapping::Aces,
+            exposure: 1.0,
+            gamma: 1.0,
+        },
+        PostEffect::ColorGrading { intensity: 1.0 },
+        PostEffect::Vignette {
+            intensity: 0.5,
+            radius: 0.75,
+            smoothness: 0.5,
+        },
+        PostEffect::Fxaa,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostEffect::Bloom { .. } => "Bloom",
+            PostEffect::ToneMapping { .. } => "Tone Mapping",
+            PostEffect::ColorGrading { .. } => "Color Grading",
+            PostEffect::Vignette { .. } => "Vignette",
+            PostEffect::Fxaa => "FXAA",
+        }
+    }
+
+    fn entry_point(&self) -> &'static str {
+        match self {
+            PostEffect::Bloom { .. } => "bloom_composite_main",
+            PostEffect::ToneMapping { .. } => "tone_mapping_main",
+            PostEffect::ColorGrading { .. } => "color_grading_main",
+            PostEffect::Vignette { .. } => "vignette_main",
+            PostEffect::Fxaa => "fxaa_main",
+        }
+    }
+
+    fn uniform(&self, width: u32, height: u32) -> PostUniform {
+        let mut uniform = PostUniform {
+            texel_size: nalgebra_glm::vec2(1.0 / width as f32, 1.0 / height as f32),
+            ..Default::default()
+        };
+        match *self {
+            PostEffect::Bloom {
+                threshold,
+                knee,
+                intensity,
+            } => {
+                uniform.threshold = threshold;
+                uniform.knee = knee;
+                uniform.intensity = intensity;
+            }
+            PostEffect::ToneMapping {
+                operator,
+                exposure,
+                gamma,
+            } => {
+                uniform.tone_mapping_operator = operator as u32;
+                uniform.exposure = exposure;
+                uniform.gamma = gamma.max(0.01);
+            }
+            PostEffect::ColorGrading { intensity } => uniform.intensity = intensity,
+            PostEffect::Vignette {
+                intensity,
+                radius,
+                smoothness,
+            } => {
+                uniform.intensity = intensity;
+                uniform.radius = radius;
+                uniform.smoothness = smoothness;
+            }
+            PostEffect::Fxaa => {}
+        }
+        uniform
+    }
+}
+
+/// Tone mapping followed by anti-aliasing
+pub fn default_effects() -> Vec<PostEffect> {
+    vec![PostEffect::ALL[1], PostEffect::Fxaa]
+}
+
+#[repr(C, align(256))]
+#[derive(Default, Copy, Clone, Debug, bytemuck::Zeroable)]
+struct PostUniform {
+    texel_size: nalgebra_glm::Vec2,
+    exposure: f32,
+    gamma: f32,
+    tone_mapping_operator: u32,
+    intensity: f32,
+    radius: f32,
+    smoothness: f32,
+    threshold: f32,
+    knee: f32,
+}
+
+/// A color texture that can be rendered into and sampled
+struct RenderTarget {
+    view: wgpu::TextureView,
+}
+
+impl RenderTarget {
+    fn new(gpu: &crate::gpu::Gpu, width: u32, height: u32, label: &str) -> Self {
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        Self {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+        }
+    }
+}
+
+/// The size independent parts of post processing, which every view and render target shares
+pub struct PostProcessResources {
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// Pipelines for each effect writing to intermediate targets and to the output,
+    /// where output pipelines use the gpu's sample count
+    pipelines: std::collections::HashMap<(&'static str, wgpu::TextureFormat), wgpu::RenderPipeline>,
+}
+
+impl PostProcessResources {
+    pub fn new(gpu: &crate::gpu::Gpu) -> Self {
+        let sampler = gpu.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Process Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group_layout = create_bind_group_layout(gpu);
+        let shader_module = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Post Process Shader"),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(SHADER_SOURCE)),
+            });
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Post Process Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipelines = ENTRY_POINTS
+            .into_iter()
+            .flat_map(|entry_point| {
+                [HDR_FORMAT, gpu.surface_format].map(|format| {
+                    (
+                        (entry_point, format),
+                        create_pipeline(gpu, &pipeline_layout, &shader_module, entry_point, format),
+                    )
+                })
+            })
+            .collect();
+        Self {
+            sampler,
+            bind_group_layout,
+            pipelines,
+        }
+    }
+}
+
+/// Owns the hdr target scenes are rendered into,
+/// and runs a chain of fullscreen passes that map it to the output.
+///
+/// Each effect reads the previous one's result, ping ponging between two intermediate targets,
+/// and the last effect writes to the output. An empty chain copies the hdr target to the output.
+pub struct PostProcess {
+    pub effects: Vec<PostEffect>,
+    width: u32,
+    height: u32,
+    resources: std::sync::Arc<PostProcessResources>,
+    hdr_target: RenderTarget,
+    /// The multisampled target scenes are rendered into when msaa is enabled,
+    /// which resolves into the hdr target
+    msaa_hdr_view: Option<wgpu::TextureView>,
+    pub depth_texture_view: wgpu::TextureView,
+    intermediate_targets: [RenderTarget; 2],
+    color_grading_lut: wgpu::TextureView,
+    uniform_buffer: wgpu::Buffer,
+    /// Bind groups reading the hdr target and each intermediate target
+    bind_groups: [wgpu::BindGroup; 3],
+    /// Each level of the bloom mip chain, starting at half resolution
+    bloom_mips: Vec<wgpu::TextureView>,
+    bloom_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl PostProcess {
+    pub fn new(
+        gpu: &crate::gpu::Gpu,
+        resources: std::sync::Arc<PostProcessResources>,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        let uniform_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Post Process Uniform Buffer"),
+            size: MAX_EFFECTS as wgpu::BufferAddress * gpu.alignment(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let color_grading_lut =
+            create_lut(gpu, IDENTITY_LUT_SIZE, &identity_lut(IDENTITY_LUT_SIZE));
+
+        let hdr_target = RenderTarget::new(gpu, width, height, "HDR Target");
+        let intermediate_targets = [
+            RenderTarget::new(gpu, width, height, "Post Process Target"),
+            RenderTarget::new(gpu, width, height, "Post Process Target"),
+        ];
+        let bind_group = |input| {
+            create_bind_group(
+                gpu,
+                &resources.bind_group_layout,
+                input,
+                &resources.sampler,
+                &uniform_buffer,
+                &color_grading_lut,
+            )
+        };
+        let bind_groups = [
+            &hdr_target.view,
+            &intermediate_targets[0].view,
+            &intermediate_targets[1].view,
+        ]
+        .map(bind_group);
+        let bloom_mips = create_bloom_mips(gpu, width, height);
+        let bloom_bind_groups = bloom_mips.iter().map(bind_group).collect();
+
+        Self {
+            effects: default_effects(),
+            width,
+            height,
+            resources,
+            hdr_target,
+            msaa_hdr_view: gpu.create_msaa_texture(width, height, HDR_FORMAT),
+            depth_texture_view: gpu.create_depth_texture(width, height),
+            intermediate_targets,
+            color_grading_lut,
+            uniform_buffer,
+            bind_groups,
+            bloom_mips,
+            bloom_bind_groups,
+        }
+    }
+
+    /// The resolved hdr target that post processing reads
+    pub fn hdr_view(&self) -> &wgpu::TextureView {
+        &self.hdr_target.view
+    }
+
+    /// The view scenes are rendered into and the view it resolves into, if msaa is enabled
+    pub fn scene_color_attachment(&self) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        match self.msaa_hdr_view.as_ref() {
+            Some(msaa_hdr_view) => (msaa_hdr_view, Some(&self.hdr_target.view)),
+            None => (&self.hdr_target.view, None),
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Recreates the targets if the size changed
+    pub fn resize(&mut self, gpu: &crate::gpu::Gpu, width: u32, height: u32) {
+        let (width, height) = (width.max(1), height.max(1));
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+        self.width = width;
+        self.height = height;
+        self.hdr_target = RenderTarget::new(gpu, width, height, "HDR Target");
+        self.msaa_hdr_view = gpu.create_msaa_texture(width, height, HDR_FORMAT);
+        self.depth_texture_view = gpu.create_depth_texture(width, height);
+        self.intermediate_targets = [
+            RenderTarget::new(gpu, width, height, "Post Process Target"),
+            RenderTarget::new(gpu, width, height, "Post Process Target"),
+        ];
+        self.bloom_mips = create_bloom_mips(gpu, width, height);
+        self.recreate_bind_groups(gpu);
+    }
+
+    /// Replaces the color grading lut with one read from a strip of slices,
+    /// where the image is `size * size` texels wide and `size` texels high,
+    /// red increases within each slice, green increases downward and blue increases per slice.
+    /// Returns false if the image isn't a lut strip.
+    pub fn set_color_grading_lut(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        strip: &image::RgbaImage,
+    ) -> bool {
+        let size = strip.height();
+        if size < 2 || strip.width() != size * size {
+            return false;
+        }
+        let mut texels = Vec::with_capacity((size * size * size * 4) as usize);
+        (0..size).for_each(|blue| {
+            (0..size).for_each(|green| {
+                (0..size).for_each(|red| {
+                    texels.extend_from_slice(&strip.get_pixel(blue * size + red, green).0);
+                });
+            });
+        });
+        self.color_grading_lut = create_lut(gpu, size, &texels);
+        self.recreate_bind_groups(gpu);
+        true
+    }
+
+    /// The effects to view a scene through a camera with,
+    /// which are the camera's own if it overrides the chain
+    pub fn effects_for_camera<'a>(
+        &'a self,
+        scene: &'a crate::scene::Scene,
+        camera: Option<petgraph::graph::NodeIndex>,
+    ) -> &'a [PostEffect] {
+        camera
+            .and_then(|camera| scene.graph.node_weight(camera))
+            .and_then(|node| {
+                node.components
+                    .iter()
+                    .find_map(|component| match component {
+                        crate::scene::NodeComponent::Camera(camera) => {
+                            camera.post_effects.as_deref()
+                        }
+                        _ => None,
+                    })
+            })
+            .unwrap_or(&self.effects)
+    }
+
+    /// Runs an effect chain on the hdr target, writing the result to the output view,
+    /// which must have the gpu's surface format and sample count.
+    /// Multisampled outputs are resolved into the resolve target.
+    /// Given a viewport, the result is written into that region of the output
+    /// and the rest of it is kept instead of cleared.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        gpu: &crate::gpu::Gpu,
+        effects: &[PostEffect],
+        output_view: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        viewport: Option<crate::view::PixelRect>,
+    ) {
+        let effects = &effects[..effects.len().min(MAX_EFFECTS)];
+
+        let mut uniforms = vec![PostUniform::default(); MAX_EFFECTS];
+        effects.iter().enumerate().for_each(|(index, effect)| {
+            uniforms[index] = effect.uniform(self.width, self.height);
+        });
+        gpu.queue.write_buffer(&self.uniform_buffer, 0, unsafe {
+            std::slice::from_raw_parts(
+                uniforms.as_ptr() as *const u8,
+                uniforms.len() * gpu.alignment() as usize,
+            )
+        });
+
+        if effects.is_empty() {
+            let pass = Pass {
+                entry_point: "copy_main",
+                input: &self.bind_groups[0],
+                uniform_index: 0,
+                target_view: output_view,
+                resolve_target,
+                format: gpu.surface_format,
+                clear: viewport.is_none(),
+                viewport,
+            };
+            self.render_pass(encoder, gpu, pass);
+            return;
+        }
+        effects.iter().enumerate().for_each(|(index, effect)| {
+            let (target_view, resolve_target, format, pass_viewport) = if index + 1 == effects.len()
+            {
+                (output_view, resolve_target, gpu.surface_format, viewport)
+            } else {
+                (
+                    &self.intermediate_targets[index % 2].view,
+                    None,
+                    HDR_FORMAT,
+                    None,
+                )
+            };
+            // The first effect reads the hdr target, later ones read what the previous one wrote
+            let input = &self.bind_groups[if index == 0 { 0 } else { 1 + (index - 1) % 2 }];
+            let pass = Pass {
+                entry_point: effect.entry_point(),
+                input,
+                uniform_index: index,
+                target_view,
+                resolve_target,
+                format,
+                clear: pass_viewport.is_none(),
+                viewport: pass_viewport,
+            };
+            if let PostEffect::Bloom { .. } = effect {
+                self.render_bloom(encoder, gpu, pass);
+            } else {
+                self.render_pass(encoder, gpu, pass);
+            }
+        });
+    }
+
+    /// Thresholds the input into a chain of downsampled mips, then blurs it back up
+    /// by adding each mip's upsampled successor, and finally adds the result to a copy of the input
+    fn render_bloom(&self, encoder: &mut wgpu::CommandEncoder, gpu: &crate::gpu::Gpu, pass: Pass) {
+        let mip_pass = |entry_point, input, target_view, clear| Pass {
+            entry_point,
+            input,
+            uniform_index: pass.uniform_index,
+            target_view,
+            resolve_target: None,
+            format: HDR_FORMAT,
+            clear,
+            viewport: None,
+        };
+        self.render_pass(
+            encoder,
+            gpu,
+            mip_pass(
+                "bloom_prefilter_main",
+                pass.input,
+                &self.bloom_mips[0],
+                true,
+            ),
+        );
+        (1..self.bloom_mips.len()).for_each(|mip| {
+            self.render_pass(
+                encoder,
+                gpu,
+                mip_pass(
+                    "bloom_downsample_main",
+                    &self.bloom_bind_groups[mip - 1],
+                    &self.bloom_mips[mip],
+                    true,
+                ),
+            );
+        });
+        (1..self.bloom_mips.len()).rev().for_each(|mip| {
+            self.render_pass(
+                encoder,
+                gpu,
+                mip_pass(
+                    "bloom_upsample_main",
+                    &self.bloom_bind_groups[mip],
+                    &self.bloom_mips[mip - 1],
+                    false,
+                ),
+            );
+        });
+        self.render_pass(
+            encoder,
+            gpu,
+            Pass {
+                entry_point: "copy_main",
+                ..pass
+            },
+        );
+        self.render_pass(
+            encoder,
+            gpu,
+            Pass {
+                input: &self.bloom_bind_groups[0],
+                clear: false,
+                ..pass
+            },
+        );
+    }
+
+    fn render_pass(&self, encoder: &mut wgpu::CommandEncoder, gpu: &crate::gpu::Gpu, pass: Pass) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Post Process Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: pass.target_view,
+                resolve_target: pass.resolve_target,
+                ops: wgpu::Operations {
+                    load: if pass.clear {
+                        wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        if let Some(viewport) = pass.viewport {
+            viewport.apply(&mut render_pass);
+        }
+        render_pass.set_pipeline(&self.resources.pipelines[&(pass.entry_point, pass.format)]);
+        render_pass.set_bind_group(
+            0,
+            pass.input,
+            &[(pass.uniform_index as u64 * gpu.alignment()) as wgpu::DynamicOffset],
+        );
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn recreate_bind_groups(&mut self, gpu: &crate::gpu::Gpu) {
+        let bind_group = |input| {
+            create_bind_group(
+                gpu,
+                &self.resources.bind_group_layout,
+                input,
+                &self.resources.sampler,
+                &self.uniform_buffer,
+                &self.color_grading_lut,
+            )
+        };
+        self.bind_groups = [
+            &self.hdr_target.view,
+            &self.intermediate_targets[0].view,
+            &self.intermediate_targets[1].view,
+        ]
+        .map(bind_group);
+        self.bloom_bind_groups = self.bloom_mips.iter().map(bind_group).collect();
+    }
+}
+
+/// A single fullscreen pass of an effect
+#[derive(Clone, Copy)]
+struct Pass<'a> {
+    entry_point: &'static str,
+    input: &'a wgpu::BindGroup,
+    uniform_index: usize,
+    target_view: &'a wgpu::TextureView,
+    resolve_target: Option<&'a wgpu::TextureView>,
+    format: wgpu::TextureFormat,
+    /// Passes that don't clear their target blend onto it
+    clear: bool,
+    /// The region of the target drawn into, or all of it
+    viewport: Option<crate::view::PixelRect>,
+}
+
+/// Views of each mip of the bloom texture, halving down to at most a few texels
+fn create_bloom_mips(gpu: &crate::gpu::Gpu, width: u32, height: u32) -> Vec<wgpu::TextureView> {
+    let (width, height) = ((width / 2).max(1), (height / 2).max(1));
+    let mip_level_count = crate::texture::mip_level_count(width, height).min(MAX_BLOOM_MIPS);
+    let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Bloom Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HDR_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    (0..mip_level_count)
+        .map(|mip| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Bloom Mip"),
+                base_mip_level: mip,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// A lut mapping every color to itself
+fn identity_lut(size: u32) -> Vec<u8> {
+    let scale = |value: u32| (value * 255 / (size - 1)) as u8;
+    (0..size)
+        .flat_map(|blue| {
+            (0..size).flat_map(move |green| {
+                (0..size).flat_map(move |red| [scale(red), scale(green), scale(blue), u8::MAX])
+            })
+        })
+        .collect()
+}
+
+fn create_lut(gpu: &crate::gpu::Gpu, size: u32, texels: &[u8]) -> wgpu::TextureView {
+    let extent = wgpu::Extent3d {
+        width: size,
+        height: size,
+        depth_or_array_layers: size,
+    };
+    let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Color Grading Lut"),
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D3,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    gpu.queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        texels,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(size * 4),
+            rows_per_image: Some(size),
+        },
+        extent,
+    );
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn create_bind_group_layout(gpu: &crate::gpu::Gpu) -> wgpu::BindGroupLayout {
+    gpu.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post Process Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<PostUniform>() as _
+                        ),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        })
+}
+
+fn create_bind_group(
+    gpu: &crate::gpu::Gpu,
+    layout: &wgpu::BindGroupLayout,
+    input: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+    uniform_buffer: &wgpu::Buffer,
+    color_grading_lut: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Post Process Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(input),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: uniform_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<PostUniform>() as _),
+                }),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(color_grading_lut),
+            },
+        ],
+    })
+}
+
+fn create_pipeline(
+    gpu: &crate::gpu::Gpu,
+    layout: &wgpu::PipelineLayout,
+    shader_module: &wgpu::ShaderModule,
+    entry_point: &str,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    gpu.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Post Process Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader_module,
+                entry_point: "fullscreen_vertex_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: if format == HDR_FORMAT {
+                wgpu::MultisampleState::default()
+            } else {
+                gpu.multisample_state()
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader_module,
+                entry_point,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    // Bloom blurs are accumulated by adding each pass onto the target
+                    blend: matches!(entry_point, "bloom_upsample_main" | "bloom_composite_main")
+                        .then_some(wgpu::BlendState {
+                            color: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::One,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                            alpha: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::Zero,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                        }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+}
+
+const SHADER_SOURCE: &str = "
+struct PostUniform {
+    texel_size: vec2<f32>,
+    exposure: f32,
+    gamma: f32,
+    tone_mapping_operator: u32,
+    intensity: f32,
+    radius: f32,
+    smoothness: f32,
+    threshold: f32,
+    knee: f32,
+};
+
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var input_sampler: sampler;
+
+@group(0) @binding(2)
+var<uniform> params: PostUniform;
+
+@group(0) @binding(3)
+var color_grading_lut: texture_3d<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// A single triangle covering the screen, clipped to the viewport
+@vertex
+fn fullscreen_vertex_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let position = vec2(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u)) * 2.0 - 1.0;
+    var out: VertexOutput;
+    out.position = vec4(position, 0.0, 1.0);
+    out.uv = vec2(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+    return out;
+}
+
+fn sample_input(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(input_texture, input_sampler, uv, 0.0);
+}
+
+@fragment
+fn copy_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return sample_input(in.uv);
+}
+
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3(0.0), vec3(1.0));
+}
+
+@fragment
+fn tone_mapping_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let input = sample_input(in.uv);
+    var color = max(input.rgb * params.exposure, vec3(0.0));
+    if params.tone_mapping_operator == 1u {
+        color = color / (color + 1.0);
+    } else if params.tone_mapping_operator == 2u {
+        color = aces(color);
+    } else {
+        color = clamp(color, vec3(0.0), vec3(1.0));
+    }
+    color = pow(color, vec3(1.0 / params.gamma));
+    return vec4(color, input.a);
+}
+
+@fragment
+fn color_grading_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let input = sample_input(in.uv);
+    let color = clamp(input.rgb, vec3(0.0), vec3(1.0));
+    // Sample texel centers so the first and last entries map to zero and one
+    let size = f32(textureDimensions(color_grading_lut).x);
+    let coordinates = color * ((size - 1.0) / size) + 0.5 / size;
+    let graded = textureSampleLevel(color_grading_lut, input_sampler, coordinates, 0.0).rgb;
+    return vec4(mix(color, graded, params.intensity), input.a);
+}
+
+@fragment
+fn vignette_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let input = sample_input(in.uv);
+    let distance = length(in.uv - 0.5) * 1.41421356;
+    let falloff = smoothstep(params.radius, params.radius + params.smoothness, distance);
+    return vec4(input.rgb * (1.0 - falloff * params.intensity), input.a);
+}
+
+fn input_texel_size() -> vec2<f32> {
+    return 1.0 / vec2<f32>(textureDimensions(input_texture));
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// Removes light below the threshold, easing in over the knee
+fn bloom_threshold(color: vec3<f32>) -> vec3<f32> {
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - params.threshold + params.knee, 0.0, 2.0 * params.knee);
+    soft = soft * soft / (4.0 * params.knee + 0.00001);
+    let contribution = max(soft, brightness - params.threshold) / max(brightness, 0.00001);
+    return color * contribution;
+}
+
+// Weights a group of samples so single very bright pixels don't flicker as they move
+fn karis_weight(color: vec3<f32>) -> f32 {
+    return 1.0 / (1.0 + luminance(color));
+}
+
+// The 13 tap downsampling filter from Jorge Jimenez's Next Generation Post Processing in Call of Duty
+fn downsample(uv: vec2<f32>, karis_average: bool) -> vec3<f32> {
+    let texel = input_texel_size();
+    let a = sample_input(uv + texel * vec2(-2.0, -2.0)).rgb;
+    let b = sample_input(uv + texel * vec2(0.0, -2.0)).rgb;
+    let c = sample_input(uv + texel * vec2(2.0, -2.0)).rgb;
+    let d = sample_input(uv + texel * vec2(-2.0, 0.0)).rgb;
+    let e = sample_input(uv).rgb;
+    let f = sample_input(uv + texel * vec2(2.0, 0.0)).rgb;
+    let g = sample_input(uv + texel * vec2(-2.0, 2.0)).rgb;
+    let h = sample_input(uv + texel * vec2(0.0, 2.0)).rgb;
+    let i = sample_input(uv + texel * vec2(2.0, 2.0)).rgb;
+    let j = sample_input(uv + texel * vec2(-1.0, -1.0)).rgb;
+    let k = sample_input(uv + texel * vec2(1.0, -1.0)).rgb;
+    let l = sample_input(uv + texel * vec2(-1.0, 1.0)).rgb;
+    let m = sample_input(uv + texel * vec2(1.0, 1.0)).rgb;
+
+    var groups = array<vec3<f32>, 5>(
+        (j + k + l + m) * 0.25,
+        (a + b + d + e) * 0.25,
+        (b + c + e + f) * 0.25,
+        (d + e + g + h) * 0.25,
+        (e + f + h + i) * 0.25,
+    );
+    var weights = array<f32, 5>(0.5, 0.125, 0.125, 0.125, 0.125);
+    var result = vec3(0.0);
+    var total_weight = 0.0;
+    for (var index = 0; index < 5; index += 1) {
+        var weight = weights[index];
+        if karis_average {
+            weight *= karis_weight(groups[index]);
+        }
+        result += groups[index] * weight;
+        total_weight += weight;
+    }
+    return result / total_weight;
+}
+
+// A 3x3 tent filter, which blurs while upsampling
+fn upsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = input_texel_size();
+    var result = sample_input(uv).rgb * 4.0;
+    result += (
+        sample_input(uv + texel * vec2(0.0, -1.0)).rgb
+        + sample_input(uv + texel * vec2(-1.0, 0.0)).rgb
+        + sample_input(uv + texel * vec2(1.0, 0.0)).rgb
+        + sample_input(uv + texel * vec2(0.0, 1.0)).rgb
+    ) * 2.0;
+    result += sample_input(uv + texel * vec2(-1.0, -1.0)).rgb
+        + sample_input(uv + texel * vec2(1.0, -1.0)).rgb
+        + sample_input(uv + texel * vec2(-1.0, 1.0)).rgb
+        + sample_input(uv + texel * vec2(1.0, 1.0)).rgb;
+    return result / 16.0;
+}
+
+@fragment
+fn bloom_prefilter_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(bloom_threshold(downsample(in.uv, true)), 1.0);
+}
+
+@fragment
+fn bloom_downsample_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(downsample(in.uv, false), 1.0);
+}
+
+@fragment
+fn bloom_upsample_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(upsample(in.uv), 0.0);
+}
+
+@fragment
+fn bloom_composite_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(upsample(in.uv) * params.intensity, 0.0);
+}
+
+fn luma(color: vec3<f32>) -> f32 {
+    // The square root approximates the perceptual curve for linear colors
+    return sqrt(dot(color, vec3(0.299, 0.587, 0.114)));
+}
+
+const FXAA_REDUCE_MIN: f32 = 0.0078125;
+const FXAA_REDUCE_MUL: f32 = 0.125;
+const FXAA_SPAN_MAX: f32 = 8.0;
+
+// Blurs along edges found from the luma of neighboring pixels
+@fragment
+fn fxaa_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = params.texel_size;
+    let center = sample_input(in.uv);
+    let luma_center = luma(center.rgb);
+    let luma_north_west = luma(sample_input(in.uv + vec2(-1.0, -1.0) * texel).rgb);
+    let luma_north_east = luma(sample_input(in.uv + vec2(1.0, -1.0) * texel).rgb);
+    let luma_south_west = luma(sample_input(in.uv + vec2(-1.0, 1.0) * texel).rgb);
+    let luma_south_east = luma(sample_input(in.uv + vec2(1.0, 1.0) * texel).rgb);
+    let luma_min = min(luma_center, min(min(luma_north_west, luma_north_east), min(luma_south_west, luma_south_east)));
+    let luma_max = max(luma_center, max(max(luma_north_west, luma_north_east), max(luma_south_west, luma_south_east)));
+
+    var direction = vec2(
+        -((luma_north_west + luma_north_east) - (luma_south_west + luma_south_east)),
+        (luma_north_west + luma_south_west) - (luma_north_east + luma_south_east),
+    );
+    let direction_reduce = max(
+        (luma_north_west + luma_north_east + luma_south_west + luma_south_east) * 0.25 * FXAA_REDUCE_MUL,
+        FXAA_REDUCE_MIN,
+    );
+    let inverse_direction_min = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+    direction = clamp(direction * inverse_direction_min, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * texel;
+
+    let near = 0.5 * (
+        sample_input(in.uv + direction * (1.0 / 3.0 - 0.5)).rgb
+        + sample_input(in.uv + direction * (2.0 / 3.0 - 0.5)).rgb
+    );
+    let far = near * 0.5 + 0.25 * (
+        sample_input(in.uv + direction * -0.5).rgb
+        + sample_input(in.uv + direction * 0.5).rgb
+    );
+    let luma_far = luma(far);
+    if luma_far < luma_min || luma_far > luma_max {
+        return vec4(near, center.a);
+    }
+    return vec4(far, center.a);
+}
+";
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn identity_lut_spans_full_range() {
+        let size = 4;
+        let lut = super::identity_lut(size);
+        assert_eq!(lut.len(), (size * size * size * 4) as usize);
+        assert_eq!(&lut[..4], &[0, 0, 0, 255]);
+        assert_eq!(&lut[lut.len() - 4..], &[255, 255, 255, 255]);
+        // Red increases fastest
+        assert_eq!(&lut[4..8], &[85, 0, 0, 255]);
+    }
+
+    #[test]
+    fn effect_chains_render() {
+        let Some(mut renderer) = crate::render::test_renderer(32, 32, 1) else {
+            return;
+        };
+        let mut scene = crate::scene::Scene::default();
+        scene.graph.add_node(crate::scene::Node::default());
+        let camera = scene.add_root_node(crate::scene::create_camera_node(1.0));
+        renderer.view.import_scene(&scene, &renderer.gpu);
+
+        assert!(!renderer
+            .post_process
+            .set_color_grading_lut(&renderer.gpu, &image::RgbaImage::new(16, 16)));
+        let inverted_lut = image::RgbaImage::from_fn(16, 4, |x, y| {
+            let scale = |value: u32| 255 - (value * 255 / 3) as u8;
+            image::Rgba([scale(x % 4), scale(y), scale(x / 4), 255])
+        });
+        assert!(renderer
+            .post_process
+            .set_color_grading_lut(&renderer.gpu, &inverted_lut));
+
+        renderer.post_process.effects = Vec::new();
+        let copied = renderer.render_to_image(&scene, camera, 32, 32);
+        renderer.post_process.effects = super::PostEffect::ALL.to_vec();
+        let graded = renderer.render_to_image(&scene, camera, 32, 32);
+
+        // The clear color is mostly red, so inverting it leaves it mostly cyan
+        let (copied, graded) = (copied.get_pixel(16, 16), graded.get_pixel(16, 16));
+        assert!(copied[0] > copied[2]);
+        assert!(graded[0] < graded[2]);
+    }
+
+    #[test]
+    fn bloom_spreads_emissive_light() {
+        let Some(mut fixture) = crate::render::HelmetFixture::new() else {
+            return;
+        };
+        // Make the whole helmet glow well above the bloom threshold
+        fixture.scene.materials.values_mut().for_each(|material| {
+            material.emissive_factor = nalgebra_glm::vec3(4.0, 4.0, 4.0);
+            material.emissive_texture.clear();
+        });
+        fixture.renderer.view.clear_color = wgpu::Color::BLACK;
+
+        let without_bloom = fixture.reimport_and_render();
+        fixture
+            .renderer
+            .post_process
+            .effects
+            .insert(0, super::PostEffect::ALL[0]);
+        let with_bloom = fixture.render();
+
+        // Bloom only adds light, and spreads some of it onto the background
+        let brightness = |image: &image::RgbaImage| {
+            image
+                .pixels()
+                .map(|pixel| pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32)
+                .sum::<u32>()
+        };
+        assert!(brightness(&with_bloom) > brightness(&without_bloom));
+    }
+}
@@ -2,7 +2,66 @@ pub struct Renderer {
     pub gpu: crate::gpu::Gpu,
     pub gui: crate::gui::Gui,
     pub view: crate::view::View,
+    /// The post process pipelines shared by the main view, every camera view and every render target
+    post_process_resources: std::sync::Arc<crate::postprocess::PostProcessResources>,
+    pub post_process: crate::postprocess::PostProcess,
+    pub id_buffer: crate::picking::IdBuffer,
+    pub outline: crate::outline::Outline,
+    /// Depth attachment for the gui pass, which is composited after post processing
     pub depth_texture_view: wgpu::TextureView,
+    /// The multisampled target post processing and the gui render into when msaa is enabled,
+    /// which resolves into the surface
+    msaa_texture_view: Option<wgpu::TextureView>,
+    /// The offscreen texture the scene renders into when it is displayed inside the gui
+    viewport: Option<Viewport>,
+    /// Views rendered instead of the scene's cameras, like the editor's quad view
+    pub camera_views: Option<Vec<crate::view::CameraView>>,
+    /// The post process and outline targets of every camera view after the first,
+    /// which are sized to their own viewports
+    view_targets: Vec<ViewTargets>,
+    pub render_targets: crate::render_target::RenderTargets,
+}
+
+struct ViewTargets {
+    post_process: crate::postprocess::PostProcess,
+    outline: crate::outline::Outline,
+}
+
+/// An offscreen color target registered with the gui renderer,
+/// so the scene can be shown in a panel instead of behind the gui
+struct Viewport {
+    texture_id: egui::TextureId,
+    texture_view: wgpu::TextureView,
+    msaa_texture_view: Option<wgpu::TextureView>,
+    width: u32,
+    height: u32,
+}
+
+impl Viewport {
+    fn create_texture_views(
+        gpu: &crate::gpu::Gpu,
+        width: u32,
+        height: u32,
+    ) -> (wgpu::TextureView, Option<wgpu::TextureView>) {
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Viewport Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: gpu.surface_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        (
+            texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            gpu.create_msaa_texture(width, height, gpu.surface_format),
+        )
+    }
 }
 
 impl Renderer {
@@ -13,17 +72,52 @@ impl Renderer {
         width: u32,
         height: u32,
         scale_factor: f64,
+        sample_count: u32,
     ) -> Self {
-        let gpu = pollster::block_on(crate::gpu::Gpu::new_async(&window, width, height));
+        let gpu = pollster::block_on(crate::gpu::Gpu::new_async(
+            &window,
+            width,
+            height,
+            sample_count,
+        ));
         let depth_texture_view =
             gpu.create_depth_texture(gpu.surface_config.width, gpu.surface_config.height);
+        let msaa_texture_view = gpu.create_msaa_texture(
+            gpu.surface_config.width,
+            gpu.surface_config.height,
+            gpu.surface_format,
+        );
         let gui = crate::gui::Gui::new(&window, &gpu, scale_factor);
         let view = crate::view::View::new(&gpu);
+        let post_process_resources =
+            std::sync::Arc::new(crate::postprocess::PostProcessResources::new(&gpu));
+        let post_process = crate::postprocess::PostProcess::new(
+            &gpu,
+            post_process_resources.clone(),
+            gpu.surface_config.width,
+            gpu.surface_config.height,
+        );
+        let id_buffer = crate::picking::IdBuffer::new(
+            &gpu,
+            gpu.surface_config.width,
+            gpu.surface_config.height,
+        );
+        let outline =
+            crate::outline::Outline::new(&gpu, gpu.surface_config.width, gpu.surface_config.height);
         Self {
             gpu,
             gui,
             view,
+            post_process_resources,
+            post_process,
+            id_buffer,
+            outline,
             depth_texture_view,
+            msaa_texture_view,
+            viewport: None,
+            camera_views: None,
+            view_targets: Vec::new(),
+            render_targets: crate::render_target::RenderTargets::default(),
         }
     }
 
@@ -33,6 +127,76 @@ impl Renderer {
             self.gpu.surface_config.width,
             self.gpu.surface_config.height,
         );
+        self.msaa_texture_view = self.gpu.create_msaa_texture(
+            self.gpu.surface_config.width,
+            self.gpu.surface_config.height,
+            self.gpu.surface_format,
+        );
+        let (width, height) = self.viewport_size();
+        self.post_process.resize(&self.gpu, width, height);
+        self.id_buffer.resize(&self.gpu, width, height);
+        self.outline.resize(&self.gpu, width, height);
+    }
+
+    /// Renders the scene into an offscreen texture of the given size instead of the window surface,
+    /// returning the texture id to display it with in the gui
+    pub fn viewport_texture(&mut self, width: u32, height: u32) -> egui::TextureId {
+        let (width, height) = (width.max(1), height.max(1));
+        match self.viewport.as_mut() {
+            Some(viewport) if viewport.width == width && viewport.height == height => {}
+            Some(viewport) => {
+                let (texture_view, msaa_texture_view) =
+                    Viewport::create_texture_views(&self.gpu, width, height);
+                self.gui.renderer.update_egui_texture_from_wgpu_texture(
+                    &self.gpu.device,
+                    &texture_view,
+                    wgpu::FilterMode::Linear,
+                    viewport.texture_id,
+                );
+                viewport.texture_view = texture_view;
+                viewport.msaa_texture_view = msaa_texture_view;
+                viewport.width = width;
+                viewport.height = height;
+            }
+            None => {
+                let (texture_view, msaa_texture_view) =
+                    Viewport::create_texture_views(&self.gpu, width, height);
+                let texture_id = self.gui.renderer.register_native_texture(
+                    &self.gpu.device,
+                    &texture_view,
+                    wgpu::FilterMode::Linear,
+                );
+                self.viewport = Some(Viewport {
+                    texture_id,
+                    texture_view,
+                    msaa_texture_view,
+                    width,
+                    height,
+                });
+            }
+        }
+        self.viewport
+            .as_ref()
+            .expect("The viewport was just created!")
+            .texture_id
+    }
+
+    /// The size in pixels the scene renders at,
+    /// which is the offscreen viewport if there is one and the window surface otherwise
+    pub fn viewport_size(&self) -> (u32, u32) {
+        match self.viewport.as_ref() {
+            Some(viewport) => (viewport.width, viewport.height),
+            None => (
+                self.gpu.surface_config.width,
+                self.gpu.surface_config.height,
+            ),
+        }
+    }
+
+    /// Requests the node drawn at a pixel of the viewport to be read back from the id buffer,
+    /// returning the latest pick that finished reading back, which may be a few frames old
+    pub fn pick(&mut self, x: u32, y: u32) -> Option<crate::picking::GpuPick> {
+        self.id_buffer.pick(&self.gpu, x, y)
     }
 
     pub fn render_frame(
@@ -63,6 +227,8 @@ impl Renderer {
         let surface_texture = self
             .gpu
             .surface
+            .as_ref()
+            .expect("Windowed renderers always have a surface!")
             .get_current_texture()
             .expect("Failed to get surface texture!");
 
@@ -80,7 +246,141 @@ impl Renderer {
                     array_layer_count: None,
                 });
 
+        let (width, height) = self.viewport_size();
+        let output_rect = crate::view::PixelRect::full(width, height);
+        let (surface_view, surface_resolve_target) = match self.msaa_texture_view.as_ref() {
+            Some(msaa_texture_view) => (msaa_texture_view, Some(&surface_texture_view)),
+            None => (&surface_texture_view, None),
+        };
+        let (output_view, resolve_target) = match self.viewport.as_ref() {
+            Some(Viewport {
+                texture_view,
+                msaa_texture_view: Some(msaa_texture_view),
+                ..
+            }) => (msaa_texture_view, Some(texture_view)),
+            Some(Viewport { texture_view, .. }) => (texture_view, None),
+            None => (surface_view, surface_resolve_target),
+        };
+
+        let camera_views = self
+            .camera_views
+            .clone()
+            .unwrap_or_else(|| crate::view::camera_views(&context.scene));
+        let viewports = camera_views
+            .iter()
+            .map(|camera_view| camera_view.viewport.pixel_rect(width, height))
+            .collect::<Vec<_>>();
+        // Picks read back the topmost view under the requested pixel
+        self.id_buffer.resize(&self.gpu, width, height);
+        let picked_view = self.id_buffer.requested().and_then(|(x, y)| {
+            viewports
+                .iter()
+                .rposition(|viewport| viewport.contains(x, y))
+        });
+        self.view.debug.prepare(&self.gpu, &context.debug);
+        self.render_targets.render(
+            &self.gpu,
+            &mut self.view,
+            &context.scene,
+            &self.post_process_resources,
+            &self.post_process.effects,
+            context.delta_time as f32,
+        );
+
         encoder.insert_debug_marker("Render scene");
+        // Views that don't cover the whole output are drawn over a cleared one
+        if viewports.first() != Some(&output_rect) {
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Clear Viewports Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output_view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+        }
+        let mut picking = false;
+        for (index, (camera_view, viewport)) in camera_views.iter().zip(viewports).enumerate() {
+            if viewport.width == 0 || viewport.height == 0 {
+                continue;
+            }
+            while self.view_targets.len() < index {
+                self.view_targets.push(ViewTargets {
+                    post_process: crate::postprocess::PostProcess::new(
+                        &self.gpu,
+                        self.post_process_resources.clone(),
+                        viewport.width,
+                        viewport.height,
+                    ),
+                    outline: crate::outline::Outline::new(
+                        &self.gpu,
+                        viewport.width,
+                        viewport.height,
+                    ),
+                });
+            }
+            let effects = camera_view
+                .camera
+                .post_effects
+                .clone()
+                .unwrap_or_else(|| self.post_process.effects.clone());
+            let (post_process, outline) = match index.checked_sub(1) {
+                None => (&mut self.post_process, &mut self.outline),
+                Some(target_index) => {
+                    let targets = &mut self.view_targets[target_index];
+                    targets.outline.copy_settings_from(&self.outline);
+                    (&mut targets.post_process, &mut targets.outline)
+                }
+            };
+
+            self.view.prepare(
+                &self.gpu,
+                &context.scene,
+                camera_view.matrices(viewport.aspect_ratio()),
+            );
+            // Screenshots may have resized the post process and outline targets since the last frame
+            post_process.resize(&self.gpu, viewport.width, viewport.height);
+            outline.resize(&self.gpu, viewport.width, viewport.height);
+            outline.prepare(&self.gpu, &context.scene, &self.view);
+            render_view(
+                &mut encoder,
+                &self.gpu,
+                &self.view,
+                post_process,
+                Some(outline),
+                &effects,
+                output_view,
+                resolve_target,
+                (index > 0 || viewport != output_rect).then_some(viewport),
+            );
+            if picked_view == Some(index) {
+                picking =
+                    self.id_buffer
+                        .render(&mut encoder, &self.gpu, &self.view, Some(viewport));
+            }
+
+            // Every view is prepared into the same uniform buffers,
+            // so each view is submitted before the next one is prepared
+            if index + 1 < camera_views.len() {
+                let view_encoder = std::mem::replace(
+                    &mut encoder,
+                    self.gpu
+                        .device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("Render Encoder"),
+                        }),
+                );
+                self.gpu
+                    .queue
+                    .submit(std::iter::once(view_encoder.finish()));
+            }
+        }
+
+        encoder.insert_debug_marker("Render gui");
 
         // This scope around the render_pass prevents the
         // render_pass from holding a borrow to the encoder,
@@ -88,17 +388,17 @@ impl Renderer {
         // preparation for queue submission.
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
+                label: Some("Gui Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &surface_texture_view,
-                    resolve_target: None,
+                    view: surface_view,
+                    resolve_target: surface_resolve_target,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.4,
-                            g: 0.2,
-                            b: 0.2,
-                            a: 1.0,
-                        }),
+                        // The scene is drawn over only when it isn't shown inside the gui
+                        load: if self.viewport.is_some() {
+                            wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                        } else {
+                            wgpu::LoadOp::Load
+                        },
                         store: true,
                     },
                 })],
@@ -111,24 +411,301 @@ impl Renderer {
                     stencil_ops: None,
                 }),
             });
-
-            self.view
-                .render(&mut render_pass, &self.gpu, &context.scene);
             self.gui
                 .renderer
                 .render(&mut render_pass, &paint_jobs, &screen_descriptor);
         }
 
         self.gpu.queue.submit(std::iter::once(encoder.finish()));
+        if picking {
+            self.id_buffer.begin_readback();
+        }
 
         surface_texture.present();
     }
+
+    /// Renders the scene from a camera node into an image, independently of the window
+    /// and its surface, after rendering the cameras that target textures which are due for an update
+    pub fn render_to_image(
+        &mut self,
+        scene: &crate::scene::Scene,
+        camera: petgraph::graph::NodeIndex,
+        width: u32,
+        height: u32,
+    ) -> image::RgbaImage {
+        self.render_targets.render(
+            &self.gpu,
+            &mut self.view,
+            scene,
+            &self.post_process_resources,
+            &self.post_process.effects,
+            0.0,
+        );
+        render_to_image(
+            &self.gpu,
+            &mut self.view,
+            &mut self.post_process,
+            &mut self.outline,
+            scene,
+            camera,
+            width,
+            height,
+        )
+    }
+}
+
+/// A renderer without a window, used for tests, thumbnails and other offscreen rendering
+pub struct HeadlessRenderer {
+    pub gpu: crate::gpu::Gpu,
+    pub view: crate::view::View,
+    post_process_resources: std::sync::Arc<crate::postprocess::PostProcessResources>,
+    pub post_process: crate::postprocess::PostProcess,
+    pub outline: crate::outline::Outline,
+    pub render_targets: crate::render_target::RenderTargets,
+}
+
+impl HeadlessRenderer {
+    /// Returns `None` if neither a hardware nor a software adapter is available
+    pub fn new(width: u32, height: u32) -> Option<Self> {
+        Self::with_sample_count(width, height, 1)
+    }
+
+    /// Creates a renderer that multisamples with up to the requested number of samples
+    pub fn with_sample_count(width: u32, height: u32, sample_count: u32) -> Option<Self> {
+        let gpu = pollster::block_on(crate::gpu::Gpu::new_headless_async(
+            width,
+            height,
+            sample_count,
+        ))?;
+        let view = crate::view::View::new(&gpu);
+        let post_process_resources =
+            std::sync::Arc::new(crate::postprocess::PostProcessResources::new(&gpu));
+        let post_process = crate::postprocess::PostProcess::new(
+            &gpu,
+            post_process_resources.clone(),
+            width,
+            height,
+        );
+        let outline = crate::outline::Outline::new(&gpu, width, height);
+        Some(Self {
+            gpu,
+            view,
+            post_process_resources,
+            post_process,
+            outline,
+            render_targets: crate::render_target::RenderTargets::default(),
+        })
+    }
+
+    /// Renders the scene from a camera node into an image,
+    /// after rendering the cameras that target textures which are due for an update
+    pub fn render_to_image(
+        &mut self,
+        scene: &crate::scene::Scene,
+        camera: petgraph::graph::NodeIndex,
+        width: u32,
+        height: u32,
+    ) -> image::RgbaImage {
+        self.render_targets.render(
+            &self.gpu,
+            &mut self.view,
+            scene,
+            &self.post_process_resources,
+            &self.post_process.effects,
+            0.0,
+        );
+        render_to_image(
+            &self.gpu,
+            &mut self.view,
+            &mut self.post_process,
+            &mut self.outline,
+            scene,
+            camera,
+            width,
+            height,
+        )
+    }
+}
+
+/// Renders the prepared view into the hdr target, then post processes it into the output
+/// and outlines the prepared selection if given one, resolving into the resolve target if the output is multisampled.
+/// Given a viewport, only that region of the output is drawn over.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn render_view(
+    encoder: &mut wgpu::CommandEncoder,
+    gpu: &crate::gpu::Gpu,
+    view: &crate::view::View,
+    post_process: &crate::postprocess::PostProcess,
+    outline: Option<&crate::outline::Outline>,
+    effects: &[crate::postprocess::PostEffect],
+    output_view: &wgpu::TextureView,
+    resolve_target: Option<&wgpu::TextureView>,
+    viewport: Option<crate::view::PixelRect>,
+) {
+    view.render_shadows(encoder, gpu);
+    {
+        let (scene_view, scene_resolve_target) = post_process.scene_color_attachment();
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Scene Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: scene_view,
+                resolve_target: scene_resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(view.background_color()),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &post_process.depth_texture_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        view.render(&mut render_pass, gpu);
+    }
+    post_process.render(encoder, gpu, effects, output_view, resolve_target, viewport);
+    if let Some(outline) = outline {
+        outline.render(
+            encoder,
+            gpu,
+            view,
+            post_process,
+            output_view,
+            resolve_target,
+            viewport,
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_to_image(
+    gpu: &crate::gpu::Gpu,
+    view: &mut crate::view::View,
+    post_process: &mut crate::postprocess::PostProcess,
+    outline: &mut crate::outline::Outline,
+    scene: &crate::scene::Scene,
+    camera: petgraph::graph::NodeIndex,
+    width: u32,
+    height: u32,
+) -> image::RgbaImage {
+    let (width, height) = (width.max(1), height.max(1));
+    let extent = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+
+    let color_texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen Color Texture"),
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: gpu.surface_format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let color_texture_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let msaa_texture_view = gpu.create_msaa_texture(width, height, gpu.surface_format);
+    let (output_view, resolve_target) = match msaa_texture_view.as_ref() {
+        Some(msaa_texture_view) => (msaa_texture_view, Some(&color_texture_view)),
+        None => (&color_texture_view, None),
+    };
+    post_process.resize(gpu, width, height);
+    outline.resize(gpu, width, height);
+
+    // Rows copied out of a texture must be padded to a fixed alignment
+    let unpadded_bytes_per_row = width * 4;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let readback_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Offscreen Readback Buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let camera_matrices =
+        crate::view::create_camera_matrices_for_node(scene, camera, width as f32 / height as f32)
+            .unwrap_or_default();
+
+    let mut encoder = gpu
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Offscreen Render Encoder"),
+        });
+    view.prepare(gpu, scene, camera_matrices);
+    outline.prepare(gpu, scene, view);
+    let effects = post_process.effects_for_camera(scene, Some(camera));
+    render_view(
+        &mut encoder,
+        gpu,
+        view,
+        post_process,
+        Some(outline),
+        effects,
+        output_view,
+        resolve_target,
+        None,
+    );
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture: &color_texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &readback_buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        extent,
+    );
+    gpu.queue.submit(std::iter::once(encoder.finish()));
+
+    let buffer_slice = readback_buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    gpu.device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .expect("Offscreen readback was never mapped!")
+        .expect("Failed to map offscreen readback buffer!");
+
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    {
+        let data = buffer_slice.get_mapped_range();
+        data.chunks(padded_bytes_per_row as usize)
+            .for_each(|row| pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]));
+    }
+    readback_buffer.unmap();
+
+    // Window surfaces are commonly BGRA
+    if matches!(
+        gpu.surface_format,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    ) {
+        pixels.chunks_mut(4).for_each(|pixel| pixel.swap(0, 2));
+    }
+
+    image::RgbaImage::from_raw(width, height, pixels)
+        .expect("Offscreen readback has the wrong size!")
 }
 
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
+    /// Shared between all textures with the same sampling parameters
+    pub sampler: std::sync::Arc<wgpu::Sampler>,
 }
 
 impl From<crate::scene::Sampler> for wgpu::SamplerDescriptor<'static> {
@@ -173,3 +750,102 @@ impl From<crate::scene::Sampler> for wgpu::SamplerDescriptor<'static> {
         }
     }
 }
+
+/// Creates a headless renderer for a test.
+/// Without an adapter this panics unless `SERENITY_SKIP_GPU_TESTS=1` is set,
+/// in which case it returns `None` and the test is skipped.
+#[cfg(test)]
+pub(crate) fn test_renderer(
+    width: u32,
+    height: u32,
+    sample_count: u32,
+) -> Option<HeadlessRenderer> {
+    let renderer = HeadlessRenderer::with_sample_count(width, height, sample_count);
+    if renderer.is_none() {
+        assert!(
+            std::env::var("SERENITY_SKIP_GPU_TESTS").is_ok_and(|value| value == "1"),
+            "No adapter is available, set SERENITY_SKIP_GPU_TESTS=1 to skip gpu tests"
+        );
+        eprintln!("Skipping gpu test, no adapter is available");
+    }
+    renderer
+}
+
+/// The damaged helmet seen from a default camera, imported and rendered once at 64x64
+#[cfg(test)]
+pub(crate) struct HelmetFixture {
+    pub renderer: HeadlessRenderer,
+    pub scene: crate::scene::Scene,
+    pub camera: petgraph::graph::NodeIndex,
+    pub image: image::RgbaImage,
+}
+
+#[cfg(test)]
+impl HelmetFixture {
+    pub const SIZE: u32 = 64;
+
+    pub fn new() -> Option<Self> {
+        let mut renderer = test_renderer(Self::SIZE, Self::SIZE, 1)?;
+        let mut scene = crate::gltf::import_gltf("resources/models/DamagedHelmet.glb");
+        let camera = scene.add_root_node(crate::scene::create_camera_node(1.0));
+        renderer.view.import_scene(&scene, &renderer.gpu);
+        let image = renderer.render_to_image(&scene, camera, Self::SIZE, Self::SIZE);
+        Some(Self {
+            renderer,
+            scene,
+            camera,
+            image,
+        })
+    }
+
+    /// Imports the scene again after it was changed and renders it
+    pub fn reimport_and_render(&mut self) -> image::RgbaImage {
+        self.renderer
+            .view
+            .import_scene(&self.scene, &self.renderer.gpu);
+        self.render()
+    }
+
+    pub fn render(&mut self) -> image::RgbaImage {
+        self.renderer
+            .render_to_image(&self.scene, self.camera, Self::SIZE, Self::SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn headless_render_draws_scene() {
+        let Some(mut fixture) = super::HelmetFixture::new() else {
+            return;
+        };
+
+        let image = fixture
+            .renderer
+            .render_to_image(&fixture.scene, fixture.camera, 64, 48);
+        assert_eq!(image.dimensions(), (64, 48));
+        let background = image.get_pixel(0, 0);
+        assert!(
+            image.pixels().any(|pixel| pixel != background),
+            "Only the clear color was rendered"
+        );
+    }
+
+    #[test]
+    fn multisampled_render_resolves() {
+        let Some(mut renderer) = super::test_renderer(64, 64, 4) else {
+            return;
+        };
+        assert!(crate::gpu::Gpu::SAMPLE_COUNTS.contains(&renderer.gpu.sample_count));
+        let mut scene = crate::gltf::import_gltf("resources/models/DamagedHelmet.glb");
+        let camera = scene.add_root_node(crate::scene::create_camera_node(1.0));
+        renderer.view.import_scene(&scene, &renderer.gpu);
+
+        let image = renderer.render_to_image(&scene, camera, 64, 64);
+        let background = image.get_pixel(0, 0);
+        assert!(
+            image.pixels().any(|pixel| pixel != background),
+            "Only the clear color was resolved"
+        );
+    }
+}
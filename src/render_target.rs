@@ -0,0 +1,262 @@
+/// Every camera in the scene that renders into a texture instead of the window
+pub fn camera_render_targets(
+    scene: &crate::scene::Scene,
+) -> Vec<(petgraph::graph::NodeIndex, crate::scene::CameraRenderTarget)> {
+    let mut render_targets = Vec::new();
+    scene.walk_dfs(|node, node_index| {
+        node.components.iter().for_each(|component| {
+            if let crate::scene::NodeComponent::Camera(crate::scene::Camera {
+                render_target: Some(render_target),
+                ..
+            }) = component
+            {
+                render_targets.push((node_index, render_target.clone()));
+            }
+        });
+    });
+    render_targets
+}
+
+/// Creates the texture a camera renders into, which materials sample like an srgb color texture
+pub fn create_texture(
+    gpu: &crate::gpu::Gpu,
+    texture_uploader: &mut crate::texture::TextureUploader,
+    render_target: &crate::scene::CameraRenderTarget,
+) -> crate::render::Texture {
+    let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(&render_target.texture),
+        size: wgpu::Extent3d {
+            width: render_target.width.max(1),
+            height: render_target.height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        // Materials sample the texture as srgb, while post processing writes through a view
+        // in the surface format, which reinterprets it as linear if the surface isn't srgb
+        format: gpu.surface_format.add_srgb_suffix(),
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: if gpu.surface_format.is_srgb() {
+            &[]
+        } else {
+            std::slice::from_ref(&gpu.surface_format)
+        },
+    });
+    crate::render::Texture {
+        view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+        texture,
+        sampler: texture_uploader.sampler(gpu, &crate::scene::Sampler::default()),
+    }
+}
+
+/// The post process targets a camera renders through before writing its texture,
+/// and how long it has been since it last did
+struct RenderTargetState {
+    post_process: crate::postprocess::PostProcess,
+    msaa_texture_view: Option<wgpu::TextureView>,
+    seconds_since_update: Option<f32>,
+}
+
+/// Renders the cameras that target textures, each at its own resolution and update rate.
+///
+/// The textures themselves are created by the view when a scene is imported,
+/// so changing the resolution of a render target takes effect on the next import.
+#[derive(Default)]
+pub struct RenderTargets {
+    /// Keyed by the id of the texture rendered into
+    states: std::collections::HashMap<String, RenderTargetState>,
+}
+
+impl RenderTargets {
+    /// Renders every camera whose texture is due for an update through its own post effects
+    /// or the given ones, submitting each camera before the next is prepared,
+    /// so it must be called before the main view is prepared.
+    pub fn render(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        view: &mut crate::view::View,
+        scene: &crate::scene::Scene,
+        post_process_resources: &std::sync::Arc<crate::postprocess::PostProcessResources>,
+        effects: &[crate::postprocess::PostEffect],
+        delta_time: f32,
+    ) {
+        let render_targets = camera_render_targets(scene);
+        self.states.retain(|texture_id, _| {
+            render_targets
+                .iter()
+                .any(|(_, render_target)| &render_target.texture == texture_id)
+        });
+
+        render_targets
+            .into_iter()
+            .for_each(|(camera, render_target)| {
+                let Some(texture) = view.textures.get(&render_target.texture) else {
+                    return;
+                };
+                let (width, height) = (texture.texture.width(), texture.texture.height());
+                let state = self
+                    .states
+                    .entry(render_target.texture.clone())
+                    .or_insert_with(|| RenderTargetState {
+                        post_process: crate::postprocess::PostProcess::new(
+                            gpu,
+                            post_process_resources.clone(),
+                            width,
+                            height,
+                        ),
+                        msaa_texture_view: None,
+                        seconds_since_update: None,
+                    });
+
+                if !is_due(
+                    &mut state.seconds_since_update,
+                    render_target.updates_per_second,
+                    delta_time,
+                ) {
+                    return;
+                }
+
+                if state.post_process.size() != (width, height) {
+                    state.post_process.resize(gpu, width, height);
+                    state.msaa_texture_view = None;
+                }
+                if state.msaa_texture_view.is_none() {
+                    state.msaa_texture_view =
+                        gpu.create_msaa_texture(width, height, gpu.surface_format);
+                }
+
+                let Some(camera_matrices) = crate::view::create_camera_matrices_for_node(
+                    scene,
+                    camera,
+                    width as f32 / height as f32,
+                ) else {
+                    return;
+                };
+                view.prepare(gpu, scene, camera_matrices);
+                let texture_view = view.textures[&render_target.texture].texture.create_view(
+                    &wgpu::TextureViewDescriptor {
+                        format: Some(gpu.surface_format),
+                        ..Default::default()
+                    },
+                );
+                let (output_view, resolve_target) = match state.msaa_texture_view.as_ref() {
+                    Some(msaa_texture_view) => (msaa_texture_view, Some(&texture_view)),
+                    None => (&texture_view, None),
+                };
+                let mut encoder =
+                    gpu.device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("Render Target Encoder"),
+                        });
+                encoder.insert_debug_marker("Render camera target");
+                let camera_effects = scene.graph[camera]
+                    .components
+                    .iter()
+                    .find_map(|component| match component {
+                        crate::scene::NodeComponent::Camera(camera) => {
+                            camera.post_effects.as_deref()
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or(effects);
+                crate::render::render_view(
+                    &mut encoder,
+                    gpu,
+                    view,
+                    &state.post_process,
+                    None,
+                    camera_effects,
+                    output_view,
+                    resolve_target,
+                    None,
+                );
+                gpu.queue.submit(std::iter::once(encoder.finish()));
+            });
+    }
+}
+
+/// Targets are always rendered the first time, then once per update interval,
+/// carrying the time past each interval over so the average rate matches the requested one
+fn is_due(
+    seconds_since_update: &mut Option<f32>,
+    updates_per_second: f32,
+    delta_time: f32,
+) -> bool {
+    let interval = if updates_per_second > 0.0 {
+        1.0 / updates_per_second
+    } else {
+        0.0
+    };
+    let Some(seconds) = seconds_since_update.as_mut() else {
+        *seconds_since_update = Some(0.0);
+        return true;
+    };
+    *seconds += delta_time;
+    if *seconds < interval {
+        return false;
+    }
+    // A long frame catches up by at most one update rather than rendering several in a row
+    *seconds = (*seconds - interval).min(interval);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn updates_carry_the_remaining_time() {
+        let mut seconds_since_update = None;
+        assert!(super::is_due(&mut seconds_since_update, 10.0, 0.0));
+        // Frames that don't line up with the interval still update ten times a second
+        let updates = (0..60)
+            .filter(|_| super::is_due(&mut seconds_since_update, 10.0, 1.0 / 60.0))
+            .count();
+        assert_eq!(updates, 10);
+        assert!(super::is_due(&mut seconds_since_update, 0.0, 0.0));
+    }
+
+    #[test]
+    fn cameras_render_into_material_textures() {
+        let Some(crate::render::HelmetFixture {
+            mut renderer,
+            mut scene,
+            camera,
+            ..
+        }) = crate::render::HelmetFixture::new()
+        else {
+            return;
+        };
+        let render_target = crate::scene::CameraRenderTarget {
+            texture: "monitor".to_string(),
+            width: 32,
+            height: 32,
+            updates_per_second: 0.0,
+        };
+        scene.add_render_target_texture(&render_target);
+        scene.materials.values_mut().for_each(|material| {
+            material.base_color_texture = render_target.texture.clone();
+        });
+        // The monitor camera looks away from the helmet and only renders the clear color
+        let mut monitor_camera = crate::scene::create_camera_node(1.0);
+        monitor_camera.transform.rotation =
+            nalgebra_glm::quat_angle_axis(std::f32::consts::PI, &nalgebra_glm::Vec3::y());
+        monitor_camera.components.iter_mut().for_each(|component| {
+            if let crate::scene::NodeComponent::Camera(camera) = component {
+                camera.render_target = Some(render_target.clone());
+            }
+        });
+        scene.add_root_node(monitor_camera);
+        assert_eq!(crate::view::active_camera(&scene), Some(camera));
+
+        renderer.view.view_mode = crate::view::ViewMode::BaseColor;
+        renderer.view.clear_color = wgpu::Color::GREEN;
+        renderer.view.import_scene(&scene, &renderer.gpu);
+        let center = *renderer
+            .render_to_image(&scene, camera, 64, 64)
+            .get_pixel(32, 32);
+        assert!(
+            center[1] > 200 && center[0] < 30 && center[2] < 30,
+            "The helmet shows {center:?} instead of the monitor's green clear color"
+        );
+    }
+}
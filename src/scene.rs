@@ -8,6 +8,34 @@ pub struct Scene {
     pub meshes: std::collections::HashMap<String, Mesh>,
     pub animations: std::collections::HashMap<String, Animation>,
     pub skins: std::collections::HashMap<String, Skin>,
+    /// Lights the scene from every direction and is drawn behind it
+    #[serde(default)]
+    pub environment: Option<Environment>,
+}
+
+/// Image based lighting and a skybox from an equirectangular hdr image
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Environment {
+    /// The path of an `.hdr` image
+    pub path: String,
+    /// Scales the environment's light and the skybox
+    pub intensity: f32,
+    /// Whether the environment is drawn behind the scene instead of the clear color
+    pub skybox: bool,
+}
+
+impl Environment {
+    pub const DEFAULT_PATH: &'static str = "resources/textures/sky.hdr";
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self {
+            path: Self::DEFAULT_PATH.to_string(),
+            intensity: 1.0,
+            skybox: true,
+        }
+    }
 }
 
 pub fn create_camera_node(aspect_ratio: f32) -> Node {
@@ -33,24 +61,53 @@ pub fn create_camera_node(aspect_ratio: f32) -> Node {
                 sensitivity: nalgebra_glm::vec2(1.0, 1.0),
                 direction: nalgebra_glm::vec2(0_f32.to_radians(), 45_f32.to_radians()),
             },
+            post_effects: None,
+            viewport: None,
+            render_target: None,
         })],
     }
 }
 
 impl Scene {
+    /// Whether the scene has a camera to view it through,
+    /// which cameras rendering into a texture aren't
     pub fn has_camera(&self) -> bool {
         let mut has_camera = false;
         self.walk_dfs(|node, _| {
             for component in node.components.iter() {
-                if let crate::scene::NodeComponent::Camera(_) = component {
-                    has_camera = true;
-                    return;
+                if let crate::scene::NodeComponent::Camera(camera) = component {
+                    if camera.render_target.is_none() {
+                        has_camera = true;
+                        return;
+                    }
                 }
             }
         });
         has_camera
     }
 
+    /// Adds the texture a camera renders into, so materials can reference it like any other texture.
+    /// A single black pixel stands in for the image until the camera renders into it.
+    pub fn add_render_target_texture(&mut self, render_target: &CameraRenderTarget) {
+        self.images.insert(
+            render_target.texture.clone(),
+            Image {
+                pixels: vec![0, 0, 0, u8::MAX],
+                format: ImageFormat::R8G8B8A8,
+                width: 1,
+                height: 1,
+            },
+        );
+        self.textures.insert(
+            render_target.texture.clone(),
+            Texture {
+                label: render_target.texture.clone(),
+                image: render_target.texture.clone(),
+                sampler: String::new(),
+            },
+        );
+    }
+
     pub fn add_root_node(&mut self, node: crate::scene::Node) -> petgraph::graph::NodeIndex {
         let child = self.graph.add_node(node);
         self.graph
@@ -81,6 +138,38 @@ impl Scene {
         }
     }
 
+    /// Returns the world space bounds of a node's meshes,
+    /// if the node exists and has any
+    pub fn world_bounds(
+        &self,
+        node_index: petgraph::graph::NodeIndex,
+    ) -> Option<crate::geometry::Aabb> {
+        let local_bounds = self
+            .graph
+            .node_weight(node_index)?
+            .components
+            .iter()
+            .filter_map(|component| match component {
+                crate::scene::NodeComponent::Mesh(mesh_id) => self.meshes.get(mesh_id),
+                _ => None,
+            })
+            .fold(crate::geometry::Aabb::empty(), |bounds, mesh| {
+                bounds.merge(&mesh.bounding_box())
+            });
+        if local_bounds.is_empty() {
+            return None;
+        }
+        Some(local_bounds.transform(&self.graph.global_transform(node_index)))
+    }
+
+    /// Returns the world space bounds of all meshes in the scene, if there are any
+    pub fn bounds(&self) -> Option<crate::geometry::Aabb> {
+        self.graph
+            .node_indices()
+            .filter_map(|node_index| self.world_bounds(node_index))
+            .reduce(|bounds, node_bounds| bounds.merge(&node_bounds))
+    }
+
     pub fn flatten_geometry(
         &self,
     ) -> (
@@ -117,6 +206,11 @@ impl Scene {
                                 index_offset,
                                 vertices: number_of_vertices,
                                 indices: number_of_indices,
+                                material: primitive.material.to_string(),
+                                center: crate::geometry::Aabb::from_points(
+                                    primitive.vertices.iter().map(|vertex| &vertex.position),
+                                )
+                                .center(),
                             }
                         })
                         .collect::<Vec<_>>();
@@ -212,6 +306,62 @@ pub struct Primitive {
     pub indices: Vec<u32>,
 }
 
+impl Primitive {
+    /// Returns the vertex indices of each triangle,
+    /// unrolling strips and fans and handling non-indexed geometry.
+    /// Point and line primitives have no triangles.
+    pub fn triangles(&self) -> Vec<[u32; 3]> {
+        let indices = if self.indices.is_empty() {
+            (0..self.vertices.len() as u32).collect::<Vec<_>>()
+        } else {
+            self.indices.clone()
+        };
+        match self.mode {
+            PrimitiveMode::Triangles => indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+            PrimitiveMode::TriangleStrip => indices
+                .windows(3)
+                .enumerate()
+                .map(|(index, triangle)| {
+                    // Every other triangle in a strip has its winding flipped
+                    if index % 2 == 0 {
+                        [triangle[0], triangle[1], triangle[2]]
+                    } else {
+                        [triangle[1], triangle[0], triangle[2]]
+                    }
+                })
+                .collect(),
+            PrimitiveMode::TriangleFan => indices
+                .iter()
+                .skip(1)
+                .collect::<Vec<_>>()
+                .windows(2)
+                .map(|edge| [indices[0], *edge[0], *edge[1]])
+                .collect(),
+            PrimitiveMode::Points
+            | PrimitiveMode::Lines
+            | PrimitiveMode::LineLoop
+            | PrimitiveMode::LineStrip => Vec::new(),
+        }
+    }
+
+    pub fn bounding_box(&self) -> crate::geometry::Aabb {
+        crate::geometry::Aabb::from_points(self.vertices.iter().map(|vertex| &vertex.position))
+    }
+}
+
+impl Mesh {
+    pub fn bounding_box(&self) -> crate::geometry::Aabb {
+        self.primitives
+            .iter()
+            .fold(crate::geometry::Aabb::empty(), |aabb, primitive| {
+                aabb.merge(&primitive.bounding_box())
+            })
+    }
+}
+
 #[derive(Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Node {
     pub id: String,
@@ -232,6 +382,9 @@ pub enum NodeComponent {
     Camera(Camera),
     Mesh(String),
     Light(Light),
+    Collider(Collider),
+    RigidBody(RigidBody),
+    CharacterController(CharacterController),
 }
 
 #[derive(Copy, Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -321,6 +474,72 @@ impl Transform {
 pub struct Camera {
     pub projection: Projection,
     pub orientation: Orientation,
+    /// Post effects used instead of the renderer's when viewing through this camera
+    #[serde(default)]
+    pub post_effects: Option<Vec<crate::postprocess::PostEffect>>,
+    /// Where in the window the camera renders, for split screen and picture in picture views.
+    /// Cameras without a viewport only render when they are the active camera, filling the window.
+    #[serde(default)]
+    pub viewport: Option<CameraViewport>,
+    /// Renders into a texture materials can sample instead of into the window,
+    /// like for security monitors and mirrors
+    #[serde(default)]
+    pub render_target: Option<CameraRenderTarget>,
+}
+
+/// An offscreen texture a camera renders into, which materials reference by its texture id
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct CameraRenderTarget {
+    /// The id of the scene texture the camera renders into
+    pub texture: String,
+    pub width: u32,
+    pub height: u32,
+    /// How many times a second the texture is rendered, where zero renders it every frame
+    #[serde(default)]
+    pub updates_per_second: f32,
+}
+
+/// A region of the window in normalized coordinates with the origin at the top left,
+/// and the order cameras render in, where later cameras draw over earlier ones
+#[derive(Debug, serde::Serialize, serde::Deserialize, Copy, Clone, PartialEq)]
+pub struct CameraViewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    #[serde(default)]
+    pub order: i32,
+}
+
+impl Default for CameraViewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+impl CameraViewport {
+    /// The whole window
+    pub const FULL: Self = Self {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+        order: 0,
+    };
+
+    /// The viewport's region of an output of the given size in pixels
+    pub fn pixel_rect(&self, width: u32, height: u32) -> crate::view::PixelRect {
+        let to_pixels =
+            |value: f32, size: u32| (value.clamp(0.0, 1.0) * size as f32).round() as u32;
+        let x = to_pixels(self.x, width);
+        let y = to_pixels(self.y, height);
+        crate::view::PixelRect {
+            x,
+            y,
+            width: to_pixels(self.x + self.width, width).saturating_sub(x),
+            height: to_pixels(self.y + self.height, height).saturating_sub(y),
+        }
+    }
 }
 
 impl Camera {
@@ -371,26 +590,15 @@ pub struct OrthographicCamera {
 }
 
 impl OrthographicCamera {
+    /// Maps depth to the zero to one range of wgpu's clip space, like perspective cameras do
     pub fn matrix(&self) -> nalgebra_glm::Mat4 {
-        let z_sum = self.z_near + self.z_far;
-        let z_diff = self.z_near - self.z_far;
-        nalgebra_glm::Mat4::new(
-            1.0 / self.x_mag,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            1.0 / self.y_mag,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            2.0 / z_diff,
-            0.0,
-            0.0,
-            0.0,
-            z_sum / z_diff,
-            1.0,
+        nalgebra_glm::ortho_rh_zo(
+            -self.x_mag,
+            self.x_mag,
+            -self.y_mag,
+            self.y_mag,
+            self.z_near,
+            self.z_far,
         )
     }
 }
@@ -485,14 +693,46 @@ pub struct PrimitiveDrawCommand {
     pub index_offset: usize,
     pub vertices: usize,
     pub indices: usize,
+    pub material: String,
+    /// The center of the primitive's bounds, which blended primitives are sorted by
+    #[serde(default)]
+    pub center: nalgebra_glm::Vec3,
 }
 
 #[derive(Default, Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Light {
     pub intensity: f32,
+    /// The distance at which the light's influence reaches zero, or zero for infinite range
     pub range: f32,
     pub color: nalgebra_glm::Vec3,
     pub kind: LightKind,
+    #[serde(default)]
+    pub shadow: ShadowSettings,
+}
+
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+    /// Depth offset applied when comparing against the shadow map, to avoid shadow acne
+    pub bias: f32,
+    /// Offset along the surface normal in world units, scaled by the shadow map's texel size
+    pub normal_bias: f32,
+    /// Width and height of each of the light's shadow maps in texels, rounded down to a power of two
+    pub resolution: u32,
+    /// How far from the camera directional light shadows are cast
+    pub distance: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bias: 0.002,
+            normal_bias: 1.5,
+            resolution: 2048,
+            distance: 50.0,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
@@ -511,6 +751,327 @@ impl Default for LightKind {
     }
 }
 
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RigidBody {
+    pub kind: RigidBodyKind,
+    /// Total mass of the body, distributed across its solid colliders
+    pub mass: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub restitution: f32,
+    pub friction: f32,
+}
+
+impl Default for RigidBody {
+    fn default() -> Self {
+        Self {
+            kind: RigidBodyKind::default(),
+            mass: 1.0,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            restitution: 0.0,
+            friction: 0.5,
+        }
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum RigidBodyKind {
+    /// Moved by the simulation, which writes its transform back to the node
+    #[default]
+    Dynamic,
+    /// Moved by setting the node's transform, pushing dynamic bodies out of the way
+    Kinematic,
+    /// Never moves
+    Static,
+}
+
+/// A capsule that is moved by input rather than simulated,
+/// colliding with the static geometry of the physics world.
+/// The node's translation is the center of the capsule.
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CharacterController {
+    pub radius: f32,
+    /// Half the height of the capsule's cylindrical section
+    pub half_height: f32,
+    pub move_speed: f32,
+    pub jump_speed: f32,
+    pub gravity: f32,
+    /// Steepest slope in radians the character can walk up
+    pub max_slope_climb_angle: f32,
+    /// Slopes steeper than this in radians make the character slide down
+    pub min_slope_slide_angle: f32,
+    /// Tallest ledge the character automatically steps onto
+    pub step_height: f32,
+    /// Distance the character is pulled down to stay on the ground when walking down slopes
+    pub snap_to_ground: f32,
+    #[serde(skip)]
+    pub vertical_velocity: f32,
+    #[serde(skip)]
+    pub is_grounded: bool,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            radius: 0.3,
+            half_height: 0.6,
+            move_speed: 5.0,
+            jump_speed: 5.0,
+            gravity: 9.81,
+            max_slope_climb_angle: 45_f32.to_radians(),
+            min_slope_slide_angle: 30_f32.to_radians(),
+            step_height: 0.3,
+            snap_to_ground: 0.2,
+            vertical_velocity: 0.0,
+            is_grounded: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Collider {
+    pub shape: ColliderShape,
+    /// Bitmask of the collision layers this collider belongs to
+    pub layers: u32,
+    /// Bitmask of the collision layers this collider interacts with
+    pub mask: u32,
+    /// Triggers report overlaps without generating contact responses
+    pub is_trigger: bool,
+}
+
+impl Default for Collider {
+    fn default() -> Self {
+        Self {
+            shape: ColliderShape::default(),
+            layers: 1,
+            mask: u32::MAX,
+            is_trigger: false,
+        }
+    }
+}
+
+impl Collider {
+    pub fn interacts_with(&self, other: &Collider) -> bool {
+        (self.layers & other.mask) != 0 && (other.layers & self.mask) != 0
+    }
+}
+
+/// Collider shapes are in the local space of the node they are attached to,
+/// and capsules are aligned with the local y axis
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum ColliderShape {
+    Sphere {
+        radius: f32,
+    },
+    Box {
+        half_extents: nalgebra_glm::Vec3,
+        /// The box's offset from the node's origin
+        #[serde(default)]
+        center: nalgebra_glm::Vec3,
+    },
+    Capsule {
+        half_height: f32,
+        radius: f32,
+    },
+    ConvexHull {
+        points: Vec<nalgebra_glm::Vec3>,
+        indices: Vec<[u32; 3]>,
+    },
+    TriangleMesh {
+        vertices: Vec<nalgebra_glm::Vec3>,
+        indices: Vec<[u32; 3]>,
+    },
+}
+
+impl Default for ColliderShape {
+    fn default() -> Self {
+        Self::Sphere { radius: 0.5 }
+    }
+}
+
+impl ColliderShape {
+    /// Computes the convex hull of a set of points,
+    /// returning `None` if the points are degenerate (e.g. coplanar)
+    pub fn convex_hull(points: &[nalgebra_glm::Vec3]) -> Option<Self> {
+        let points = points
+            .iter()
+            .map(|point| parry3d::math::Point::from(*point))
+            .collect::<Vec<_>>();
+        let (hull_points, indices) = parry3d::transformation::try_convex_hull(&points).ok()?;
+        if indices.is_empty() {
+            return None;
+        }
+        Some(Self::ConvexHull {
+            points: hull_points.into_iter().map(|point| point.coords).collect(),
+            indices,
+        })
+    }
+
+    pub fn convex_hull_from_mesh(mesh: &Mesh) -> Option<Self> {
+        let points = mesh
+            .primitives
+            .iter()
+            .flat_map(|primitive| primitive.vertices.iter().map(|vertex| vertex.position))
+            .collect::<Vec<_>>();
+        Self::convex_hull(&points)
+    }
+
+    pub fn triangle_mesh_from_mesh(mesh: &Mesh) -> Self {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        mesh.primitives.iter().for_each(|primitive| {
+            let offset = vertices.len() as u32;
+            vertices.extend(primitive.vertices.iter().map(|vertex| vertex.position));
+            indices.extend(
+                primitive
+                    .triangles()
+                    .into_iter()
+                    .map(|triangle| triangle.map(|index| index + offset)),
+            );
+        });
+        Self::TriangleMesh { vertices, indices }
+    }
+
+    /// Fits a box around the mesh's bounds, offset to their center
+    pub fn box_from_mesh(mesh: &Mesh) -> Self {
+        let bounds = mesh.bounding_box();
+        if bounds.is_empty() {
+            return Self::Box {
+                half_extents: nalgebra_glm::vec3(0.5, 0.5, 0.5),
+                center: nalgebra_glm::Vec3::zeros(),
+            };
+        }
+        Self::Box {
+            half_extents: bounds.half_extents(),
+            center: bounds.center(),
+        }
+    }
+
+    pub fn bounding_box(&self) -> crate::geometry::Aabb {
+        match self {
+            Self::Sphere { radius } => crate::geometry::Aabb::new(
+                nalgebra_glm::Vec3::repeat(-radius),
+                nalgebra_glm::Vec3::repeat(*radius),
+            ),
+            Self::Box {
+                half_extents,
+                center,
+            } => crate::geometry::Aabb::new(center - half_extents, center + half_extents),
+            Self::Capsule {
+                half_height,
+                radius,
+            } => {
+                let half_extents = nalgebra_glm::vec3(*radius, half_height + radius, *radius);
+                crate::geometry::Aabb::new(-half_extents, half_extents)
+            }
+            Self::ConvexHull { points, .. } => crate::geometry::Aabb::from_points(points.iter()),
+            Self::TriangleMesh { vertices, .. } => {
+                crate::geometry::Aabb::from_points(vertices.iter())
+            }
+        }
+    }
+
+    /// Line segments outlining the shape, for visualization
+    pub fn wireframe(&self) -> Vec<[nalgebra_glm::Vec3; 2]> {
+        const SEGMENTS: usize = 24;
+        let circle = |center: nalgebra_glm::Vec3,
+                      radius: f32,
+                      axis_a: nalgebra_glm::Vec3,
+                      axis_b: nalgebra_glm::Vec3,
+                      from_angle: f32,
+                      to_angle: f32| {
+            let point = |index: usize| {
+                let angle = from_angle + (to_angle - from_angle) * index as f32 / SEGMENTS as f32;
+                center + (axis_a * angle.cos() + axis_b * angle.sin()) * radius
+            };
+            (0..SEGMENTS)
+                .map(|index| [point(index), point(index + 1)])
+                .collect::<Vec<_>>()
+        };
+        let (x, y, z) = (
+            nalgebra_glm::Vec3::x(),
+            nalgebra_glm::Vec3::y(),
+            nalgebra_glm::Vec3::z(),
+        );
+        let full_turn = std::f32::consts::TAU;
+        let half_turn = std::f32::consts::PI;
+
+        match self {
+            Self::Sphere { radius } => {
+                let center = nalgebra_glm::Vec3::zeros();
+                [
+                    circle(center, *radius, x, y, 0.0, full_turn),
+                    circle(center, *radius, y, z, 0.0, full_turn),
+                    circle(center, *radius, z, x, 0.0, full_turn),
+                ]
+                .concat()
+            }
+            Self::Box { .. } => {
+                let corners = self.bounding_box().corners();
+                [
+                    (0, 1),
+                    (2, 3),
+                    (4, 5),
+                    (6, 7),
+                    (0, 2),
+                    (1, 3),
+                    (4, 6),
+                    (5, 7),
+                    (0, 4),
+                    (1, 5),
+                    (2, 6),
+                    (3, 7),
+                ]
+                .iter()
+                .map(|(a, b)| [corners[*a], corners[*b]])
+                .collect()
+            }
+            Self::Capsule {
+                half_height,
+                radius,
+            } => {
+                let top = y * *half_height;
+                let bottom = -top;
+                let mut lines = [
+                    circle(top, *radius, z, x, 0.0, full_turn),
+                    circle(bottom, *radius, z, x, 0.0, full_turn),
+                    circle(top, *radius, x, y, 0.0, half_turn),
+                    circle(top, *radius, z, y, 0.0, half_turn),
+                    circle(bottom, *radius, x, y, half_turn, full_turn),
+                    circle(bottom, *radius, z, y, half_turn, full_turn),
+                ]
+                .concat();
+                lines.extend(
+                    [x, -x, z, -z]
+                        .iter()
+                        .map(|side| [top + side * *radius, bottom + side * *radius]),
+                );
+                lines
+            }
+            Self::ConvexHull {
+                points: vertices,
+                indices,
+            }
+            | Self::TriangleMesh { vertices, indices } => {
+                let mut edges = std::collections::HashSet::new();
+                indices.iter().for_each(|triangle| {
+                    [(0, 1), (1, 2), (2, 0)].iter().for_each(|(a, b)| {
+                        let (a, b) = (triangle[*a], triangle[*b]);
+                        edges.insert((a.min(b), a.max(b)));
+                    });
+                });
+                edges
+                    .into_iter()
+                    .filter_map(|(a, b)| {
+                        Some([*vertices.get(a as usize)?, *vertices.get(b as usize)?])
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Texture {
     pub label: String,
@@ -552,7 +1113,7 @@ pub enum ImageFormat {
     R32G32B32A32F,
 }
 
-#[derive(Default, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Default, Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct Sampler {
     pub min_filter: Filter,
     pub mag_filter: Filter,
@@ -560,7 +1121,7 @@ pub struct Sampler {
     pub wrap_t: WrappingMode,
 }
 
-#[derive(Default, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Default, Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub enum WrappingMode {
     ClampToEdge,
     MirroredRepeat,
@@ -568,20 +1129,148 @@ pub enum WrappingMode {
     Repeat,
 }
 
-#[derive(Default, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Default, Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub enum Filter {
     #[default]
     Nearest,
     Linear,
 }
 
-#[derive(Default, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Material {
     pub base_color_factor: nalgebra_glm::Vec4,
     pub base_color_texture: String,
+    /// Light emitted by the surface in linear hdr units, multiplied by the emissive texture
+    #[serde(default)]
+    pub emissive_factor: nalgebra_glm::Vec3,
+    #[serde(default)]
+    pub emissive_texture: String,
+    #[serde(default = "default_factor")]
+    pub metallic_factor: f32,
+    #[serde(default = "default_factor")]
+    pub roughness_factor: f32,
+    /// Metalness is read from the blue channel and roughness from the green channel
+    #[serde(default)]
+    pub metallic_roughness_texture: String,
+    #[serde(default)]
+    pub alpha_mode: AlphaMode,
+    /// Fragments of masked materials with a lower alpha are discarded
+    #[serde(default = "default_alpha_cutoff")]
+    pub alpha_cutoff: f32,
+    /// Double sided materials are drawn without culling back faces
+    #[serde(default)]
+    pub double_sided: bool,
+    /// The name of the registered material shader drawing the material,
+    /// or none for the built in shader
+    #[serde(default)]
+    pub shader: Option<String>,
+    /// Values read by the material's shader, keyed by name
+    #[serde(default)]
+    pub parameters: std::collections::HashMap<String, MaterialParameter>,
 }
 
-#[derive(Default, Clone, Debug, serde::Serialize, serde::Deserialize)]
+impl Material {
+    /// The value of a float parameter, if the material has one with the name
+    pub fn float_parameter(&self, name: &str) -> Option<f32> {
+        match self.parameters.get(name) {
+            Some(MaterialParameter::Float(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of a signed integer parameter, if the material has one with the name
+    pub fn int_parameter(&self, name: &str) -> Option<i32> {
+        match self.parameters.get(name) {
+            Some(MaterialParameter::Int(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of an unsigned integer parameter, if the material has one with the name
+    pub fn uint_parameter(&self, name: &str) -> Option<u32> {
+        match self.parameters.get(name) {
+            Some(MaterialParameter::UInt(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of a two component vector parameter, if the material has one with the name
+    pub fn vec2_parameter(&self, name: &str) -> Option<nalgebra_glm::Vec2> {
+        match self.parameters.get(name) {
+            Some(MaterialParameter::Vec2(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of a three component vector parameter, if the material has one with the name
+    pub fn vec3_parameter(&self, name: &str) -> Option<nalgebra_glm::Vec3> {
+        match self.parameters.get(name) {
+            Some(MaterialParameter::Vec3(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of a four component vector parameter, if the material has one with the name
+    pub fn vec4_parameter(&self, name: &str) -> Option<nalgebra_glm::Vec4> {
+        match self.parameters.get(name) {
+            Some(MaterialParameter::Vec4(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The id of a texture parameter, if the material has one with the name
+    pub fn texture_parameter(&self, name: &str) -> Option<&str> {
+        match self.parameters.get(name) {
+            Some(MaterialParameter::Texture(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+}
+
+/// A typed value a material passes to its shader
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum MaterialParameter {
+    Float(f32),
+    Int(i32),
+    UInt(u32),
+    Vec2(nalgebra_glm::Vec2),
+    Vec3(nalgebra_glm::Vec3),
+    Vec4(nalgebra_glm::Vec4),
+    /// The id of one of the scene's textures
+    Texture(String),
+}
+
+fn default_alpha_cutoff() -> f32 {
+    0.5
+}
+
+fn default_factor() -> f32 {
+    1.0
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color_factor: nalgebra_glm::vec4(1.0, 1.0, 1.0, 1.0),
+            base_color_texture: String::new(),
+            emissive_factor: nalgebra_glm::Vec3::zeros(),
+            emissive_texture: String::new(),
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: String::new(),
+            alpha_mode: AlphaMode::default(),
+            alpha_cutoff: default_alpha_cutoff(),
+            double_sided: false,
+            shader: None,
+            parameters: std::collections::HashMap::new(),
+        }
+    }
+}
+
+/// How a material's alpha is used, where the discriminants are the values shaders see
+#[derive(
+    Default, Copy, Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
 pub enum AlphaMode {
     #[default]
     Opaque = 1,
@@ -632,3 +1321,106 @@ pub struct Joint {
     pub target: String,
     pub inverse_bind_matrix: nalgebra_glm::Mat4,
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn box_from_mesh_is_offset_to_the_mesh_center() {
+        let vertex = |x: f32, y: f32, z: f32| crate::scene::Vertex {
+            position: nalgebra_glm::vec3(x, y, z),
+            ..Default::default()
+        };
+        let mesh = crate::scene::Mesh {
+            label: "Off center".to_string(),
+            primitives: vec![crate::scene::Primitive {
+                vertices: vec![vertex(2.0, 0.0, -1.0), vertex(4.0, 1.0, 1.0)],
+                ..Default::default()
+            }],
+        };
+        let shape = crate::scene::ColliderShape::box_from_mesh(&mesh);
+        assert_eq!(
+            shape,
+            crate::scene::ColliderShape::Box {
+                half_extents: nalgebra_glm::vec3(1.0, 0.5, 1.0),
+                center: nalgebra_glm::vec3(3.0, 0.5, 0.0),
+            }
+        );
+        assert_eq!(shape.bounding_box(), mesh.bounding_box());
+
+        // The physics shape keeps the offset, scaled with the node
+        let aabb = crate::physics::create_shape(&shape, &nalgebra_glm::vec3(2.0, 2.0, 2.0))
+            .expect("Box colliders should have a shape!")
+            .compute_local_aabb();
+        assert_eq!(aabb.center().coords, nalgebra_glm::vec3(6.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn colliders_interact_when_layers_and_masks_overlap() {
+        let player = crate::scene::Collider {
+            layers: 0b01,
+            mask: 0b10,
+            ..Default::default()
+        };
+        let wall = crate::scene::Collider {
+            layers: 0b10,
+            mask: 0b01,
+            ..Default::default()
+        };
+        let ghost = crate::scene::Collider {
+            layers: 0b100,
+            mask: 0b01,
+            ..Default::default()
+        };
+        assert!(player.interacts_with(&wall));
+        assert!(wall.interacts_with(&player));
+        assert!(!player.interacts_with(&ghost));
+        assert!(!ghost.interacts_with(&player));
+    }
+
+    #[test]
+    fn colliders_round_trip_through_serde() {
+        let collider = crate::scene::Collider {
+            shape: crate::scene::ColliderShape::Box {
+                half_extents: nalgebra_glm::vec3(1.0, 2.0, 3.0),
+                center: nalgebra_glm::vec3(0.5, -0.5, 0.0),
+            },
+            layers: 0b10,
+            mask: 0b11,
+            is_trigger: true,
+        };
+        let bytes = bincode::serialize(&collider).expect("Failed to serialize collider!");
+        let deserialized: crate::scene::Collider =
+            bincode::deserialize(&bytes).expect("Failed to deserialize collider!");
+        assert_eq!(deserialized, collider);
+    }
+
+    #[test]
+    fn material_parameters_are_read_by_type() {
+        let mut material = crate::scene::Material::default();
+        material.parameters.extend([
+            ("int".to_string(), crate::scene::MaterialParameter::Int(-2)),
+            ("uint".to_string(), crate::scene::MaterialParameter::UInt(3)),
+            (
+                "vec2".to_string(),
+                crate::scene::MaterialParameter::Vec2(nalgebra_glm::vec2(1.0, 2.0)),
+            ),
+            (
+                "vec3".to_string(),
+                crate::scene::MaterialParameter::Vec3(nalgebra_glm::vec3(1.0, 2.0, 3.0)),
+            ),
+        ]);
+        assert_eq!(material.int_parameter("int"), Some(-2));
+        assert_eq!(material.uint_parameter("uint"), Some(3));
+        assert_eq!(
+            material.vec2_parameter("vec2"),
+            Some(nalgebra_glm::vec2(1.0, 2.0))
+        );
+        assert_eq!(
+            material.vec3_parameter("vec3"),
+            Some(nalgebra_glm::vec3(1.0, 2.0, 3.0))
+        );
+        // Parameters of another type aren't converted
+        assert_eq!(material.uint_parameter("int"), None);
+        assert_eq!(material.vec4_parameter("vec3"), None);
+    }
+}
@@ -0,0 +1,328 @@
+#[derive(Debug, thiserror::Error)]
+pub enum ShaderError {
+    #[error("Failed to read shader '{path}': {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("Failed to parse shader '{path}':\n{message}")]
+    Parse { path: String, message: String },
+    #[error("Failed to validate shader '{path}':\n{message}")]
+    Validate { path: String, message: String },
+    #[error("Failed to preprocess shader '{path}' at line {line}: {message}")]
+    Preprocess {
+        path: String,
+        line: usize,
+        message: String,
+    },
+    #[error("Failed to create pipelines for shader '{path}':\n{message}")]
+    Pipeline { path: String, message: String },
+}
+
+/// Names defined for the preprocessor, with the text they are replaced by.
+/// Names with an empty value are only checked by `#ifdef` and `#ifndef`.
+pub type ShaderDefines = std::collections::BTreeMap<String, String>;
+
+/// Expands the preprocessor directives of a wgsl source, which are
+/// `#include "name"`, `#define NAME [value]`, `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`.
+///
+/// Includes are looked up by name in the given sources and are only expanded the first time,
+/// so shared declarations can be included by every source that needs them.
+pub fn preprocess(
+    path: &str,
+    source: &str,
+    defines: &ShaderDefines,
+    includes: &std::collections::HashMap<String, String>,
+) -> Result<String, ShaderError> {
+    let mut defines = defines.clone();
+    let mut included = std::collections::HashSet::new();
+    let mut output = String::with_capacity(source.len());
+    expand(
+        path,
+        source,
+        &mut defines,
+        includes,
+        &mut included,
+        &mut output,
+    )?;
+    Ok(output)
+}
+
+/// A conditional block being expanded
+struct Branch {
+    active: bool,
+    has_else: bool,
+}
+
+fn expand(
+    path: &str,
+    source: &str,
+    defines: &mut ShaderDefines,
+    includes: &std::collections::HashMap<String, String>,
+    included: &mut std::collections::HashSet<String>,
+    output: &mut String,
+) -> Result<(), ShaderError> {
+    let mut branches: Vec<Branch> = Vec::new();
+    for (line_index, line) in source.lines().enumerate() {
+        let error = |message: String| ShaderError::Preprocess {
+            path: path.to_string(),
+            line: line_index + 1,
+            message,
+        };
+        let active = branches.iter().all(|branch| branch.active);
+        let Some(directive) = line.trim().strip_prefix('#') else {
+            if active {
+                output.push_str(&substitute(line, defines));
+                output.push('\n');
+            }
+            continue;
+        };
+        let (name, argument) = directive
+            .split_once(char::is_whitespace)
+            .map(|(name, argument)| (name, argument.trim()))
+            .unwrap_or((directive, ""));
+        match name {
+            "ifdef" | "ifndef" => {
+                if argument.is_empty() {
+                    return Err(error(format!("#{name} requires a name")));
+                }
+                branches.push(Branch {
+                    active: defines.contains_key(argument) == (name == "ifdef"),
+                    has_else: false,
+                });
+            }
+            "else" => match branches.last_mut() {
+                Some(branch) if !branch.has_else => {
+                    branch.active = !branch.active;
+                    branch.has_else = true;
+                }
+                Some(_) => return Err(error("#else appears twice".to_string())),
+                None => return Err(error("#else without #ifdef".to_string())),
+            },
+            "endif" => {
+                if branches.pop().is_none() {
+                    return Err(error("#endif without #ifdef".to_string()));
+                }
+            }
+            _ if !active => {}
+            "define" => {
+                let (define, value) = argument
+                    .split_once(char::is_whitespace)
+                    .map(|(define, value)| (define, value.trim()))
+                    .unwrap_or((argument, ""));
+                if define.is_empty() {
+                    return Err(error("#define requires a name".to_string()));
+                }
+                defines.insert(define.to_string(), value.to_string());
+            }
+            "include" => {
+                let include = argument.trim_matches('"');
+                let Some(include_source) = includes.get(include) else {
+                    return Err(error(format!("Unknown include '{include}'")));
+                };
+                if included.insert(include.to_string()) {
+                    expand(include, include_source, defines, includes, included, output)?;
+                }
+            }
+            _ => return Err(error(format!("Unknown directive '#{name}'"))),
+        }
+    }
+    if !branches.is_empty() {
+        return Err(ShaderError::Preprocess {
+            path: path.to_string(),
+            line: source.lines().count(),
+            message: "#ifdef without #endif".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Replaces every identifier defined with a value by that value
+fn substitute(line: &str, defines: &ShaderDefines) -> String {
+    let is_identifier = |character: char| character.is_ascii_alphanumeric() || character == '_';
+    let mut output = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find(is_identifier) {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(|character| !is_identifier(character))
+            .unwrap_or(rest.len());
+        let word = &rest[..end];
+        match defines.get(word) {
+            Some(value) if !value.is_empty() => output.push_str(value),
+            _ => output.push_str(word),
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Parses and validates wgsl with naga, so broken shaders are reported
+/// instead of failing pipeline creation
+pub fn validate(path: &str, source: &str) -> Result<(), ShaderError> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|error| ShaderError::Parse {
+        path: path.to_string(),
+        message: error.emit_to_string_with_path(source, path),
+    })?;
+    // Capabilities are checked by the device when the pipeline is created,
+    // such as the texture arrays of bindless materials
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|error| ShaderError::Validate {
+        path: path.to_string(),
+        message: error.emit_to_string_with_path(source, path),
+    })?;
+    Ok(())
+}
+
+/// Creates pipelines from a shader, returning them only if the device reported no validation errors,
+/// which catches what naga accepts but the device doesn't, such as unsupported capabilities
+pub fn create_validated<T>(
+    gpu: &crate::gpu::Gpu,
+    path: &str,
+    create: impl FnOnce() -> T,
+) -> Result<T, ShaderError> {
+    gpu.device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let created = create();
+    match pollster::block_on(gpu.device.pop_error_scope()) {
+        Some(error) => Err(ShaderError::Pipeline {
+            path: path.to_string(),
+            message: error.to_string(),
+        }),
+        None => Ok(created),
+    }
+}
+
+/// A wgsl file on disk that is checked for changes by its modification time
+pub struct ShaderFile {
+    pub path: String,
+    modified: Option<std::time::SystemTime>,
+}
+
+impl ShaderFile {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            modified: Self::modified_time(path),
+        }
+    }
+
+    /// Reads the file, falling back to the source compiled into the binary
+    /// when it can't be read, such as when running away from the repository
+    pub fn load(&self, embedded_source: &str) -> String {
+        std::fs::read_to_string(&self.path).unwrap_or_else(|_| embedded_source.to_string())
+    }
+
+    /// Returns the file's source if it was modified since it was last checked
+    pub fn poll(&mut self) -> Option<Result<String, ShaderError>> {
+        let modified = Self::modified_time(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(
+            std::fs::read_to_string(&self.path).map_err(|source| ShaderError::Read {
+                path: self.path.clone(),
+                source,
+            }),
+        )
+    }
+
+    fn modified_time(path: &str) -> Option<std::time::SystemTime> {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn changed_shaders_are_validated() {
+        let path = std::env::temp_dir().join(format!("{}.wgsl", uuid::Uuid::new_v4()));
+        let path = path
+            .to_str()
+            .expect("The temporary path should be valid utf-8!");
+        std::fs::write(path, "fn value() -> f32 { return 1.0; }").expect("Failed to write shader!");
+        let mut shader_file = super::ShaderFile::new(path);
+        assert!(shader_file.poll().is_none());
+
+        // Modification times can be coarse, so the change is dated explicitly
+        let broken = "fn value() -> f32 { return missing; }";
+        std::fs::write(path, broken).expect("Failed to write shader!");
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| {
+                file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(1))
+            })
+            .expect("Failed to update the shader's modification time!");
+        let source = shader_file
+            .poll()
+            .expect("The shader should have changed!")
+            .expect("The shader should be readable!");
+        assert_eq!(source, broken);
+        assert!(shader_file.poll().is_none());
+        assert!(matches!(
+            super::validate(path, &source),
+            Err(super::ShaderError::Parse { .. })
+        ));
+        assert!(super::validate(path, "fn value() -> f32 { return 1.0; }").is_ok());
+        std::fs::remove_file(path).expect("Failed to remove shader!");
+    }
+
+    #[test]
+    fn directives_expand_with_defines_and_includes() {
+        let includes = std::collections::HashMap::from([(
+            "common.wgsl".to_string(),
+            "#define SCALE 2.0\nstruct Common { value: f32 };".to_string(),
+        )]);
+        let source = "\
+#include \"common.wgsl\"
+#include \"common.wgsl\"
+#ifdef MASKED
+fn scale() -> f32 { return SCALE; }
+#else
+fn scale() -> f32 { return 1.0; }
+#endif
+#ifndef MASKED
+fn unmasked() {}
+#endif
+";
+        let masked = super::preprocess(
+            "test.wgsl",
+            source,
+            &super::ShaderDefines::from([("MASKED".to_string(), String::new())]),
+            &includes,
+        )
+        .expect("Failed to preprocess shader!");
+        assert_eq!(
+            masked,
+            "struct Common { value: f32 };\nfn scale() -> f32 { return 2.0; }\n"
+        );
+
+        let unmasked =
+            super::preprocess("test.wgsl", source, &super::ShaderDefines::new(), &includes)
+                .expect("Failed to preprocess shader!");
+        assert_eq!(
+            unmasked,
+            "struct Common { value: f32 };\nfn scale() -> f32 { return 1.0; }\nfn unmasked() {}\n"
+        );
+        assert!(super::validate("test.wgsl", &unmasked).is_ok());
+
+        assert!(matches!(
+            super::preprocess(
+                "test.wgsl",
+                "#ifdef MASKED\n",
+                &super::ShaderDefines::new(),
+                &includes
+            ),
+            Err(super::ShaderError::Preprocess { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,562 @@
+/// How the color values of an image are encoded
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Color textures such as base color, which are decoded to linear values when sampled
+    #[default]
+    Srgb,
+    /// Data textures such as normal, metallic-roughness and occlusion maps
+    Linear,
+}
+
+/// Uploads scene images to the gpu with full mip chains,
+/// and shares one sampler between all textures using the same sampling parameters
+pub struct TextureUploader {
+    samplers: std::collections::HashMap<crate::scene::Sampler, std::sync::Arc<wgpu::Sampler>>,
+    mipmap_shader: wgpu::ShaderModule,
+    mipmap_bind_group_layout: wgpu::BindGroupLayout,
+    mipmap_pipelines: std::collections::HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl TextureUploader {
+    pub fn new(gpu: &crate::gpu::Gpu) -> Self {
+        let mipmap_shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Mipmap Shader"),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(MIPMAP_SHADER_SOURCE)),
+            });
+        let mipmap_bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Mipmap Bind Group Layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    }],
+                });
+        Self {
+            samplers: std::collections::HashMap::new(),
+            mipmap_shader,
+            mipmap_bind_group_layout,
+            mipmap_pipelines: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Uploads every texture in the scene, keyed by texture id.
+    /// Textures used as a material's base or emissive color are treated as sRGB,
+    /// all others as linear.
+    pub fn upload_scene(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        scene: &crate::scene::Scene,
+    ) -> std::collections::HashMap<String, crate::render::Texture> {
+        let color_textures = scene
+            .materials
+            .values()
+            .flat_map(|material| [&material.base_color_texture, &material.emissive_texture])
+            .collect::<std::collections::HashSet<_>>();
+        scene
+            .textures
+            .iter()
+            .filter_map(|(texture_id, texture)| {
+                let Some(image) = scene.images.get(&texture.image) else {
+                    log::warn!(
+                        "Texture '{texture_id}' refers to missing image '{}'",
+                        texture.image
+                    );
+                    return None;
+                };
+                let sampler = scene
+                    .samplers
+                    .get(&texture.sampler)
+                    .cloned()
+                    .unwrap_or_default();
+                let color_space = if color_textures.contains(texture_id) {
+                    ColorSpace::Srgb
+                } else {
+                    ColorSpace::Linear
+                };
+                let gpu_texture = self.upload_image(gpu, image, color_space, &texture.label);
+                let view = gpu_texture.create_view(&wgpu::TextureViewDescriptor::default());
+                Some((
+                    texture_id.to_string(),
+                    crate::render::Texture {
+                        texture: gpu_texture,
+                        view,
+                        sampler: self.sampler(gpu, &sampler),
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// Uploads an image and generates its mip chain
+    pub fn upload_image(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        image: &crate::scene::Image,
+        color_space: ColorSpace,
+        label: &str,
+    ) -> wgpu::Texture {
+        let (format, pixels) = convert_image(image, color_space, gpu.device.features());
+        let (width, height) = (image.width.max(1), image.height.max(1));
+
+        // Formats that can't be rendered to have their mip chain downsampled on the cpu instead
+        let renderable = format
+            .guaranteed_format_features(gpu.device.features())
+            .allowed_usages
+            .contains(wgpu::TextureUsages::RENDER_ATTACHMENT);
+        let levels = if renderable {
+            vec![pixels]
+        } else if let Some(levels) = downsample_mip_levels(format, &pixels, width, height) {
+            levels
+        } else {
+            log::warn!("Mipmaps can't be generated for texture '{label}' with format {format:?}");
+            vec![pixels]
+        };
+        let mip_level_count = if renderable {
+            mip_level_count(width, height)
+        } else {
+            levels.len() as u32
+        };
+
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        if renderable && mip_level_count > 1 {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        let bytes_per_pixel = format
+            .block_size(None)
+            .expect("Uploaded texture formats are never depth or stencil formats!");
+        levels.iter().enumerate().for_each(|(mip_level, pixels)| {
+            let (width, height) = mip_level_size(width, height, mip_level as u32);
+            gpu.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                pixels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(width * bytes_per_pixel),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        });
+
+        if renderable && mip_level_count > 1 {
+            self.generate_mipmaps(gpu, &texture, format, mip_level_count);
+        }
+
+        texture
+    }
+
+    /// Returns the sampler for the given sampling parameters, creating it on first use
+    pub fn sampler(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        sampler: &crate::scene::Sampler,
+    ) -> std::sync::Arc<wgpu::Sampler> {
+        self.samplers
+            .entry(sampler.clone())
+            .or_insert_with(|| {
+                std::sync::Arc::new(gpu.device.create_sampler(&sampler.clone().into()))
+            })
+            .clone()
+    }
+
+    /// Renders each mip level by downsampling the level above it
+    fn generate_mipmaps(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        texture: &wgpu::Texture,
+        format: wgpu::TextureFormat,
+        mip_level_count: u32,
+    ) {
+        let Self {
+            mipmap_shader,
+            mipmap_bind_group_layout,
+            mipmap_pipelines,
+            ..
+        } = self;
+        let pipeline = mipmap_pipelines.entry(format).or_insert_with(|| {
+            create_mipmap_pipeline(gpu, mipmap_shader, mipmap_bind_group_layout, format)
+        });
+
+        let mip_views = (0..mip_level_count)
+            .map(|mip_level| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Mip Level View"),
+                    base_mip_level: mip_level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Mipmap Encoder"),
+            });
+        mip_views.windows(2).for_each(|views| {
+            let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Mipmap Bind Group"),
+                layout: mipmap_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&views[0]),
+                }],
+            });
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Mipmap Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &views[1],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        });
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+/// The number of mip levels needed to reduce an image down to a single pixel
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+/// The size of a mip level, which halves with each level down to a single pixel
+pub fn mip_level_size(width: u32, height: u32, mip_level: u32) -> (u32, u32) {
+    ((width >> mip_level).max(1), (height >> mip_level).max(1))
+}
+
+/// Downsamples converted pixels into a full mip chain on the cpu,
+/// returning `None` for formats without a matching cpu pixel type
+pub fn downsample_mip_levels(
+    format: wgpu::TextureFormat,
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+) -> Option<Vec<Vec<u8>>> {
+    let channels = || {
+        pixels
+            .chunks_exact(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect::<Vec<_>>()
+    };
+    match format {
+        wgpu::TextureFormat::R16Unorm => {
+            Some(downsample::<image::Luma<u16>>(channels(), width, height))
+        }
+        wgpu::TextureFormat::Rg16Unorm => {
+            Some(downsample::<image::LumaA<u16>>(channels(), width, height))
+        }
+        wgpu::TextureFormat::Rgba16Unorm => {
+            Some(downsample::<image::Rgba<u16>>(channels(), width, height))
+        }
+        _ => None,
+    }
+}
+
+/// Resizes each mip level from the level above it
+fn downsample<P: image::Pixel<Subpixel = u16> + 'static>(
+    channels: Vec<u16>,
+    width: u32,
+    height: u32,
+) -> Vec<Vec<u8>> {
+    let mut level = image::ImageBuffer::<P, _>::from_raw(width, height, channels)
+        .expect("The pixels don't fill the image!");
+    let mut levels = vec![bytemuck::cast_slice(level.as_raw()).to_vec()];
+    (1..mip_level_count(width, height)).for_each(|mip_level| {
+        let (width, height) = mip_level_size(width, height, mip_level);
+        level =
+            image::imageops::resize(&level, width, height, image::imageops::FilterType::Triangle);
+        levels.push(bytemuck::cast_slice(level.as_raw()).to_vec());
+    });
+    levels
+}
+
+/// Converts an image into a texture format wgpu supports, returning the format and converted pixels.
+///
+/// Three channel images are expanded to four channels, since wgpu has no three channel formats.
+/// Grayscale color images are expanded to RGBA so they sample as gray instead of red.
+/// 16-bit color images are narrowed to 8 bits because there are no 16-bit sRGB formats,
+/// as are 16-bit data images on devices without 16-bit normalized texture support.
+pub fn convert_image(
+    image: &crate::scene::Image,
+    color_space: ColorSpace,
+    features: wgpu::Features,
+) -> (wgpu::TextureFormat, Vec<u8>) {
+    use crate::scene::ImageFormat;
+
+    let srgb = color_space == ColorSpace::Srgb;
+    let rgba8 = if srgb {
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    } else {
+        wgpu::TextureFormat::Rgba8Unorm
+    };
+    let bgra8 = if srgb {
+        wgpu::TextureFormat::Bgra8UnormSrgb
+    } else {
+        wgpu::TextureFormat::Bgra8Unorm
+    };
+    let pixels = &image.pixels;
+
+    match image.format {
+        ImageFormat::R16
+        | ImageFormat::R16G16
+        | ImageFormat::R16G16B16
+        | ImageFormat::R16G16B16A16
+            if srgb || !features.contains(wgpu::Features::TEXTURE_FORMAT_16BIT_NORM) =>
+        {
+            let format = match image.format {
+                ImageFormat::R16 => ImageFormat::R8,
+                ImageFormat::R16G16 => ImageFormat::R8G8,
+                ImageFormat::R16G16B16 => ImageFormat::R8G8B8,
+                _ => ImageFormat::R8G8B8A8,
+            };
+            let narrowed = crate::scene::Image {
+                pixels: pixels.chunks_exact(2).map(|channel| channel[1]).collect(),
+                format,
+                width: image.width,
+                height: image.height,
+            };
+            convert_image(&narrowed, color_space, features)
+        }
+
+        ImageFormat::R8 if srgb => (rgba8, grayscale_to_rgba(pixels, false)),
+        ImageFormat::R8G8 if srgb => (rgba8, grayscale_to_rgba(pixels, true)),
+        ImageFormat::R8 => (wgpu::TextureFormat::R8Unorm, pixels.to_vec()),
+        ImageFormat::R8G8 => (wgpu::TextureFormat::Rg8Unorm, pixels.to_vec()),
+        ImageFormat::R8G8B8 => (rgba8, expand_to_four_channels(pixels, &[u8::MAX])),
+        ImageFormat::R8G8B8A8 => (rgba8, pixels.to_vec()),
+        ImageFormat::B8G8R8 => (bgra8, expand_to_four_channels(pixels, &[u8::MAX])),
+        ImageFormat::B8G8R8A8 => (bgra8, pixels.to_vec()),
+
+        ImageFormat::R16 => (wgpu::TextureFormat::R16Unorm, pixels.to_vec()),
+        ImageFormat::R16G16 => (wgpu::TextureFormat::Rg16Unorm, pixels.to_vec()),
+        ImageFormat::R16G16B16 => (
+            wgpu::TextureFormat::Rgba16Unorm,
+            expand_to_four_channels(pixels, &u16::MAX.to_le_bytes()),
+        ),
+        ImageFormat::R16G16B16A16 => (wgpu::TextureFormat::Rgba16Unorm, pixels.to_vec()),
+
+        ImageFormat::R16F => (wgpu::TextureFormat::R16Float, pixels.to_vec()),
+        ImageFormat::R16G16F => (wgpu::TextureFormat::Rg16Float, pixels.to_vec()),
+        ImageFormat::R16G16B16F => (
+            wgpu::TextureFormat::Rgba16Float,
+            // 1.0 as a little endian half precision float
+            expand_to_four_channels(pixels, &[0x00, 0x3c]),
+        ),
+        ImageFormat::R16G16B16A16F => (wgpu::TextureFormat::Rgba16Float, pixels.to_vec()),
+
+        // 32-bit images are only produced from floating point data
+        ImageFormat::R32 | ImageFormat::R32F => (wgpu::TextureFormat::R32Float, pixels.to_vec()),
+        ImageFormat::R32G32 | ImageFormat::R32G32F => {
+            (wgpu::TextureFormat::Rg32Float, pixels.to_vec())
+        }
+        ImageFormat::R32G32B32 | ImageFormat::R32G32B32F => (
+            wgpu::TextureFormat::Rgba32Float,
+            expand_to_four_channels(pixels, &1.0_f32.to_le_bytes()),
+        ),
+        ImageFormat::R32G32B32A32 | ImageFormat::R32G32B32A32F => {
+            (wgpu::TextureFormat::Rgba32Float, pixels.to_vec())
+        }
+    }
+}
+
+/// Appends an alpha channel to every pixel of three channel image data
+fn expand_to_four_channels(pixels: &[u8], alpha: &[u8]) -> Vec<u8> {
+    pixels
+        .chunks_exact(alpha.len() * 3)
+        .flat_map(|pixel| pixel.iter().chain(alpha).copied())
+        .collect()
+}
+
+fn grayscale_to_rgba(pixels: &[u8], has_alpha: bool) -> Vec<u8> {
+    let channels = if has_alpha { 2 } else { 1 };
+    pixels
+        .chunks_exact(channels)
+        .flat_map(|pixel| {
+            let alpha = if has_alpha { pixel[1] } else { u8::MAX };
+            [pixel[0], pixel[0], pixel[0], alpha]
+        })
+        .collect()
+}
+
+fn create_mipmap_pipeline(
+    gpu: &crate::gpu::Gpu,
+    shader: &wgpu::ShaderModule,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let pipeline_layout = gpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mipmap Pipeline Layout"),
+            bind_group_layouts: &[bind_group_layout],
+            push_constant_ranges: &[],
+        });
+    gpu.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mipmap Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vertex_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fragment_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+}
+
+const MIPMAP_SHADER_SOURCE: &str = "
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+
+@vertex
+fn vertex_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Averages the 2x2 block of texels in the level above, clamping at the edges of odd sized levels
+@fragment
+fn fragment_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let max_coordinate = vec2<i32>(textureDimensions(source)) - vec2<i32>(1, 1);
+    let base = vec2<i32>(position.xy) * 2;
+    var color = vec4<f32>(0.0);
+    color += textureLoad(source, min(base, max_coordinate), 0);
+    color += textureLoad(source, min(base + vec2<i32>(1, 0), max_coordinate), 0);
+    color += textureLoad(source, min(base + vec2<i32>(0, 1), max_coordinate), 0);
+    color += textureLoad(source, min(base + vec2<i32>(1, 1), max_coordinate), 0);
+    return color * 0.25;
+}
+";
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn images_are_converted_to_supported_formats() {
+        let image = |format, pixels: Vec<u8>| crate::scene::Image {
+            pixels,
+            format,
+            width: 1,
+            height: 2,
+        };
+
+        let (format, pixels) = super::convert_image(
+            &image(crate::scene::ImageFormat::R8G8B8, vec![1, 2, 3, 4, 5, 6]),
+            super::ColorSpace::Srgb,
+            wgpu::Features::empty(),
+        );
+        assert_eq!(format, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(pixels, vec![1, 2, 3, 255, 4, 5, 6, 255]);
+
+        let (format, pixels) = super::convert_image(
+            &image(crate::scene::ImageFormat::R8, vec![7, 8]),
+            super::ColorSpace::Srgb,
+            wgpu::Features::empty(),
+        );
+        assert_eq!(format, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(pixels, vec![7, 7, 7, 255, 8, 8, 8, 255]);
+
+        let sixteen_bit = image(crate::scene::ImageFormat::R16, vec![0x34, 0x12, 0xff, 0xab]);
+        let (format, pixels) = super::convert_image(
+            &sixteen_bit,
+            super::ColorSpace::Linear,
+            wgpu::Features::empty(),
+        );
+        assert_eq!(format, wgpu::TextureFormat::R8Unorm);
+        assert_eq!(pixels, vec![0x12, 0xab]);
+        let (format, _) = super::convert_image(
+            &sixteen_bit,
+            super::ColorSpace::Linear,
+            wgpu::Features::TEXTURE_FORMAT_16BIT_NORM,
+        );
+        assert_eq!(format, wgpu::TextureFormat::R16Unorm);
+
+        assert_eq!(super::mip_level_count(1, 1), 1);
+        assert_eq!(super::mip_level_count(2048, 1024), 12);
+        assert_eq!(super::mip_level_count(300, 5), 9);
+    }
+
+    #[test]
+    fn unrenderable_formats_get_a_full_mip_chain() {
+        let format = wgpu::TextureFormat::Rgba16Unorm;
+        assert!(!format
+            .guaranteed_format_features(wgpu::Features::TEXTURE_FORMAT_16BIT_NORM)
+            .allowed_usages
+            .contains(wgpu::TextureUsages::RENDER_ATTACHMENT));
+
+        // A white and a black column, which average to gray
+        let pixels = (0..5 * 3)
+            .flat_map(|pixel| {
+                let value = if pixel % 5 < 2 { u16::MAX } else { 0 };
+                [value, value, value, u16::MAX]
+            })
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        let levels = super::downsample_mip_levels(format, &pixels, 5, 3)
+            .expect("Rgba16Unorm should be downsampled on the cpu!");
+        assert_eq!(levels.len() as u32, super::mip_level_count(5, 3));
+        levels.iter().enumerate().for_each(|(mip_level, level)| {
+            let (width, height) = super::mip_level_size(5, 3, mip_level as u32);
+            assert_eq!(level.len() as u32, width * height * 8);
+        });
+        let last = levels.last().expect("The mip chain is empty!");
+        let red = u16::from_le_bytes([last[0], last[1]]);
+        assert!(red > 0 && red < u16::MAX);
+
+        assert!(
+            super::downsample_mip_levels(wgpu::TextureFormat::Rgba8Unorm, &[0; 4], 1, 1).is_none()
+        );
+    }
+}
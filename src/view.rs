@@ -1,52 +1,415 @@
 pub struct View {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
+    /// The edges of every triangle in the index buffer, for the wireframe overlay
+    pub wireframe_index_buffer: wgpu::Buffer,
     pub uniform_buffer: wgpu::Buffer,
+    pub uniform_bind_group_layout: wgpu::BindGroupLayout,
     pub uniform_bind_group: wgpu::BindGroup,
     pub dynamic_uniform_buffer: wgpu::Buffer,
+    pub dynamic_uniform_bind_group_layout: wgpu::BindGroupLayout,
     pub dynamic_uniform_bind_group: wgpu::BindGroup,
-    pub pipeline: wgpu::RenderPipeline,
+    /// The scene shader file, watched so pipelines are rebuilt when it changes
+    pub shader_file: crate::shader::ShaderFile,
+    /// The last scene shader source that compiled, which the pipelines are created from
+    pub shader_source: String,
+    /// The include declaring the view bindings and vertex stage, watched like the scene shader
+    pub view_shader_file: crate::shader::ShaderFile,
+    /// The includes the scene and material shaders were last compiled with
+    pub shader_includes: std::collections::HashMap<String, String>,
+    pub pipelines: PipelineCache,
+    /// Shaders registered for materials to draw with in place of the scene shader
+    pub material_shaders: crate::material_shader::MaterialShaders,
+    pub wireframe_pipeline: wgpu::RenderPipeline,
+    pub overdraw_pipeline: wgpu::RenderPipeline,
+    pub view_mode: ViewMode,
     pub mesh_draw_commands:
         std::collections::HashMap<String, Vec<crate::scene::PrimitiveDrawCommand>>,
+    pub textures: std::collections::HashMap<String, crate::render::Texture>,
+    pub texture_uploader: crate::texture::TextureUploader,
+    pub materials: crate::material::GpuMaterials,
+    pub pass_pipelines: PassPipelines,
+    pub lighting: crate::lighting::Lighting,
+    pub debug: crate::debug::DebugRender,
+    /// The primitives drawn this frame, in dynamic uniform order,
+    /// which is opaque, then masked, then blended primitives from back to front
+    pub draws: Vec<Draw>,
+    /// The linear hdr color behind the scene
+    pub clear_color: wgpu::Color,
+}
+
+/// What the scene's primitives are shaded with
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ViewMode {
+    #[default]
+    Lit,
+    /// Base color and emissive without any lighting
+    Unlit,
+    /// Lit, with the edges of every triangle drawn over it
+    Wireframe,
+    /// World space normals
+    Normals,
+    /// A checker pattern tinted by the first texture coordinates
+    Uv0,
+    /// A checker pattern tinted by the second texture coordinates
+    Uv1,
+    VertexColors,
+    /// The material base color factor and texture only
+    BaseColor,
+    /// Roughness in green and metallic in blue, as packed in gltf textures
+    MetallicRoughness,
+    /// Linear view depth, from white at the camera fading to black
+    Depth,
+    /// Brighter where more fragments are shaded, ignoring depth
+    Overdraw,
+}
+
+impl ViewMode {
+    pub const ALL: [ViewMode; 11] = [
+        ViewMode::Lit,
+        ViewMode::Unlit,
+        ViewMode::Wireframe,
+        ViewMode::Normals,
+        ViewMode::Uv0,
+        ViewMode::Uv1,
+        ViewMode::VertexColors,
+        ViewMode::BaseColor,
+        ViewMode::MetallicRoughness,
+        ViewMode::Depth,
+        ViewMode::Overdraw,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ViewMode::Lit => "Lit",
+            ViewMode::Unlit => "Unlit",
+            ViewMode::Wireframe => "Wireframe",
+            ViewMode::Normals => "Normals",
+            ViewMode::Uv0 => "UV0",
+            ViewMode::Uv1 => "UV1",
+            ViewMode::VertexColors => "Vertex Colors",
+            ViewMode::BaseColor => "Base Color",
+            ViewMode::MetallicRoughness => "Metallic / Roughness",
+            ViewMode::Depth => "Depth",
+            ViewMode::Overdraw => "Overdraw",
+        }
+    }
+
+    /// Whether the scene is shown with its lighting and environment
+    pub fn is_lit(&self) -> bool {
+        matches!(self, ViewMode::Lit | ViewMode::Wireframe)
+    }
+}
+
+/// A single primitive drawn with its own dynamic uniform entry
+pub struct Draw {
+    pub indices: std::ops::Range<u32>,
+    pub base_vertex: i32,
+    pub material_index: u32,
+    /// The node the primitive's mesh belongs to
+    pub node_index: petgraph::graph::NodeIndex,
+    pub alpha_mode: crate::scene::AlphaMode,
+    pub double_sided: bool,
+    pub pipeline: DrawPipeline,
+}
+
+/// Which pipeline a primitive is drawn with, and so which bindings its material uses
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DrawPipeline {
+    /// A variant of the scene shader, by its index in the view's pipeline cache
+    Scene(usize),
+    /// A registered material shader, by the index of the material's binding
+    MaterialShader(usize),
+}
+
+impl Draw {
+    /// The defines of the scene shader variant the primitive is drawn with
+    pub fn shader_defines(&self) -> crate::shader::ShaderDefines {
+        shader_defines(self.alpha_mode, self.double_sided)
+    }
+
+    /// The index of the pass variant the primitive is drawn with outside the main pass
+    pub fn pass_variant(&self) -> usize {
+        2 * (self.alpha_mode == crate::scene::AlphaMode::Mask) as usize + self.double_sided as usize
+    }
+}
+
+/// The defines that select how a primitive is shaded, blended and culled
+pub fn shader_defines(
+    alpha_mode: crate::scene::AlphaMode,
+    double_sided: bool,
+) -> crate::shader::ShaderDefines {
+    let mut defines = crate::shader::ShaderDefines::new();
+    match alpha_mode {
+        crate::scene::AlphaMode::Opaque => {}
+        crate::scene::AlphaMode::Mask => {
+            defines.insert("ALPHA_MASK".to_string(), String::new());
+        }
+        crate::scene::AlphaMode::Blend => {
+            defines.insert("ALPHA_BLEND".to_string(), String::new());
+        }
+    }
+    if double_sided {
+        defines.insert("DOUBLE_SIDED".to_string(), String::new());
+    }
+    defines
+}
+
+/// The defines of every variant of the scene shader primitives can be drawn with
+fn shader_variants() -> Vec<crate::shader::ShaderDefines> {
+    [
+        crate::scene::AlphaMode::Opaque,
+        crate::scene::AlphaMode::Mask,
+        crate::scene::AlphaMode::Blend,
+    ]
+    .into_iter()
+    .flat_map(|alpha_mode| {
+        [false, true]
+            .into_iter()
+            .map(move |double_sided| shader_defines(alpha_mode, double_sided))
+    })
+    .collect()
+}
+
+/// The defines of each variant of a pass drawing primitives outside the main pass,
+/// such as into shadow maps, the id buffer or the outline mask, indexed by `Draw::pass_variant`.
+/// Masked variants discard fragments below the material's alpha cutoff,
+/// and blended primitives are drawn like opaque ones by the passes that draw them at all.
+pub fn pass_variants() -> [crate::shader::ShaderDefines; 4] {
+    [
+        (crate::scene::AlphaMode::Opaque, false),
+        (crate::scene::AlphaMode::Opaque, true),
+        (crate::scene::AlphaMode::Mask, false),
+        (crate::scene::AlphaMode::Mask, true),
+    ]
+    .map(|(alpha_mode, double_sided)| shader_defines(alpha_mode, double_sided))
+}
+
+/// What the pipelines of passes drawing the view's primitives outside the main pass are created with
+pub struct PassContext<'a> {
+    pub uniform_bind_group_layout: &'a wgpu::BindGroupLayout,
+    pub dynamic_uniform_bind_group_layout: &'a wgpu::BindGroupLayout,
+    pub materials: &'a crate::material::GpuMaterials,
+    /// The includes the scene shader was compiled with,
+    /// so passes share its vertex stage from the view include
+    pub includes: &'a std::collections::HashMap<String, String>,
+}
+
+/// The pipelines of passes drawing the view's primitives outside the main pass,
+/// each indexed by `Draw::pass_variant`. They include the same view include as the scene pipelines,
+/// so they are created and swapped together with them.
+pub struct PassPipelines {
+    /// Depth only pipelines rendering shadow maps
+    pub shadow: Vec<wgpu::RenderPipeline>,
+    /// Pipelines writing the ids of drawn nodes for picking
+    pub id: Vec<wgpu::RenderPipeline>,
+    /// Pairs of pipelines writing the selected and hovered outline mask channels,
+    /// the first of each pair drawing regardless of depth and the second only where visible
+    pub outline_mask: Vec<[[wgpu::RenderPipeline; 2]; 2]>,
+}
+
+impl PassPipelines {
+    pub fn new(
+        gpu: &crate::gpu::Gpu,
+        shadow_view_bind_group_layout: &wgpu::BindGroupLayout,
+        context: &PassContext,
+    ) -> Self {
+        Self {
+            shadow: crate::lighting::create_depth_pipelines(
+                gpu,
+                shadow_view_bind_group_layout,
+                context,
+            ),
+            id: crate::picking::create_id_pipelines(gpu, context),
+            outline_mask: crate::outline::create_mask_pipelines(gpu, context),
+        }
+    }
+}
+
+/// The sources of the pass shaders, which are validated with the scene shader's includes
+fn pass_shaders() -> [(&'static str, &'static str); 3] {
+    [
+        ("Shadow Depth Shader", crate::lighting::DEPTH_SHADER_SOURCE),
+        ("Id Shader", crate::picking::ID_SHADER_SOURCE),
+        ("Outline Mask Shader", crate::outline::MASK_SHADER_SOURCE),
+    ]
+}
+
+/// Creates a variant of a pass shader, which is preprocessed with the scene shader's includes
+/// and the defines of the material bindings
+pub fn create_pass_shader_module(
+    gpu: &crate::gpu::Gpu,
+    path: &str,
+    source: &str,
+    variant: &crate::shader::ShaderDefines,
+    context: &PassContext,
+) -> wgpu::ShaderModule {
+    let mut defines = context.materials.shader_defines();
+    defines.extend(variant.clone());
+    let source = crate::shader::preprocess(path, source, &defines, context.includes)
+        .expect("Pass shaders should be validated with the includes before they are created!");
+    gpu.device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(path),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(source)),
+        })
+}
+
+/// The filled pipelines built from variants of the scene shader, each created the first time
+/// its set of defines is drawn with. Blended variants are alpha blended without writing depth
+/// and double sided variants are drawn without culling back faces.
+pub struct PipelineCache {
+    layout: wgpu::PipelineLayout,
+    /// The scene shader before it is preprocessed
+    source: String,
+    /// Defined in every variant, such as how materials are bound
+    defines: crate::shader::ShaderDefines,
+    includes: std::collections::HashMap<String, String>,
+    pipelines: Vec<(crate::shader::ShaderDefines, wgpu::RenderPipeline)>,
+}
+
+impl PipelineCache {
+    /// Returns the index of the pipeline built with the defines, creating it if needed
+    pub fn index(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        defines: &crate::shader::ShaderDefines,
+    ) -> usize {
+        if let Some(index) = self
+            .pipelines
+            .iter()
+            .position(|(pipeline_defines, _)| pipeline_defines == defines)
+        {
+            return index;
+        }
+        let mut variant_defines = self.defines.clone();
+        variant_defines.extend(defines.clone());
+        let shader_module =
+            create_shader_module(gpu, &self.source, &variant_defines, &self.includes);
+        let blended = defines.contains_key("ALPHA_BLEND");
+        let pipeline = create_pipeline(
+            gpu,
+            &self.layout,
+            &shader_module,
+            "fragment_main",
+            wgpu::PrimitiveTopology::TriangleList,
+            (!blended, wgpu::CompareFunction::Less),
+            if blended {
+                wgpu::BlendState::ALPHA_BLENDING
+            } else {
+                wgpu::BlendState::REPLACE
+            },
+            (!defines.contains_key("DOUBLE_SIDED")).then_some(wgpu::Face::Back),
+        );
+        self.pipelines.push((defines.clone(), pipeline));
+        self.pipelines.len() - 1
+    }
+
+    pub fn pipeline(&self, index: usize) -> &wgpu::RenderPipeline {
+        &self.pipelines[index].1
+    }
 }
 
 impl View {
+    /// The maximum number of primitives drawn per frame
     pub const MAX_NUMBER_OF_MESHES: usize = 10_000;
 
+    pub const DEFAULT_CLEAR_COLOR: wgpu::Color = wgpu::Color {
+        r: 0.4,
+        g: 0.2,
+        b: 0.2,
+        a: 1.0,
+    };
+
     pub fn new(gpu: &crate::gpu::Gpu) -> Self {
-        let (vertex_buffer, index_buffer) = create_geometry_buffers(&gpu.device, &[], &[]);
+        let (vertex_buffer, index_buffer, wireframe_index_buffer) =
+            create_geometry_buffers(&gpu.device, &[], &[]);
         let (uniform_buffer, uniform_bind_group_layout, uniform_bind_group) = create_uniform(gpu);
         let (dynamic_uniform_buffer, dynamic_uniform_bind_group_layout, dynamic_uniform_bind_group) =
             create_dynamic_uniform(gpu, Self::MAX_NUMBER_OF_MESHES as _);
 
-        let pipeline = create_pipeline(
+        let textures = std::collections::HashMap::new();
+        let mut texture_uploader = crate::texture::TextureUploader::new(gpu);
+        let materials = crate::material::GpuMaterials::new(
+            gpu,
+            &mut texture_uploader,
+            &crate::scene::Scene::default(),
+            &textures,
+        );
+        let lighting = crate::lighting::Lighting::new(gpu);
+        let debug = crate::debug::DebugRender::new(gpu, &uniform_bind_group_layout);
+
+        let shader_file = crate::shader::ShaderFile::new(SHADER_PATH);
+        let view_shader_file = crate::shader::ShaderFile::new(VIEW_SHADER_PATH);
+        let (shader_source, shader_includes) =
+            load_shader(&shader_file, &view_shader_file, &materials.shader_defines());
+        let (pipelines, wireframe_pipeline, overdraw_pipeline) = create_pipelines(
             gpu,
             &[
                 &uniform_bind_group_layout,
                 &dynamic_uniform_bind_group_layout,
+                &materials.bind_group_layout,
+                &lighting.bind_group_layout,
             ],
+            &shader_source,
+            &shader_includes,
+            materials.shader_defines(),
+        );
+        let pass_pipelines = PassPipelines::new(
+            gpu,
+            &lighting.shadow_view_uniform_bind_group_layout,
+            &PassContext {
+                uniform_bind_group_layout: &uniform_bind_group_layout,
+                dynamic_uniform_bind_group_layout: &dynamic_uniform_bind_group_layout,
+                materials: &materials,
+                includes: &shader_includes,
+            },
         );
 
         Self {
             vertex_buffer,
             index_buffer,
+            wireframe_index_buffer,
             uniform_buffer,
+            uniform_bind_group_layout,
             uniform_bind_group,
             dynamic_uniform_buffer,
+            dynamic_uniform_bind_group_layout,
             dynamic_uniform_bind_group,
-            pipeline,
+            shader_file,
+            shader_source,
+            view_shader_file,
+            shader_includes,
+            pipelines,
+            material_shaders: crate::material_shader::MaterialShaders::default(),
+            wireframe_pipeline,
+            overdraw_pipeline,
+            view_mode: ViewMode::default(),
             mesh_draw_commands: std::collections::HashMap::new(),
+            textures,
+            texture_uploader,
+            materials,
+            pass_pipelines,
+            lighting,
+            debug,
+            draws: Vec::new(),
+            clear_color: Self::DEFAULT_CLEAR_COLOR,
         }
     }
 
-    pub fn render<'rp>(
-        &'rp self,
-        render_pass: &mut wgpu::RenderPass<'rp>,
+    /// Writes the camera, per draw and lighting uniforms for the scene viewed
+    /// from the given camera position, projection and view matrices
+    pub fn prepare(
+        &mut self,
         gpu: &crate::gpu::Gpu,
         scene: &crate::scene::Scene,
+        (camera_position, projection, view): (
+            nalgebra_glm::Vec3,
+            nalgebra_glm::Mat4,
+            nalgebra_glm::Mat4,
+        ),
     ) {
-        let (camera_position, projection, view) =
-            create_camera_matrices(scene, gpu.aspect_ratio()).unwrap_or_default();
+        self.lighting.prepare(gpu, scene, &view, &projection);
         gpu.queue.write_buffer(
             &self.uniform_buffer,
             0,
@@ -54,17 +417,114 @@ impl View {
                 view,
                 projection,
                 camera_position: nalgebra_glm::vec3_to_vec4(&camera_position),
+                light_count: self.lighting.number_of_lights,
+                has_environment: self.lighting.environment.is_loaded() as u32,
+                environment_intensity: self.lighting.environment.intensity(),
+                view_mode: self.view_mode as u32,
             }]),
         );
 
-        let mut mesh_ubos = vec![DynamicUniform::default(); View::MAX_NUMBER_OF_MESHES];
-        let mut ubo_offset = 0;
-        scene.walk_dfs(|_, node_index| {
-            mesh_ubos[ubo_offset] = DynamicUniform {
-                model: scene.graph.global_transform(node_index),
-            };
-            ubo_offset += 1;
+        self.material_shaders
+            .prepare(gpu, scene, &self.textures, &self.materials.default_texture);
+
+        // Each primitive drawn gets its own model matrix and material index
+        let mut draws = Vec::new();
+        scene.walk_dfs(|node, node_index| {
+            let model = scene.graph.global_transform(node_index);
+            node.components.iter().for_each(|component| {
+                let crate::scene::NodeComponent::Mesh(mesh_id) = component else {
+                    return;
+                };
+                let Some(commands) = self.mesh_draw_commands.get(mesh_id) else {
+                    return;
+                };
+                commands.iter().for_each(|command| {
+                    if draws.len() == View::MAX_NUMBER_OF_MESHES {
+                        return;
+                    }
+                    let material_index = self.materials.index(&command.material);
+                    let material = scene.materials.get(&command.material);
+                    let index_offset = command.index_offset as u32;
+                    let mut draw = Draw {
+                        indices: index_offset..index_offset + command.indices as u32,
+                        base_vertex: command.vertex_offset as i32,
+                        material_index,
+                        node_index,
+                        alpha_mode: material
+                            .map(|material| material.alpha_mode)
+                            .unwrap_or_default(),
+                        double_sided: material.is_some_and(|material| material.double_sided),
+                        pipeline: DrawPipeline::Scene(0),
+                    };
+                    // Materials drawn with a registered shader are sorted by its blending alone
+                    if let Some(binding_index) =
+                        self.material_shaders.binding_index(&command.material)
+                    {
+                        draw.pipeline = DrawPipeline::MaterialShader(binding_index);
+                        draw.alpha_mode = if self.material_shaders.is_blended(binding_index) {
+                            crate::scene::AlphaMode::Blend
+                        } else {
+                            crate::scene::AlphaMode::Opaque
+                        };
+                    }
+                    let center = nalgebra_glm::vec4(
+                        command.center.x,
+                        command.center.y,
+                        command.center.z,
+                        1.0,
+                    );
+                    let view_depth = -(view * model * center).z;
+                    draws.push((draw, model, view_depth));
+                });
+            });
+        });
+
+        // Primitives sharing an alpha mode and sidedness share a variant of the scene shader
+        let mut pipeline_indices = std::collections::HashMap::new();
+        draws.iter_mut().for_each(|(draw, ..)| {
+            if let DrawPipeline::Scene(_) = draw.pipeline {
+                draw.pipeline = DrawPipeline::Scene(
+                    *pipeline_indices
+                        .entry((draw.alpha_mode, draw.double_sided))
+                        .or_insert_with(|| self.pipelines.index(gpu, &draw.shader_defines())),
+                );
+            }
+        });
+
+        // Blended primitives are drawn after everything else from back to front,
+        // so they blend over whatever is behind them. The rest are batched by shader.
+        let batch = |draw: &Draw| match draw.pipeline {
+            DrawPipeline::MaterialShader(binding_index) => {
+                DrawPipeline::MaterialShader(self.material_shaders.shader_index(binding_index))
+            }
+            pipeline => pipeline,
+        };
+        draws.sort_by(|(a, _, a_depth), (b, _, b_depth)| {
+            (a.alpha_mode as u32)
+                .cmp(&(b.alpha_mode as u32))
+                .then_with(|| match a.alpha_mode {
+                    crate::scene::AlphaMode::Blend => b_depth.total_cmp(a_depth),
+                    _ => batch(a).cmp(&batch(b)),
+                })
         });
+
+        let mut mesh_ubos = vec![DynamicUniform::default(); View::MAX_NUMBER_OF_MESHES];
+        draws
+            .iter()
+            .zip(mesh_ubos.iter_mut())
+            .for_each(|((draw, model, _), mesh_ubo)| {
+                *mesh_ubo = DynamicUniform {
+                    model: *model,
+                    material_index: draw.material_index,
+                    node_id: crate::picking::node_id(draw.node_index),
+                    normal_matrix: nalgebra_glm::transpose(
+                        &model
+                            .try_inverse()
+                            .unwrap_or_else(nalgebra_glm::Mat4::identity),
+                    ),
+                    ..Default::default()
+                };
+            });
         gpu.queue
             .write_buffer(&self.dynamic_uniform_buffer, 0, unsafe {
                 std::slice::from_raw_parts(
@@ -72,52 +532,374 @@ impl View {
                     mesh_ubos.len() * gpu.alignment() as usize,
                 )
             });
+        self.draws = draws.into_iter().map(|(draw, ..)| draw).collect();
+    }
+
+    /// The color the scene is cleared to, which is black when counting overdraw
+    pub fn background_color(&self) -> wgpu::Color {
+        match self.view_mode {
+            ViewMode::Overdraw => wgpu::Color::BLACK,
+            _ => self.clear_color,
+        }
+    }
+
+    /// Renders the shadow maps of the prepared lights, before the main render pass
+    pub fn render_shadows(&self, encoder: &mut wgpu::CommandEncoder, gpu: &crate::gpu::Gpu) {
+        self.lighting.render_shadows(encoder, gpu, self);
+    }
+
+    /// Draws the prepared scene
+    pub fn render<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>, gpu: &crate::gpu::Gpu) {
+        // The skybox is drawn first so transparent primitives blend over it
+        if self.view_mode.is_lit() {
+            self.lighting.environment.render_skybox(render_pass);
+        }
+
+        let pipeline = match self.view_mode {
+            ViewMode::Overdraw => Some(&self.overdraw_pipeline),
+            _ => None,
+        };
+        self.draw_primitives(render_pass, gpu, pipeline, &self.index_buffer, 1);
+        if self.view_mode == ViewMode::Wireframe {
+            // Every triangle of three indices has three edges of two indices
+            self.draw_primitives(
+                render_pass,
+                gpu,
+                Some(&self.wireframe_pipeline),
+                &self.wireframe_index_buffer,
+                2,
+            );
+        }
+
+        self.debug.render(render_pass, &self.uniform_bind_group);
+    }
 
-        render_pass.set_pipeline(&self.pipeline);
+    /// Draws every prepared primitive with the given pipeline and the built in material bindings,
+    /// or with its own pipeline and material bindings, reading indices from the index buffer
+    /// at the primitive's index range scaled by `index_scale`
+    fn draw_primitives<'rp>(
+        &'rp self,
+        render_pass: &mut wgpu::RenderPass<'rp>,
+        gpu: &crate::gpu::Gpu,
+        pipeline: Option<&'rp wgpu::RenderPipeline>,
+        index_buffer: &'rp wgpu::Buffer,
+        index_scale: u32,
+    ) {
+        if self.draws.is_empty() {
+            return;
+        }
+        let mut current_pipeline = None;
+        let mut material_shader_bound = false;
         render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        self.materials.bind_all(render_pass, 2);
+        render_pass.set_bind_group(3, &self.lighting.bind_group, &[]);
 
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
 
-        let mut ubo_offset = 0;
-        scene.walk_dfs(|node, _| {
-            let offset = ubo_offset;
-            ubo_offset += 1;
-            node.components.iter().for_each(|component| {
-                if let crate::scene::NodeComponent::Mesh(mesh_id) = component {
-                    let offset = (offset * gpu.alignment()) as wgpu::DynamicOffset;
-                    render_pass.set_bind_group(1, &self.dynamic_uniform_bind_group, &[offset]);
-                    if let Some(commands) = self.mesh_draw_commands.get(mesh_id) {
-                        execute_draw_commands(commands, render_pass);
+        self.draws
+            .iter()
+            .enumerate()
+            .for_each(|(draw_index, draw)| {
+                let draw_pipeline = match (pipeline, draw.pipeline) {
+                    (Some(pipeline), _) => pipeline,
+                    (None, DrawPipeline::Scene(pipeline_index)) => {
+                        self.pipelines.pipeline(pipeline_index)
+                    }
+                    (None, DrawPipeline::MaterialShader(binding_index)) => {
+                        self.material_shaders.pipeline(binding_index)
                     }
+                };
+                if !current_pipeline.is_some_and(|current| std::ptr::eq(current, draw_pipeline)) {
+                    render_pass.set_pipeline(draw_pipeline);
+                    current_pipeline = Some(draw_pipeline);
                 }
+                let offset = (draw_index as u64 * gpu.alignment()) as wgpu::DynamicOffset;
+                render_pass.set_bind_group(1, &self.dynamic_uniform_bind_group, &[offset]);
+                match (pipeline, draw.pipeline) {
+                    (None, DrawPipeline::MaterialShader(binding_index)) => {
+                        self.material_shaders.bind(render_pass, 2, binding_index);
+                        material_shader_bound = true;
+                    }
+                    _ => {
+                        if material_shader_bound {
+                            self.materials.bind_all(render_pass, 2);
+                            material_shader_bound = false;
+                        }
+                        self.materials.bind(render_pass, 2, draw.material_index);
+                    }
+                }
+                let indices = draw.indices.start * index_scale..draw.indices.end * index_scale;
+                // TODO: support multiple instances per primitive
+                render_pass.draw_indexed(indices, draw.base_vertex, 0..1);
             });
-        });
     }
 
     pub fn import_scene(&mut self, scene: &crate::scene::Scene, gpu: &crate::gpu::Gpu) {
         let (vertices, indices, mesh_draw_commands) = scene.flatten_geometry();
-        let (vertex_buffer, index_buffer) =
+        let (vertex_buffer, index_buffer, wireframe_index_buffer) =
             create_geometry_buffers(&gpu.device, &vertices, &indices);
         self.vertex_buffer = vertex_buffer;
         self.index_buffer = index_buffer;
+        self.wireframe_index_buffer = wireframe_index_buffer;
         self.mesh_draw_commands = mesh_draw_commands;
+        self.textures = self.texture_uploader.upload_scene(gpu, scene);
+        // Cameras rendering into a texture replace the image standing in for it
+        crate::render_target::camera_render_targets(scene)
+            .into_iter()
+            .for_each(|(_, render_target)| {
+                let texture = crate::render_target::create_texture(
+                    gpu,
+                    &mut self.texture_uploader,
+                    &render_target,
+                );
+                self.textures.insert(render_target.texture, texture);
+            });
+
+        // The material bind group layout depends on the number of textures when bindless
+        self.materials = crate::material::GpuMaterials::new(
+            gpu,
+            &mut self.texture_uploader,
+            scene,
+            &self.textures,
+        );
+        self.material_shaders.clear_bindings();
+        self.create_pipelines(gpu);
+    }
+
+    /// Registers a shader for materials to draw with by naming it,
+    /// returning an error if its source fails to compile
+    pub fn register_material_shader(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+        name: &str,
+        shader: impl crate::material_shader::MaterialShader + 'static,
+    ) -> Result<(), crate::shader::ShaderError> {
+        let context = crate::material_shader::ShaderContext {
+            uniform_bind_group_layout: &self.uniform_bind_group_layout,
+            dynamic_uniform_bind_group_layout: &self.dynamic_uniform_bind_group_layout,
+            lighting_bind_group_layout: &self.lighting.bind_group_layout,
+            includes: &self.shader_includes,
+        };
+        self.material_shaders
+            .register(gpu, name, Box::new(shader), &context)
+            .map_err(|error| {
+                log::error!("{error}");
+                error
+            })
+    }
+
+    /// Rebuilds the pipelines if the scene shader or view include changed since they were last checked,
+    /// returning whether either did. Shaders that fail to compile or that the device rejects are logged
+    /// and returned as errors, leaving the pipelines built from the last working shader in place.
+    /// Material shaders are recompiled when the view include changes.
+    pub fn reload_shaders(
+        &mut self,
+        gpu: &crate::gpu::Gpu,
+    ) -> Result<bool, crate::shader::ShaderError> {
+        let scene_source = self.shader_file.poll();
+        let view_source = self.view_shader_file.poll();
+        if scene_source.is_none() && view_source.is_none() {
+            return Ok(false);
+        }
+        let includes_changed = view_source.is_some();
+        let reloaded = scene_source
+            .unwrap_or_else(|| Ok(self.shader_source.clone()))
+            .and_then(|source| {
+                let includes = match view_source {
+                    Some(view_source) => includes_with_view(view_source?),
+                    None => self.shader_includes.clone(),
+                };
+                validate_shader(
+                    &self.shader_file.path,
+                    &source,
+                    &self.materials.shader_defines(),
+                    &includes,
+                )?;
+                let pipelines = self.create_validated_pipelines(gpu, &source, &includes)?;
+                Ok((source, includes, pipelines))
+            });
+        match reloaded {
+            Ok((source, includes, pipelines)) => {
+                self.shader_source = source;
+                self.shader_includes = includes;
+                (
+                    self.pipelines,
+                    self.wireframe_pipeline,
+                    self.overdraw_pipeline,
+                    self.pass_pipelines,
+                ) = pipelines;
+                if includes_changed {
+                    self.material_shaders.rebuild(
+                        gpu,
+                        &crate::material_shader::ShaderContext {
+                            uniform_bind_group_layout: &self.uniform_bind_group_layout,
+                            dynamic_uniform_bind_group_layout: &self
+                                .dynamic_uniform_bind_group_layout,
+                            lighting_bind_group_layout: &self.lighting.bind_group_layout,
+                            includes: &self.shader_includes,
+                        },
+                    );
+                }
+                log::info!("Reloaded shader '{}'", self.shader_file.path);
+                Ok(true)
+            }
+            Err(error) => {
+                log::error!("{error}");
+                Err(error)
+            }
+        }
+    }
+
+    /// Recreates the pipelines for new material bindings, falling back to the scene shader
+    /// compiled into the binary if the device rejects the last one that compiled
+    fn create_pipelines(&mut self, gpu: &crate::gpu::Gpu) {
+        let pipelines = self
+            .create_validated_pipelines(gpu, &self.shader_source, &self.shader_includes)
+            .unwrap_or_else(|error| {
+                log::error!("{error}");
+                self.shader_source = SHADER_SOURCE.to_string();
+                self.shader_includes = shader_includes();
+                self.create_validated_pipelines(gpu, SHADER_SOURCE, &self.shader_includes)
+                    .expect("The scene shader compiled into the binary should create pipelines!")
+            });
+        (
+            self.pipelines,
+            self.wireframe_pipeline,
+            self.overdraw_pipeline,
+            self.pass_pipelines,
+        ) = pipelines;
+    }
+
+    /// Creates the pipelines from a scene shader, along with the variants already in use
+    /// and the pass pipelines sharing its includes,
+    /// returning an error instead if the device rejects any of them
+    fn create_validated_pipelines(
+        &self,
+        gpu: &crate::gpu::Gpu,
+        shader_source: &str,
+        includes: &std::collections::HashMap<String, String>,
+    ) -> Result<
+        (
+            PipelineCache,
+            wgpu::RenderPipeline,
+            wgpu::RenderPipeline,
+            PassPipelines,
+        ),
+        crate::shader::ShaderError,
+    > {
+        crate::shader::create_validated(gpu, &self.shader_file.path, || {
+            let (mut pipelines, wireframe_pipeline, overdraw_pipeline) = create_pipelines(
+                gpu,
+                &[
+                    &self.uniform_bind_group_layout,
+                    &self.dynamic_uniform_bind_group_layout,
+                    &self.materials.bind_group_layout,
+                    &self.lighting.bind_group_layout,
+                ],
+                shader_source,
+                includes,
+                self.materials.shader_defines(),
+            );
+            self.pipelines.pipelines.iter().for_each(|(defines, _)| {
+                pipelines.index(gpu, defines);
+            });
+            let pass_pipelines = PassPipelines::new(
+                gpu,
+                &self.lighting.shadow_view_uniform_bind_group_layout,
+                &PassContext {
+                    uniform_bind_group_layout: &self.uniform_bind_group_layout,
+                    dynamic_uniform_bind_group_layout: &self.dynamic_uniform_bind_group_layout,
+                    materials: &self.materials,
+                    includes,
+                },
+            );
+            (
+                pipelines,
+                wireframe_pipeline,
+                overdraw_pipeline,
+                pass_pipelines,
+            )
+        })
     }
 }
 
-fn execute_draw_commands(
-    commands: &[crate::scene::PrimitiveDrawCommand],
-    render_pass: &mut wgpu::RenderPass,
-) {
-    commands.iter().for_each(|command| {
-        let index_offset = command.index_offset as u32;
-        let number_of_indices = index_offset + command.indices as u32;
-        render_pass.draw_indexed(
-            index_offset..number_of_indices,
-            command.vertex_offset as i32,
-            0..1, // TODO: support multiple instances per primitive
-        );
+/// Reads the scene shader and view include files, falling back to the sources compiled into the binary
+/// if either can't be read or any variant of the scene shader doesn't compile
+fn load_shader(
+    shader_file: &crate::shader::ShaderFile,
+    view_shader_file: &crate::shader::ShaderFile,
+    defines: &crate::shader::ShaderDefines,
+) -> (String, std::collections::HashMap<String, String>) {
+    let source = shader_file.load(SHADER_SOURCE);
+    let includes = includes_with_view(view_shader_file.load(VIEW_SHADER_SOURCE));
+    match validate_shader(&shader_file.path, &source, defines, &includes) {
+        Ok(()) => (source, includes),
+        Err(error) => {
+            log::error!("{error}");
+            (SHADER_SOURCE.to_string(), shader_includes())
+        }
+    }
+}
+
+/// Preprocesses and validates every variant of the scene shader and the pass shaders
+/// sharing its includes, so variants can be created later without failing
+fn validate_shader(
+    path: &str,
+    source: &str,
+    defines: &crate::shader::ShaderDefines,
+    includes: &std::collections::HashMap<String, String>,
+) -> Result<(), crate::shader::ShaderError> {
+    let scene_variants = shader_variants()
+        .into_iter()
+        .map(|variant| (path, source, variant));
+    let pass_variants = pass_shaders().into_iter().flat_map(|(path, source)| {
+        pass_variants()
+            .into_iter()
+            .map(move |variant| (path, source, variant))
     });
+    scene_variants
+        .chain(pass_variants)
+        .try_for_each(|(path, source, mut variant)| {
+            variant.extend(defines.clone());
+            let source = crate::shader::preprocess(path, source, &variant, includes)?;
+            crate::shader::validate(path, &source)
+        })
+}
+
+/// The sources compiled into the binary that scene, material and pass shaders can include by name
+pub fn shader_includes() -> std::collections::HashMap<String, String> {
+    [
+        ("view.wgsl", VIEW_SHADER_SOURCE),
+        ("material.wgsl", crate::material::SHADER_SOURCE),
+        ("lighting.wgsl", crate::lighting::SHADER_SOURCE),
+        ("environment.wgsl", crate::environment::SHADER_SOURCE),
+    ]
+    .into_iter()
+    .map(|(name, source)| (name.to_string(), source.to_string()))
+    .collect()
+}
+
+/// The includes compiled into the binary, with the view include replaced by the given source
+fn includes_with_view(view_source: String) -> std::collections::HashMap<String, String> {
+    let mut includes = shader_includes();
+    includes.insert("view.wgsl".to_string(), view_source);
+    includes
+}
+
+fn create_shader_module(
+    gpu: &crate::gpu::Gpu,
+    source: &str,
+    defines: &crate::shader::ShaderDefines,
+    includes: &std::collections::HashMap<String, String>,
+) -> wgpu::ShaderModule {
+    let source = crate::shader::preprocess(SHADER_PATH, source, defines, includes)
+        .expect("Scene shader variants should be validated before they are created!");
+    gpu.device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(SHADER_PATH),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(source)),
+        })
 }
 
 fn create_dynamic_uniform(
@@ -136,7 +918,7 @@ fn create_dynamic_uniform(
             .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: true,
@@ -215,40 +997,171 @@ pub fn create_camera_matrices(
     scene: &crate::scene::Scene,
     aspect_ratio: f32,
 ) -> Option<(nalgebra_glm::Vec3, nalgebra_glm::Mat4, nalgebra_glm::Mat4)> {
+    create_camera_matrices_for_node(scene, active_camera(scene)?, aspect_ratio)
+}
+
+/// The camera the scene is viewed through, which is the last camera visited depth first,
+/// preferring cameras that fill the window over those rendering into their own viewport.
+/// Cameras rendering into a texture are never active.
+pub fn active_camera(scene: &crate::scene::Scene) -> Option<petgraph::graph::NodeIndex> {
     let mut result = None;
-    scene.walk_dfs(|node, _| {
-        for component in node.components.iter() {
-            if let crate::scene::NodeComponent::Camera(camera) = component {
-                result = Some((
-                    // TODO: later this will need to be the translation of the global transform,
-                    //       need to be able to aggregate transforms without turning them in to glm::Mat4 first
-                    node.transform.translation,
-                    camera.projection_matrix(aspect_ratio),
-                    {
-                        let eye = node.transform.translation;
-                        let target = eye
-                            + nalgebra_glm::quat_rotate_vec3(
-                                &node.transform.rotation.normalize(),
-                                &(-nalgebra_glm::Vec3::z()),
-                            );
-                        let up = nalgebra_glm::quat_rotate_vec3(
-                            &node.transform.rotation.normalize(),
-                            &nalgebra_glm::Vec3::y(),
-                        );
-                        nalgebra_glm::look_at(&eye, &target, &up)
-                    },
-                ));
+    let mut result_has_viewport = true;
+    scene.walk_dfs(|node, node_index| {
+        node.components.iter().for_each(|component| {
+            let crate::scene::NodeComponent::Camera(camera) = component else {
+                return;
+            };
+            if camera.render_target.is_some() {
+                return;
             }
-        }
+            let has_viewport = camera.viewport.is_some();
+            if !has_viewport || result_has_viewport {
+                result = Some(node_index);
+                result_has_viewport = has_viewport;
+            }
+        });
     });
     result
 }
 
+/// A region of an output in pixels with the origin at the top left
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PixelRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PixelRect {
+    pub fn full(width: u32, height: u32) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height.max(1) as f32
+    }
+
+    /// Restricts drawing in the render pass to this region of its attachments
+    pub fn apply(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_viewport(
+            self.x as f32,
+            self.y as f32,
+            self.width as f32,
+            self.height as f32,
+            0.0,
+            1.0,
+        );
+        render_pass.set_scissor_rect(self.x, self.y, self.width, self.height);
+    }
+}
+
+/// A camera rendered into a region of the output.
+/// Views don't have to belong to a scene node, like the editor's orthographic views.
+#[derive(Debug, Clone)]
+pub struct CameraView {
+    pub camera: crate::scene::Camera,
+    pub transform: crate::scene::Transform,
+    pub viewport: crate::scene::CameraViewport,
+}
+
+impl CameraView {
+    pub fn matrices(
+        &self,
+        aspect_ratio: f32,
+    ) -> (nalgebra_glm::Vec3, nalgebra_glm::Mat4, nalgebra_glm::Mat4) {
+        create_camera_matrices_for_camera(&self.camera, &self.transform, aspect_ratio)
+    }
+}
+
+/// The views of the scene to render this frame, in render order.
+/// The active camera fills the window first unless it has its own viewport,
+/// then every camera with a viewport renders into it.
+/// Cameras rendering into a texture are rendered separately, before these views.
+pub fn camera_views(scene: &crate::scene::Scene) -> Vec<CameraView> {
+    let active_camera = active_camera(scene);
+    let mut views = Vec::new();
+    scene.walk_dfs(|node, node_index| {
+        node.components.iter().for_each(|component| {
+            let crate::scene::NodeComponent::Camera(camera) = component else {
+                return;
+            };
+            if camera.render_target.is_some() {
+                return;
+            }
+            let viewport = match camera.viewport {
+                Some(viewport) => viewport,
+                None if active_camera == Some(node_index) => crate::scene::CameraViewport {
+                    order: i32::MIN,
+                    ..crate::scene::CameraViewport::FULL
+                },
+                None => return,
+            };
+            views.push(CameraView {
+                camera: camera.clone(),
+                transform: node.transform,
+                viewport,
+            });
+        });
+    });
+    // The sort is stable, so cameras with the same order render depth first
+    views.sort_by_key(|view| view.viewport.order);
+    views
+}
+
+pub fn create_camera_matrices_for_node(
+    scene: &crate::scene::Scene,
+    node_index: petgraph::graph::NodeIndex,
+    aspect_ratio: f32,
+) -> Option<(nalgebra_glm::Vec3, nalgebra_glm::Mat4, nalgebra_glm::Mat4)> {
+    let node = &scene.graph[node_index];
+    let camera = node
+        .components
+        .iter()
+        .find_map(|component| match component {
+            crate::scene::NodeComponent::Camera(camera) => Some(camera),
+            _ => None,
+        })?;
+    // TODO: later this will need to be the global transform,
+    //       need to be able to aggregate transforms without turning them in to glm::Mat4 first
+    Some(create_camera_matrices_for_camera(
+        camera,
+        &node.transform,
+        aspect_ratio,
+    ))
+}
+
+/// The position, projection and view matrices of a camera placed with a transform
+pub fn create_camera_matrices_for_camera(
+    camera: &crate::scene::Camera,
+    transform: &crate::scene::Transform,
+    aspect_ratio: f32,
+) -> (nalgebra_glm::Vec3, nalgebra_glm::Mat4, nalgebra_glm::Mat4) {
+    let rotation = transform.rotation.normalize();
+    let eye = transform.translation;
+    let target = eye + nalgebra_glm::quat_rotate_vec3(&rotation, &(-nalgebra_glm::Vec3::z()));
+    let up = nalgebra_glm::quat_rotate_vec3(&rotation, &nalgebra_glm::Vec3::y());
+    (
+        eye,
+        camera.projection_matrix(aspect_ratio),
+        nalgebra_glm::look_at(&eye, &target, &up),
+    )
+}
+
 fn create_geometry_buffers(
     device: &wgpu::Device,
     vertices: &[crate::scene::Vertex],
     indices: &[u16],
-) -> (wgpu::Buffer, wgpu::Buffer) {
+) -> (wgpu::Buffer, wgpu::Buffer, wgpu::Buffer) {
     let vertex_buffer = wgpu::util::DeviceExt::create_buffer_init(
         device,
         &wgpu::util::BufferInitDescriptor {
@@ -265,7 +1178,28 @@ fn create_geometry_buffers(
             usage: wgpu::BufferUsages::INDEX,
         },
     );
-    (vertex_buffer, index_buffer)
+    let wireframe_indices = indices
+        .chunks_exact(3)
+        .flat_map(|triangle| {
+            [
+                triangle[0],
+                triangle[1],
+                triangle[1],
+                triangle[2],
+                triangle[2],
+                triangle[0],
+            ]
+        })
+        .collect::<Vec<_>>();
+    let wireframe_index_buffer = wgpu::util::DeviceExt::create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Wireframe Index Buffer"),
+            contents: bytemuck::cast_slice(&wireframe_indices),
+            usage: wgpu::BufferUsages::INDEX,
+        },
+    );
+    (vertex_buffer, index_buffer, wireframe_index_buffer)
 }
 
 #[repr(C)]
@@ -291,62 +1225,127 @@ pub struct Uniform {
     pub view: nalgebra_glm::Mat4,
     pub projection: nalgebra_glm::Mat4,
     pub camera_position: nalgebra_glm::Vec4,
+    pub light_count: u32,
+    pub has_environment: u32,
+    pub environment_intensity: f32,
+    pub view_mode: u32,
 }
 
-fn create_pipeline(
+/// Creates the pipelines the scene is drawn with, which are the cache of filled pipelines,
+/// the wireframe overlay drawn over them and the additive overdraw pipeline
+fn create_pipelines(
     gpu: &crate::gpu::Gpu,
     bind_group_layouts: &[&wgpu::BindGroupLayout],
-) -> wgpu::RenderPipeline {
-    let shader_module = gpu
-        .device
-        .create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(SHADER_SOURCE)),
-        });
-
-    let pipeline_layout = gpu
+    shader_source: &str,
+    includes: &std::collections::HashMap<String, String>,
+    defines: crate::shader::ShaderDefines,
+) -> (PipelineCache, wgpu::RenderPipeline, wgpu::RenderPipeline) {
+    let layout = gpu
         .device
         .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
             bind_group_layouts,
             push_constant_ranges: &[],
         });
+    let shader_module = create_shader_module(gpu, shader_source, &defines, includes);
+    let create_pipeline = |fragment_entry_point: &str,
+                           topology: wgpu::PrimitiveTopology,
+                           depth_write_enabled: bool,
+                           depth_compare: wgpu::CompareFunction,
+                           blend: wgpu::BlendState| {
+        create_pipeline(
+            gpu,
+            &layout,
+            &shader_module,
+            fragment_entry_point,
+            topology,
+            (depth_write_enabled, depth_compare),
+            blend,
+            Some(wgpu::Face::Back),
+        )
+    };
+
+    // Wireframes are drawn as line lists rather than with a line polygon mode,
+    // which isn't supported by every adapter, such as some software adapters
+    let wireframe_pipeline = create_pipeline(
+        "wireframe_fragment_main",
+        wgpu::PrimitiveTopology::LineList,
+        false,
+        wgpu::CompareFunction::LessEqual,
+        wgpu::BlendState::ALPHA_BLENDING,
+    );
+    // Overdraw adds up every fragment shaded, whether or not it is hidden
+    let overdraw_pipeline = create_pipeline(
+        "fragment_main",
+        wgpu::PrimitiveTopology::TriangleList,
+        false,
+        wgpu::CompareFunction::Always,
+        wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::OVER,
+        },
+    );
+
+    (
+        PipelineCache {
+            layout,
+            source: shader_source.to_string(),
+            defines,
+            includes: includes.clone(),
+            pipelines: Vec::new(),
+        },
+        wireframe_pipeline,
+        overdraw_pipeline,
+    )
+}
 
+/// Creates a pipeline drawing the scene's vertices with the shader's `vertex_main` entry point
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn create_pipeline(
+    gpu: &crate::gpu::Gpu,
+    layout: &wgpu::PipelineLayout,
+    shader_module: &wgpu::ShaderModule,
+    fragment_entry_point: &str,
+    topology: wgpu::PrimitiveTopology,
+    (depth_write_enabled, depth_compare): (bool, wgpu::CompareFunction),
+    blend: wgpu::BlendState,
+    cull_mode: Option<wgpu::Face>,
+) -> wgpu::RenderPipeline {
     gpu.device
         .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: None,
-            layout: Some(&pipeline_layout),
+            layout: Some(layout),
             vertex: wgpu::VertexState {
-                module: &shader_module,
+                module: shader_module,
                 entry_point: "vertex_main",
                 buffers: &[crate::scene::Vertex::description(
                     &crate::scene::Vertex::attributes(),
                 )],
             },
             primitive: wgpu::PrimitiveState {
+                topology,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Line,
+                cull_mode,
                 ..Default::default()
             },
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_write_enabled,
+                depth_compare,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
+            multisample: gpu.multisample_state(),
             fragment: Some(wgpu::FragmentState {
-                module: &shader_module,
-                entry_point: "fragment_main",
+                module: shader_module,
+                entry_point: fragment_entry_point,
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: gpu.surface_format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    format: crate::postprocess::HDR_FORMAT,
+                    blend: Some(blend),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
@@ -381,60 +1380,188 @@ impl crate::scene::Vertex {
 #[derive(Default, Copy, Clone, Debug, bytemuck::Zeroable)]
 pub struct DynamicUniform {
     pub model: nalgebra_glm::Mat4,
+    pub material_index: u32,
+    /// Written to the id buffer when picking
+    pub node_id: u32,
+    pub _padding: [u32; 2],
+    /// The inverse transpose of the model matrix, which keeps normals perpendicular
+    /// to surfaces under non-uniform scale
+    pub normal_matrix: nalgebra_glm::Mat4,
 }
 
-const SHADER_SOURCE: &str = "
-struct Uniform {
-    view: mat4x4<f32>,
-    projection: mat4x4<f32>,
-    camera_position: vec4<f32>,
-};
-
-@group(0) @binding(0)
-var<uniform> ubo: Uniform;
-
-struct DynamicUniform {
-    model: mat4x4<f32>,
-};
-
-@group(1) @binding(0)
-var<uniform> mesh_ubo: DynamicUniform;
-
-struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) normal: vec3<f32>,
-    @location(2) uv_0: vec2<f32>,
-    @location(3) uv_1: vec2<f32>,
-    @location(4) joint_0: vec4<f32>,
-    @location(5) weight_0: vec4<f32>,
-    @location(6) color_0: vec3<f32>,
-};
-
-struct VertexOutput {
-    @builtin(position) position: vec4<f32>,
-    @location(0) normal: vec3<f32>,
-    @location(1) color: vec3<f32>,
-};
-
-@vertex
-fn vertex_main(vert: VertexInput) -> VertexOutput {
-    var out: VertexOutput;
-    let mvp = ubo.projection * ubo.view * mesh_ubo.model;
-    out.color = vert.color_0;
-    out.position = mvp * vec4(vert.position, 1.0);
-    out.normal = vec4((mvp * vec4(vert.normal, 0.0)).xyz, 1.0).xyz;
-    return out;
-};
-
-@fragment
-fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let object_color: vec4<f32> = vec4(in.color, 1.0);
-
-    let ambient_strength = 0.1;
-    let ambient_color = ambient_strength;
-
-    let result = (ambient_color) * object_color.rgb;
-
-    return vec4<f32>(result, object_color.a);
-}
-";
+/// Where the scene shader is read from and watched, relative to the working directory
+pub const SHADER_PATH: &str = "resources/shaders/scene.wgsl";
+
+/// Where the view include is read from and watched, relative to the working directory
+pub const VIEW_SHADER_PATH: &str = "resources/shaders/view.wgsl";
+
+/// The view bindings and vertex stage shared by the scene and material shaders,
+/// used when the include file can't be read
+const VIEW_SHADER_SOURCE: &str = include_str!("../resources/shaders/view.wgsl");
+
+/// The scene shader compiled into the binary, used when the shader file can't be read
+const SHADER_SOURCE: &str = include_str!("../resources/shaders/scene.wgsl");
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn view_modes_show_surface_data() {
+        let Some(mut fixture) = crate::render::HelmetFixture::new() else {
+            return;
+        };
+
+        // The visor at the center of the image faces the camera along the positive z axis
+        fixture.renderer.view.view_mode = super::ViewMode::Normals;
+        let normal = *fixture.render().get_pixel(32, 32);
+        assert!(
+            normal[2] > normal[0] && normal[2] > normal[1],
+            "{normal:?} is not a normal facing the camera"
+        );
+
+        fixture.scene.materials.values_mut().for_each(|material| {
+            material.base_color_factor = nalgebra_glm::vec4(1.0, 0.0, 0.0, 1.0);
+            material.base_color_texture.clear();
+        });
+        fixture.renderer.view.view_mode = super::ViewMode::BaseColor;
+        let base_color = *fixture.reimport_and_render().get_pixel(32, 32);
+        assert!(
+            base_color[0] > 200 && base_color[1] < 30 && base_color[2] < 30,
+            "{base_color:?} is not the red base color"
+        );
+    }
+
+    #[test]
+    fn masked_texels_are_discarded() {
+        let Some(mut fixture) = crate::render::HelmetFixture::new() else {
+            return;
+        };
+        let background = *fixture.image.get_pixel(0, 0);
+        let lit = *fixture.image.get_pixel(32, 32);
+
+        // The helmet is opaque, so only a cutoff above one discards its texels
+        [(0.5, lit), (2.0, background)]
+            .into_iter()
+            .for_each(|(alpha_cutoff, expected)| {
+                fixture.scene.materials.values_mut().for_each(|material| {
+                    material.alpha_mode = crate::scene::AlphaMode::Mask;
+                    material.alpha_cutoff = alpha_cutoff;
+                });
+                let masked = fixture.reimport_and_render();
+                assert_eq!(*masked.get_pixel(32, 32), expected, "cutoff {alpha_cutoff}");
+            });
+    }
+
+    #[test]
+    fn blended_primitives_draw_back_to_front() {
+        let Some(mut fixture) = crate::render::HelmetFixture::new() else {
+            return;
+        };
+        let scene = &mut fixture.scene;
+
+        let mesh_id = scene
+            .graph
+            .node_weights()
+            .flat_map(|node| node.components.iter())
+            .find_map(|component| match component {
+                crate::scene::NodeComponent::Mesh(mesh_id) => Some(mesh_id.clone()),
+                _ => None,
+            })
+            .expect("The helmet should have a mesh!");
+        let helmet_at = |z: f32| crate::scene::Node {
+            id: uuid::Uuid::new_v4().to_string(),
+            label: "Helmet".to_string(),
+            transform: crate::scene::Transform {
+                translation: nalgebra_glm::vec3(0.0, 0.0, z),
+                ..Default::default()
+            },
+            components: vec![crate::scene::NodeComponent::Mesh(mesh_id.clone())],
+        };
+        let near = scene.add_root_node(helmet_at(2.0));
+        let far = scene.add_root_node(helmet_at(-10.0));
+        scene.materials.values_mut().for_each(|material| {
+            material.alpha_mode = crate::scene::AlphaMode::Blend;
+            material.base_color_factor.w = 0.5;
+        });
+        fixture.reimport_and_render();
+
+        // The camera looks down the negative z axis from z = 4
+        let draws = &fixture.renderer.view.draws;
+        assert_eq!(draws.first().map(|draw| draw.node_index), Some(far));
+        assert_eq!(draws.last().map(|draw| draw.node_index), Some(near));
+    }
+
+    #[test]
+    fn scene_shader_variants_compile() {
+        [
+            crate::shader::ShaderDefines::new(),
+            crate::shader::ShaderDefines::from([("BINDLESS".to_string(), String::new())]),
+        ]
+        .iter()
+        .for_each(|defines| {
+            if let Err(error) = super::validate_shader(
+                super::SHADER_PATH,
+                super::SHADER_SOURCE,
+                defines,
+                &super::shader_includes(),
+            ) {
+                panic!("{error}");
+            }
+        });
+    }
+
+    #[test]
+    fn pass_shaders_are_validated_with_the_view_include() {
+        // The scene shader never reads the node id, only the id pass does
+        let includes =
+            super::includes_with_view(super::VIEW_SHADER_SOURCE.replace("node_id", "object_id"));
+        match super::validate_shader(
+            super::SHADER_PATH,
+            super::SHADER_SOURCE,
+            &crate::shader::ShaderDefines::new(),
+            &includes,
+        ) {
+            Err(crate::shader::ShaderError::Parse { path, .. }) => assert_eq!(path, "Id Shader"),
+            result => panic!("The id shader was not rejected: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn camera_views_follow_viewports() {
+        let mut scene = crate::scene::Scene::default();
+        let main_camera = scene.add_root_node(crate::scene::create_camera_node(1.0));
+        let with_viewport = |order| {
+            let mut node = crate::scene::create_camera_node(1.0);
+            node.components.iter_mut().for_each(|component| {
+                if let crate::scene::NodeComponent::Camera(camera) = component {
+                    camera.viewport = Some(crate::scene::CameraViewport {
+                        x: 0.75,
+                        y: 0.0,
+                        width: 0.25,
+                        height: 0.25,
+                        order,
+                    });
+                }
+            });
+            node
+        };
+        scene.add_root_node(with_viewport(2));
+        scene.add_root_node(with_viewport(1));
+
+        // Cameras with their own viewport aren't the active camera, even when visited last
+        assert_eq!(super::active_camera(&scene), Some(main_camera));
+        let orders = super::camera_views(&scene)
+            .iter()
+            .map(|view| view.viewport.order)
+            .collect::<Vec<_>>();
+        assert_eq!(orders, vec![i32::MIN, 1, 2]);
+        assert_eq!(
+            super::camera_views(&scene)[1].viewport.pixel_rect(100, 100),
+            super::PixelRect {
+                x: 75,
+                y: 0,
+                width: 25,
+                height: 25
+            }
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Renders the bundled models headlessly and compares them against reference images.
+//!
+//! References live in `tests/golden` and were rendered with a software rasterizer.
+//! Missing references fail the test, and setting `SERENITY_UPDATE_GOLDEN=1` writes
+//! all of them after adding a case or an intentional rendering change.
+//! On failure, a diff image is written next to the actual rendering in the target directory.
+//!
+//! The test fails without an adapter unless `SERENITY_SKIP_GPU_TESTS=1` is set.
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+
+/// The maximum perceptual difference between two pixels for them to count as equal,
+/// as a fraction of the largest possible difference
+const PIXEL_THRESHOLD: f32 = 0.1;
+
+/// The fraction of pixels allowed to differ before a rendering is rejected
+const MAX_MISMATCHED_PIXELS: f32 = 0.005;
+
+struct GoldenCase {
+    name: &'static str,
+    path: &'static str,
+    /// Camera yaw and pitch around the model in degrees
+    direction: (f32, f32),
+}
+
+const CASES: [GoldenCase; 3] = [
+    GoldenCase {
+        name: "damaged_helmet",
+        path: "resources/models/DamagedHelmet.glb",
+        direction: (0.0, 90.0),
+    },
+    GoldenCase {
+        name: "orientation_test",
+        path: "resources/models/OrientationTest.glb",
+        direction: (30.0, 60.0),
+    },
+    GoldenCase {
+        name: "blocklevel",
+        path: "resources/models/blocklevel.glb",
+        direction: (45.0, 45.0),
+    },
+];
+
+#[test]
+fn rendered_models_match_references() {
+    let Some(mut renderer) = serenity::render::HeadlessRenderer::new(WIDTH, HEIGHT) else {
+        assert!(
+            std::env::var("SERENITY_SKIP_GPU_TESTS").is_ok_and(|value| value == "1"),
+            "No adapter is available, set SERENITY_SKIP_GPU_TESTS=1 to skip golden image tests"
+        );
+        eprintln!("Skipping golden image tests, no adapter is available");
+        return;
+    };
+
+    let update = std::env::var("SERENITY_UPDATE_GOLDEN").is_ok_and(|value| value == "1");
+    let reference_directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let output_directory = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&output_directory).expect("Failed to create golden output directory!");
+
+    let failures = CASES
+        .iter()
+        .filter_map(|case| {
+            let (scene, camera) = load_case(case);
+            renderer.view.import_scene(&scene, &renderer.gpu);
+            let actual = renderer.render_to_image(&scene, camera, WIDTH, HEIGHT);
+
+            let reference_path = reference_directory.join(format!("{}.png", case.name));
+            if update {
+                actual
+                    .save(&reference_path)
+                    .expect("Failed to write reference image!");
+                eprintln!("Wrote reference image {}", reference_path.display());
+                return None;
+            }
+            if !reference_path.exists() {
+                return Some(format!(
+                    "{}: {} is missing, run with SERENITY_UPDATE_GOLDEN=1 to write it",
+                    case.name,
+                    reference_path.display()
+                ));
+            }
+
+            let expected = image::open(&reference_path)
+                .expect("Failed to read reference image!")
+                .to_rgba8();
+            let comparison = compare(&expected, &actual);
+            if comparison.mismatch_ratio <= MAX_MISMATCHED_PIXELS {
+                return None;
+            }
+
+            let actual_path = output_directory.join(format!("{}.actual.png", case.name));
+            let diff_path = output_directory.join(format!("{}.diff.png", case.name));
+            actual
+                .save(&actual_path)
+                .expect("Failed to write actual image!");
+            comparison
+                .diff
+                .save(&diff_path)
+                .expect("Failed to write diff image!");
+            Some(format!(
+                "{}: {:.2}% of pixels differ, see {} and {}",
+                case.name,
+                comparison.mismatch_ratio * 100.0,
+                actual_path.display(),
+                diff_path.display()
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    assert!(
+        failures.is_empty(),
+        "Renderings differ from their references:\n{}",
+        failures.join("\n")
+    );
+}
+
+/// Loads a model and adds a camera framing it from the case's direction
+fn load_case(case: &GoldenCase) -> (serenity::scene::Scene, serenity::petgraph::graph::NodeIndex) {
+    let mut scene = serenity::gltf::import_gltf(case.path);
+    let bounds = scene.bounds().unwrap_or_default();
+
+    let mut camera_node = serenity::scene::create_camera_node(WIDTH as f32 / HEIGHT as f32);
+    if let Some(serenity::scene::NodeComponent::Camera(camera)) = camera_node.components.first_mut()
+    {
+        let orientation = &mut camera.orientation;
+        orientation.offset = bounds.center();
+        orientation.radius = bounds.half_extents().norm() * 1.5;
+        orientation.direction = serenity::nalgebra_glm::vec2(
+            case.direction.0.to_radians(),
+            case.direction.1.to_radians(),
+        );
+        camera_node.transform.translation = orientation.position();
+        camera_node.transform.rotation = orientation.look_at_offset();
+    }
+    let camera = scene.add_root_node(camera_node);
+    (scene, camera)
+}
+
+struct Comparison {
+    mismatch_ratio: f32,
+    diff: image::RgbaImage,
+}
+
+/// Compares images pixel by pixel using the YIQ color difference,
+/// which weighs brightness changes more heavily than hue changes like the human eye does
+fn compare(expected: &image::RgbaImage, actual: &image::RgbaImage) -> Comparison {
+    if expected.dimensions() != actual.dimensions() {
+        return Comparison {
+            mismatch_ratio: 1.0,
+            diff: actual.clone(),
+        };
+    }
+
+    // The largest possible squared YIQ difference, between black and white
+    const MAX_DELTA: f32 = 35215.0;
+
+    let mut mismatched_pixels = 0;
+    let diff = image::RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let expected_pixel = expected.get_pixel(x, y);
+        let actual_pixel = actual.get_pixel(x, y);
+        if yiq_delta(expected_pixel, actual_pixel) > PIXEL_THRESHOLD * PIXEL_THRESHOLD * MAX_DELTA {
+            mismatched_pixels += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            // Faded grayscale of the expected image for context
+            let gray = (255.0 - 0.1 * (255.0 - luminance(expected_pixel))) as u8;
+            image::Rgba([gray, gray, gray, 255])
+        }
+    });
+
+    Comparison {
+        mismatch_ratio: mismatched_pixels as f32 / (actual.width() * actual.height()) as f32,
+        diff,
+    }
+}
+
+fn yiq_delta(first: &image::Rgba<u8>, second: &image::Rgba<u8>) -> f32 {
+    let [r1, g1, b1] = blend_on_white(first);
+    let [r2, g2, b2] = blend_on_white(second);
+    let y = (r1 - r2) * 0.298_895_3 + (g1 - g2) * 0.586_622_5 + (b1 - b2) * 0.114_482_23;
+    let i = (r1 - r2) * 0.595_977_99 - (g1 - g2) * 0.274_176_5 - (b1 - b2) * 0.321_801_5;
+    let q = (r1 - r2) * 0.211_470_19 - (g1 - g2) * 0.522_617_2 + (b1 - b2) * 0.311_146_98;
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+fn luminance(pixel: &image::Rgba<u8>) -> f32 {
+    let [r, g, b] = blend_on_white(pixel);
+    r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_23
+}
+
+fn blend_on_white(pixel: &image::Rgba<u8>) -> [f32; 3] {
+    let alpha = pixel[3] as f32 / 255.0;
+    [0, 1, 2].map(|channel| 255.0 + (pixel[channel] as f32 - 255.0) * alpha)
+}